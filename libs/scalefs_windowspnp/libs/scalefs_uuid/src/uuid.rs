@@ -4,7 +4,7 @@
 use std::str::FromStr;
 
 // NOTE: we break the Uuid's data fields down into four data fields (and annotate the corresponding component labels from RFC 4122; note that RFC 4122 is not a complete modern UUID spec and that we have combined the last three fields into an 8-octet sequence to match convention)
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Uuid {
     pub data1: u32,     // time-low
     pub data2: u16,     // time-mid
@@ -36,6 +36,7 @@ impl Uuid {
 
 //
 
+#[cfg(target_os = "windows")]
 impl From<windows::core::GUID> for Uuid {
     fn from(value: windows::core::GUID) -> Self {
         Self {
@@ -47,6 +48,7 @@ impl From<windows::core::GUID> for Uuid {
     }
 }
 
+#[cfg(target_os = "windows")]
 impl From<Uuid> for windows::core::GUID {
     fn from(value: Uuid) -> Self {
         Self {
@@ -58,6 +60,7 @@ impl From<Uuid> for windows::core::GUID {
     }
 }
 
+#[cfg(target_os = "windows")]
 impl From<windows_sys::core::GUID> for Uuid {
     fn from(value: windows_sys::core::GUID) -> Self {
         Self {
@@ -69,6 +72,7 @@ impl From<windows_sys::core::GUID> for Uuid {
     }
 }
 
+#[cfg(target_os = "windows")]
 impl From<Uuid> for windows_sys::core::GUID {
     fn from(value: Uuid) -> Self {
         Self {
@@ -0,0 +1,61 @@
+// Copyright (c) ScaleFS LLC; used with permission
+// Licensed under the MIT License
+
+use crate::{
+    EnumerateSpecifier,
+    PnpDevicePropertyKey,
+};
+use scalefs_uuid::Uuid;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceClassType {
+    DeviceSetupClass,
+    DeviceInterfaceClass
+}
+
+// a raw (i.e. not yet decoded) property value, as returned by SetupDiGetDevicePropertyW (and friends); for device registry properties, property_type holds the registry data type (e.g. REG_SZ) instead of the device property data type (e.g. DEVPROP_TYPE_STRING)
+#[derive(Clone)]
+pub struct PnpDevicePropertyBuffer {
+    pub property_type: u32,
+    pub buffer: Vec<u8>,
+}
+
+// NOTE: the backend only supplies the raw primitives (one devnode, one property key, one raw property buffer at a time); enumeration options, filtering and property buffer decoding are implemented by PnpEnumerator once for all backends
+// NOTE: all errors are win32 error codes (e.g. ERROR_NOT_FOUND for a property which a devnode doesn't have), so that the enumeration logic treats every backend the same way
+pub trait EnumeratorBackend {
+    // the set of devnodes matching an EnumerateSpecifier (e.g. an HDEVINFO); the backend releases the set when this value is dropped
+    type DeviceInfoSet;
+    // a single devnode within a device info set (e.g. an SP_DEVINFO_DATA)
+    type DeviceNode;
+    // a single device interface within a device info set (e.g. an SP_DEVICE_INTERFACE_DATA)
+    type DeviceInterface;
+
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetclassdevsw
+    fn get_device_info_set(&self, enumerate_specifier: &EnumerateSpecifier) -> Result<Self::DeviceInfoSet, u32>;
+
+    // returns None once device_index is past the last devnode in the set
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdienumdeviceinfo
+    fn enum_device_node(&self, device_info_set: &Self::DeviceInfoSet, device_index: u32) -> Result<Option<Self::DeviceNode>, u32>;
+
+    // returns the device instance id as utf16 chars (without its null terminator)
+    fn get_device_instance_id(&self, device_info_set: &Self::DeviceInfoSet, device_node: &Self::DeviceNode) -> Result<Vec<u16>, u32>;
+
+    // property is one of the SPDRP_* values (e.g. SPDRP_BASE_CONTAINERID)
+    fn get_device_registry_property(&self, device_info_set: &Self::DeviceInfoSet, device_node: &Self::DeviceNode, property: u32) -> Result<PnpDevicePropertyBuffer, u32>;
+
+    fn get_device_instance_property_keys(&self, device_info_set: &Self::DeviceInfoSet, device_node: &Self::DeviceNode) -> Result<Vec<PnpDevicePropertyKey>, u32>;
+    fn get_device_instance_property(&self, device_info_set: &Self::DeviceInfoSet, device_node: &Self::DeviceNode, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32>;
+
+    fn get_device_class_property_keys(&self, class_guid: &Uuid, class_type: DeviceClassType) -> Result<Vec<PnpDevicePropertyKey>, u32>;
+    fn get_device_class_property(&self, class_guid: &Uuid, class_type: DeviceClassType, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32>;
+
+    // returns None if the devnode at device_index is not a device interface of the specified device interface class
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdienumdeviceinterfaces
+    fn enum_device_interface(&self, device_info_set: &Self::DeviceInfoSet, device_interface_class_guid: &Uuid, device_index: u32) -> Result<Option<Self::DeviceInterface>, u32>;
+
+    // returns the device path as utf16 chars (without its null terminator)
+    fn get_device_interface_path(&self, device_info_set: &Self::DeviceInfoSet, device_interface: &Self::DeviceInterface) -> Result<Vec<u16>, u32>;
+
+    fn get_device_interface_property_keys(&self, device_info_set: &Self::DeviceInfoSet, device_interface: &Self::DeviceInterface) -> Result<Vec<PnpDevicePropertyKey>, u32>;
+    fn get_device_interface_property(&self, device_info_set: &Self::DeviceInfoSet, device_interface: &Self::DeviceInterface, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32>;
+}
@@ -1,10 +1,16 @@
 // Copyright (c) ScaleFS LLC; used with permission
 // Licensed under the MIT License
 
+use crate::PnpDevicePropertyKey;
+
 #[derive(Clone)]
 pub enum EnumerateOption {
     IncludeInstanceProperties,
     IncludeDeviceInterfaceClassProperties,
     IncludeDeviceInterfaceProperties,
     IncludeSetupClassProperties,
+    // fetch only these device instance properties (instead of every available one); keys a devnode doesn't have are omitted from its map
+    IncludeInstancePropertyKeys(/*property_keys: */Vec<PnpDevicePropertyKey>),
+    // skip devnodes whose device instance id doesn't start with this prefix (case-insensitive), before any of their properties are fetched
+    DeviceInstanceIdPrefix(/*prefix: */String),
 }
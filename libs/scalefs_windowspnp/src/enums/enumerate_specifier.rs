@@ -1,13 +1,13 @@
 // Copyright (c) ScaleFS LLC; used with permission
 // Licensed under the MIT License
 
-use windows_sys::core::GUID;
+use scalefs_uuid::Uuid;
 
 #[derive(Clone)]
 pub enum EnumerateSpecifier {
     AllDevices,
-    DeviceInterfaceClassGuid(/*device_interface_class_guid: */GUID),
-    DeviceSetupClassGuid(/*device_setup_class_guid: */GUID),
-    PnpDeviceInstanceId(/*device_instance_id: */String, /*device_interface_class_guid: */Option<GUID>),
+    DeviceInterfaceClassGuid(/*device_interface_class_guid: */Uuid),
+    DeviceSetupClassGuid(/*device_setup_class_guid: */Uuid),
+    PnpDeviceInstanceId(/*device_instance_id: */String, /*device_interface_class_guid: */Option<Uuid>),
     PnpEnumeratorId(/*enumerator_id: */String),
 }
\ No newline at end of file
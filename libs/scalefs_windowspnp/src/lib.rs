@@ -1,32 +1,48 @@
 // Copyright (c) ScaleFS LLC; used with permission
 // Licensed under the MIT License
 
-#[cfg(target_os = "windows")]
+// the vendored enumerator keeps its upstream style
+#![allow(
+    clippy::bool_comparison,
+    clippy::enum_variant_names,
+    clippy::len_zero,
+    clippy::let_and_return,
+    clippy::manual_is_multiple_of,
+    clippy::manual_ok_err,
+    clippy::needless_borrow,
+    clippy::needless_late_init,
+    clippy::needless_return
+)]
+
 mod enums;
-#[cfg(target_os = "windows")]
 pub use enums::*;
 
-#[cfg(target_os = "windows")]
 mod errors;
-#[cfg(target_os = "windows")]
 pub use errors::*;
 
-#[cfg(target_os = "windows")]
+mod enumerator_backend;
+pub use enumerator_backend::{DeviceClassType, EnumeratorBackend, PnpDevicePropertyBuffer};
+
+mod memory_backend;
+pub use memory_backend::{MemoryBackend, MemoryDeviceInterface, MemoryDeviceNode};
+
 mod pnp_device_node_info;
-#[cfg(target_os = "windows")]
 pub use pnp_device_node_info::PnpDeviceNodeInfo;
 
-#[cfg(target_os = "windows")]
 mod pnp_device_property_key;
-#[cfg(target_os = "windows")]
 pub use pnp_device_property_key::PnpDevicePropertyKey;
 
-#[cfg(target_os = "windows")]
 mod pnp_device_property_value;
-#[cfg(target_os = "windows")]
 pub use pnp_device_property_value::PnpDevicePropertyValue;
 
-#[cfg(target_os = "windows")]
 mod pnp_enumerator;
-#[cfg(target_os = "windows")]
 pub use pnp_enumerator::PnpEnumerator;
+
+#[cfg(target_os = "windows")]
+mod setupapi_backend;
+#[cfg(target_os = "windows")]
+pub use setupapi_backend::{SetupApiBackend, SetupApiDeviceInfoSet};
+
+mod win32_constants;
+
+pub use scalefs_uuid::Uuid;
@@ -0,0 +1,419 @@
+// Copyright (c) ScaleFS LLC; used with permission
+// Licensed under the MIT License
+
+use crate::{
+    DeviceClassType,
+    EnumerateSpecifier,
+    EnumeratorBackend,
+    PnpDevicePropertyBuffer,
+    PnpDevicePropertyKey,
+    PnpDevicePropertyValue,
+};
+use crate::win32_constants::{
+    DEVPROP_TYPE_BOOLEAN,
+    DEVPROP_TYPE_BYTE,
+    DEVPROP_TYPE_GUID,
+    DEVPROP_TYPE_STRING,
+    DEVPROP_TYPE_UINT16,
+    DEVPROP_TYPE_UINT32,
+    DEVPROP_TYPEMOD_ARRAY,
+    DEVPROP_TYPEMOD_LIST,
+    ERROR_INVALID_DATA,
+    ERROR_NOT_FOUND,
+    REG_SZ,
+    SPDRP_BASE_CONTAINERID,
+    SPDRP_CLASSGUID,
+};
+use scalefs_uuid::Uuid;
+use std::cell::Cell;
+use std::collections::HashMap;
+
+// a devnode in an in-memory device tree
+#[derive(Clone)]
+pub struct MemoryDeviceNode {
+    pub device_instance_id: String,
+    // NOTE: None is reported as the nil guid, just like Windows does for devnodes without a container
+    pub base_container_id: Option<Uuid>,
+    // NOTE: None is reported as ERROR_INVALID_DATA, just like Windows does for root devnodes
+    pub device_setup_class_guid: Option<Uuid>,
+    pub device_instance_properties: HashMap<PnpDevicePropertyKey, PnpDevicePropertyBuffer>,
+    // device interface (only applies to device interfaces; will be None otherwise)
+    pub device_interface: Option<MemoryDeviceInterface>,
+}
+//
+impl MemoryDeviceNode {
+    pub fn new(device_instance_id: &str) -> Self {
+        MemoryDeviceNode {
+            device_instance_id: device_instance_id.to_string(),
+            base_container_id: None,
+            device_setup_class_guid: None,
+            device_instance_properties: HashMap::new(),
+            device_interface: None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MemoryDeviceInterface {
+    pub device_interface_class_guid: Uuid,
+    pub device_path: String,
+    pub device_interface_properties: HashMap<PnpDevicePropertyKey, PnpDevicePropertyBuffer>,
+}
+
+// enumerates an in-memory device tree (instead of the present devnodes); this backend builds on every platform, so the enumeration logic (and its callers) can be exercised without Windows
+pub struct MemoryBackend {
+    device_nodes: Vec<MemoryDeviceNode>,
+    device_class_properties: HashMap<(Uuid, DeviceClassType), HashMap<PnpDevicePropertyKey, PnpDevicePropertyBuffer>>,
+    // the number of property values fetched so far (across all devnodes, classes and interfaces)
+    // NOTE: device registry properties (SPDRP_*) are not counted; every enumerated devnode has its base container id fetched, regardless of the enumerate options
+    property_fetch_count: Cell<usize>,
+}
+//
+impl MemoryBackend {
+    pub fn new(device_nodes: Vec<MemoryDeviceNode>) -> Self {
+        MemoryBackend {
+            device_nodes,
+            device_class_properties: HashMap::new(),
+            property_fetch_count: Cell::new(0),
+        }
+    }
+
+    pub fn set_device_class_property(&mut self, class_guid: Uuid, class_type: DeviceClassType, property_key: PnpDevicePropertyKey, property_buffer: PnpDevicePropertyBuffer) {
+        self.device_class_properties.entry((class_guid, class_type)).or_default().insert(property_key, property_buffer);
+    }
+
+    pub fn property_fetch_count(&self) -> usize {
+        self.property_fetch_count.get()
+    }
+
+    fn fetch_property(&self, properties: Option<&HashMap<PnpDevicePropertyKey, PnpDevicePropertyBuffer>>, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+        self.property_fetch_count.set(self.property_fetch_count.get() + 1);
+
+        match properties.and_then(|properties| properties.get(property_key)) {
+            Some(property_buffer) => Ok(property_buffer.clone()),
+            None => Err(ERROR_NOT_FOUND),
+        }
+    }
+
+    fn device_interface(&self, device_interface: &usize) -> Result<&MemoryDeviceInterface, u32> {
+        match self.device_nodes[*device_interface].device_interface {
+            Some(ref device_interface) => Ok(device_interface),
+            None => Err(ERROR_NOT_FOUND),
+        }
+    }
+}
+
+impl EnumeratorBackend for MemoryBackend {
+    // the indices (into device_nodes) of the devnodes which match the specifier
+    type DeviceInfoSet = Vec<usize>;
+    type DeviceNode = usize;
+    type DeviceInterface = usize;
+
+    fn get_device_info_set(&self, enumerate_specifier: &EnumerateSpecifier) -> Result<Vec<usize>, u32> {
+        let interface_class_matches = |device_node: &MemoryDeviceNode, interface_class_guid: &Uuid| {
+            match device_node.device_interface {
+                Some(ref device_interface) => device_interface.device_interface_class_guid == *interface_class_guid,
+                None => false,
+            }
+        };
+
+        let device_info_set = self.device_nodes.iter().enumerate().filter(|(_, device_node)| {
+            match enumerate_specifier {
+                EnumerateSpecifier::AllDevices => true,
+                EnumerateSpecifier::DeviceInterfaceClassGuid(interface_class_guid) => interface_class_matches(device_node, interface_class_guid),
+                EnumerateSpecifier::DeviceSetupClassGuid(setup_class_guid) => device_node.device_setup_class_guid.as_ref() == Some(setup_class_guid),
+                EnumerateSpecifier::PnpDeviceInstanceId(instance_id, interface_class_guid) => {
+                    device_node.device_instance_id.eq_ignore_ascii_case(instance_id) && match interface_class_guid {
+                        Some(interface_class_guid) => interface_class_matches(device_node, interface_class_guid),
+                        None => true,
+                    }
+                },
+                EnumerateSpecifier::PnpEnumeratorId(enumerator_id) => {
+                    // the enumerator id is the first segment of the device instance id (e.g. "USB" in "USB\VID_045E&PID_0040\6&2B7A5A7F&0&2")
+                    device_node.device_instance_id.split('\\').next().unwrap_or_default().eq_ignore_ascii_case(enumerator_id)
+                },
+            }
+        }).map(|(index, _)| index).collect();
+
+        Ok(device_info_set)
+    }
+
+    fn enum_device_node(&self, device_info_set: &Vec<usize>, device_index: u32) -> Result<Option<usize>, u32> {
+        Ok(device_info_set.get(device_index as usize).copied())
+    }
+
+    fn get_device_instance_id(&self, _device_info_set: &Vec<usize>, device_node: &usize) -> Result<Vec<u16>, u32> {
+        Ok(self.device_nodes[*device_node].device_instance_id.encode_utf16().collect())
+    }
+
+    fn get_device_registry_property(&self, _device_info_set: &Vec<usize>, device_node: &usize, property: u32) -> Result<PnpDevicePropertyBuffer, u32> {
+        let device_node = &self.device_nodes[*device_node];
+        let property_value_as_guid = match property {
+            SPDRP_BASE_CONTAINERID => device_node.base_container_id.clone().unwrap_or(Uuid::from_u128(0)),
+            SPDRP_CLASSGUID => device_node.device_setup_class_guid.clone().ok_or(ERROR_INVALID_DATA)?,
+            _ => return Err(ERROR_INVALID_DATA),
+        };
+
+        // NOTE: Windows reports both of these registry properties as brace-enclosed guid strings
+        let mut property_buffer = PnpDevicePropertyBuffer::from(&PnpDevicePropertyValue::String(format!("{{{}}}", property_value_as_guid)));
+        property_buffer.property_type = REG_SZ;
+
+        Ok(property_buffer)
+    }
+
+    fn get_device_instance_property_keys(&self, _device_info_set: &Vec<usize>, device_node: &usize) -> Result<Vec<PnpDevicePropertyKey>, u32> {
+        Ok(self.device_nodes[*device_node].device_instance_properties.keys().cloned().collect())
+    }
+
+    fn get_device_instance_property(&self, _device_info_set: &Vec<usize>, device_node: &usize, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+        self.fetch_property(Some(&self.device_nodes[*device_node].device_instance_properties), property_key)
+    }
+
+    fn get_device_class_property_keys(&self, class_guid: &Uuid, class_type: DeviceClassType) -> Result<Vec<PnpDevicePropertyKey>, u32> {
+        match self.device_class_properties.get(&(class_guid.clone(), class_type)) {
+            Some(properties) => Ok(properties.keys().cloned().collect()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn get_device_class_property(&self, class_guid: &Uuid, class_type: DeviceClassType, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+        self.fetch_property(self.device_class_properties.get(&(class_guid.clone(), class_type)), property_key)
+    }
+
+    fn enum_device_interface(&self, device_info_set: &Vec<usize>, device_interface_class_guid: &Uuid, device_index: u32) -> Result<Option<usize>, u32> {
+        let Some(device_node) = device_info_set.get(device_index as usize) else {
+            return Ok(None);
+        };
+
+        match self.device_nodes[*device_node].device_interface {
+            Some(ref device_interface) if device_interface.device_interface_class_guid == *device_interface_class_guid => Ok(Some(*device_node)),
+            _ => Ok(None),
+        }
+    }
+
+    fn get_device_interface_path(&self, _device_info_set: &Vec<usize>, device_interface: &usize) -> Result<Vec<u16>, u32> {
+        Ok(self.device_interface(device_interface)?.device_path.encode_utf16().collect())
+    }
+
+    fn get_device_interface_property_keys(&self, _device_info_set: &Vec<usize>, device_interface: &usize) -> Result<Vec<PnpDevicePropertyKey>, u32> {
+        Ok(self.device_interface(device_interface)?.device_interface_properties.keys().cloned().collect())
+    }
+
+    fn get_device_interface_property(&self, _device_info_set: &Vec<usize>, device_interface: &usize, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+        self.fetch_property(Some(&self.device_interface(device_interface)?.device_interface_properties), property_key)
+    }
+}
+
+//
+
+// encodes a property value the way SetupDiGetDevicePropertyW returns it (i.e. the inverse of the property buffer decoding), for populating an in-memory device tree
+impl From<&PnpDevicePropertyValue> for PnpDevicePropertyBuffer {
+    fn from(property_value: &PnpDevicePropertyValue) -> Self {
+        let (property_type, buffer) = match property_value {
+            PnpDevicePropertyValue::ArrayOfValues(values) => {
+                let property_buffers: Vec<PnpDevicePropertyBuffer> = values.iter().map(PnpDevicePropertyBuffer::from).collect();
+                let element_property_type = property_buffers.first().map(|property_buffer| property_buffer.property_type).unwrap_or(DEVPROP_TYPE_BYTE);
+                (element_property_type | DEVPROP_TYPEMOD_ARRAY, property_buffers.into_iter().flat_map(|property_buffer| property_buffer.buffer).collect())
+            },
+            PnpDevicePropertyValue::Boolean(value) => (DEVPROP_TYPE_BOOLEAN, vec![match value { true => 0xFF /* DEVPROP_TRUE */, false => 0x00 }]),
+            PnpDevicePropertyValue::Byte(value) => (DEVPROP_TYPE_BYTE, vec![*value]),
+            PnpDevicePropertyValue::Guid(value) => {
+                // NOTE: guids are stored using native endian (matching the decoding)
+                let mut buffer = Vec::<u8>::with_capacity(16);
+                buffer.extend_from_slice(&value.data1.to_ne_bytes());
+                buffer.extend_from_slice(&value.data2.to_ne_bytes());
+                buffer.extend_from_slice(&value.data3.to_ne_bytes());
+                buffer.extend_from_slice(&value.data4);
+                (DEVPROP_TYPE_GUID, buffer)
+            },
+            PnpDevicePropertyValue::ListOfValues(values) => {
+                // NOTE: this list is effectively a REG_MULTI_SZ; each string is null-terminated and the list is terminated by an additional null terminator
+                let mut buffer: Vec<u8> = values.iter().flat_map(|value| PnpDevicePropertyBuffer::from(value).buffer).collect();
+                buffer.extend_from_slice(&0u16.to_ne_bytes());
+                (DEVPROP_TYPE_STRING | DEVPROP_TYPEMOD_LIST, buffer)
+            },
+            PnpDevicePropertyValue::String(value) => {
+                let buffer = value.encode_utf16().chain(std::iter::once(0)).flat_map(|utf16_char| utf16_char.to_ne_bytes()).collect();
+                (DEVPROP_TYPE_STRING, buffer)
+            },
+            PnpDevicePropertyValue::UInt16(value) => (DEVPROP_TYPE_UINT16, value.to_ne_bytes().to_vec()),
+            PnpDevicePropertyValue::UInt32(value) => (DEVPROP_TYPE_UINT32, value.to_ne_bytes().to_vec()),
+            PnpDevicePropertyValue::UnsupportedPropertyDataType(property_type) => (*property_type, Vec::new()),
+            PnpDevicePropertyValue::UnsupportedRegistryDataType(registry_data_type) => (*registry_data_type, Vec::new()),
+        };
+
+        PnpDevicePropertyBuffer { property_type, buffer }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PnpEnumerator;
+
+    const SYSTEM_CLASS_GUID: u128 = 0x4D36E97D_E325_11CE_BFC1_08002BE10318;
+    const BATTERY_PROPERTY_KEY: PnpDevicePropertyKey = PnpDevicePropertyKey { fmtid: Uuid { data1: 0x104EA319, data2: 0x6EE2, data3: 0x4701, data4: [0xBD, 0x47, 0x8D, 0xDB, 0xF4, 0x25, 0xBB, 0xE5] }, pid: 2 };
+
+    fn property_key(pid: u32) -> PnpDevicePropertyKey {
+        PnpDevicePropertyKey { fmtid: Uuid::from_u128(0xA45C254E_DF1C_4EFD_8020_67D146A850E0), pid }
+    }
+
+    fn system_device_node(device_instance_id: &str, property_count: u32) -> MemoryDeviceNode {
+        let mut device_node = MemoryDeviceNode::new(device_instance_id);
+        device_node.device_setup_class_guid = Some(Uuid::from_u128(SYSTEM_CLASS_GUID));
+        for pid in 0..property_count {
+            device_node.device_instance_properties.insert(property_key(pid), PnpDevicePropertyBuffer::from(&PnpDevicePropertyValue::UInt32(pid)));
+        }
+        device_node
+    }
+
+    // NOTE: benchmark-style; the system class of a real machine holds hundreds of devnodes with dozens of properties each, but only its BTHENUM devnodes carry a battery
+    #[test]
+    fn property_keys_and_prefix_fetch_one_property_per_bthenum_devnode() {
+        const OTHER_DEVICE_NODE_COUNT: usize = 500;
+        const BTHENUM_DEVICE_NODE_COUNT: usize = 8;
+        const PROPERTY_COUNT: u32 = 40;
+
+        let mut device_nodes: Vec<MemoryDeviceNode> = (0..OTHER_DEVICE_NODE_COUNT).map(|index| system_device_node(&format!("ACPI\\PNP0C02\\{}", index), PROPERTY_COUNT)).collect();
+        for index in 0..BTHENUM_DEVICE_NODE_COUNT {
+            let mut device_node = system_device_node(&format!("BTHENUM\\{{0000110B-0000-1000-8000-00805F9B34FB}}_LOCALMFG&0002\\7&1A2B3C4D&0&A4C1385D2B{:02X}_C00000000", index), PROPERTY_COUNT);
+            device_node.device_instance_properties.insert(BATTERY_PROPERTY_KEY, PnpDevicePropertyBuffer::from(&PnpDevicePropertyValue::Byte(50 + index as u8)));
+            // spread the BTHENUM devnodes across the class, like Windows does
+            device_nodes.insert(index * 50, device_node);
+        }
+
+        // every property of every devnode
+        let full_enumerator = PnpEnumerator::new(MemoryBackend::new(device_nodes.clone()));
+        let all_devices = full_enumerator.enumerate_present_devices_by_device_setup_class(Uuid::from_u128(SYSTEM_CLASS_GUID)).ok().expect("enumeration failed");
+        assert_eq!(all_devices.len(), OTHER_DEVICE_NODE_COUNT + BTHENUM_DEVICE_NODE_COUNT);
+        assert_eq!(full_enumerator.backend().property_fetch_count(), (OTHER_DEVICE_NODE_COUNT + BTHENUM_DEVICE_NODE_COUNT) * PROPERTY_COUNT as usize + BTHENUM_DEVICE_NODE_COUNT);
+
+        // only the battery property of the BTHENUM devnodes
+        let enumerator = PnpEnumerator::new(MemoryBackend::new(device_nodes));
+        let bt_devices = enumerator.enumerate_present_devices_by_device_setup_class_with_property_keys(Uuid::from_u128(SYSTEM_CLASS_GUID), "bthenum\\", vec![BATTERY_PROPERTY_KEY]).ok().expect("enumeration failed");
+        assert_eq!(bt_devices.len(), BTHENUM_DEVICE_NODE_COUNT);
+        assert_eq!(enumerator.backend().property_fetch_count(), BTHENUM_DEVICE_NODE_COUNT);
+
+        for (index, bt_device) in bt_devices.iter().enumerate() {
+            let properties = bt_device.device_instance_properties.as_ref().unwrap();
+            assert_eq!(properties.len(), 1);
+            match properties.get(&BATTERY_PROPERTY_KEY) {
+                Some(PnpDevicePropertyValue::Byte(value)) => assert_eq!(*value, 50 + index as u8),
+                _ => panic!("missing battery property for {}", bt_device.device_instance_id),
+            }
+        }
+    }
+
+    // NOTE: PnpDevicePropertyValue implements neither Debug nor PartialEq, so values are compared by their description
+    fn describe(property_value: &PnpDevicePropertyValue) -> String {
+        let describe_all = |values: &Vec<PnpDevicePropertyValue>| values.iter().map(describe).collect::<Vec<String>>().join(", ");
+        match property_value {
+            PnpDevicePropertyValue::ArrayOfValues(values) => format!("ArrayOfValues([{}])", describe_all(values)),
+            PnpDevicePropertyValue::Boolean(value) => format!("Boolean({})", value),
+            PnpDevicePropertyValue::Byte(value) => format!("Byte({})", value),
+            PnpDevicePropertyValue::Guid(value) => format!("Guid({:032X})", value.as_u128()),
+            PnpDevicePropertyValue::ListOfValues(values) => format!("ListOfValues([{}])", describe_all(values)),
+            PnpDevicePropertyValue::String(value) => format!("String({:?})", value),
+            PnpDevicePropertyValue::UInt16(value) => format!("UInt16({})", value),
+            PnpDevicePropertyValue::UInt32(value) => format!("UInt32({})", value),
+            PnpDevicePropertyValue::UnsupportedPropertyDataType(property_type) => format!("UnsupportedPropertyDataType({:#X})", property_type),
+            PnpDevicePropertyValue::UnsupportedRegistryDataType(registry_data_type) => format!("UnsupportedRegistryDataType({:#X})", registry_data_type),
+        }
+    }
+
+    #[test]
+    fn property_values_round_trip_through_the_enumerator() {
+        let string = |value: &str| PnpDevicePropertyValue::String(value.to_string());
+        let guid = Uuid::from_u128(0x0000110B_0000_1000_8000_00805F9B34FB);
+        let property_values = vec![
+            PnpDevicePropertyValue::ArrayOfValues(vec![PnpDevicePropertyValue::Byte(1), PnpDevicePropertyValue::Byte(2)]),
+            PnpDevicePropertyValue::ArrayOfValues(vec![PnpDevicePropertyValue::Boolean(true), PnpDevicePropertyValue::Boolean(false)]),
+            PnpDevicePropertyValue::ArrayOfValues(vec![PnpDevicePropertyValue::Guid(guid.clone()), PnpDevicePropertyValue::Guid(Uuid::from_u128(0))]),
+            PnpDevicePropertyValue::ArrayOfValues(vec![PnpDevicePropertyValue::UInt16(0x045E), PnpDevicePropertyValue::UInt16(0xFFFF)]),
+            PnpDevicePropertyValue::ArrayOfValues(vec![PnpDevicePropertyValue::UInt32(0xDEAD_BEEF)]),
+            PnpDevicePropertyValue::ArrayOfValues(Vec::new()),
+            PnpDevicePropertyValue::Boolean(true),
+            PnpDevicePropertyValue::Boolean(false),
+            PnpDevicePropertyValue::Byte(0),
+            PnpDevicePropertyValue::Byte(255),
+            PnpDevicePropertyValue::Guid(guid),
+            PnpDevicePropertyValue::ListOfValues(vec![string("BTHENUM\\{0000110b-0000-1000-8000-00805f9b34fb}"), string(""), string("BTHENUM\\Dev_A4C1385D2B1E")]),
+            PnpDevicePropertyValue::ListOfValues(Vec::new()),
+            string("Microsoft® 2.4GHz Transceiver v9.0"),
+            string(""),
+            PnpDevicePropertyValue::UInt16(0x045E),
+            PnpDevicePropertyValue::UInt32(0xDEAD_BEEF),
+            // DEVPROP_TYPE_FILETIME
+            PnpDevicePropertyValue::UnsupportedPropertyDataType(0x0000_0010),
+        ];
+
+        let mut device_node = MemoryDeviceNode::new("BTHENUM\\DEV_A4C1385D2B1E\\8&1");
+        for (pid, property_value) in property_values.iter().enumerate() {
+            device_node.device_instance_properties.insert(property_key(pid as u32), PnpDevicePropertyBuffer::from(property_value));
+        }
+        let enumerator = PnpEnumerator::new(MemoryBackend::new(vec![device_node]));
+        let devices = enumerator.enumerate_present_devices_with_options(EnumerateSpecifier::AllDevices, vec![crate::EnumerateOption::IncludeInstanceProperties]).ok().expect("enumeration failed");
+        let device_instance_properties = devices[0].device_instance_properties.as_ref().unwrap();
+
+        assert_eq!(device_instance_properties.len(), property_values.len());
+        for (pid, property_value) in property_values.iter().enumerate() {
+            let decoded_property_value = device_instance_properties.get(&property_key(pid as u32)).map(describe);
+            assert_eq!(decoded_property_value, Some(describe(property_value)));
+        }
+
+        // NOTE: registry data types only ever come from device registry properties (which this backend always reports as REG_SZ strings, see the base container ids in enumerate_specifiers_select_their_devnodes); the buffer just keeps the data type
+        let property_buffer = PnpDevicePropertyBuffer::from(&PnpDevicePropertyValue::UnsupportedRegistryDataType(REG_SZ));
+        assert_eq!((property_buffer.property_type, property_buffer.buffer.len()), (REG_SZ, 0));
+    }
+
+    #[test]
+    fn enumerate_specifiers_select_their_devnodes() {
+        const HID_INTERFACE_CLASS_GUID: u128 = 0x4D1E55B2_F16F_11CF_88CB_001111000030;
+        const HID_CLASS_GUID: u128 = 0x745A17A0_74D3_11D0_B6FE_00A0C90F57DA;
+
+        let mut root_device_node = MemoryDeviceNode::new("ROOT\\SYSTEM\\0000");
+        root_device_node.base_container_id = Some(Uuid::from_u128(0));
+        let mut usb_device_node = MemoryDeviceNode::new("USB\\VID_045E&PID_0040\\6&2B7A5A7F&0&2");
+        usb_device_node.device_setup_class_guid = Some(Uuid::from_u128(HID_CLASS_GUID));
+        usb_device_node.base_container_id = Some(Uuid::from_u128(0x1234));
+        let bthenum_device_node = system_device_node("BTHENUM\\DEV_A4C1385D2B1E\\8&1", 0);
+        let mut hid_device_node = MemoryDeviceNode::new("HID\\VID_046D&PID_B023\\8&2");
+        hid_device_node.device_setup_class_guid = Some(Uuid::from_u128(HID_CLASS_GUID));
+        hid_device_node.device_interface = Some(MemoryDeviceInterface {
+            device_interface_class_guid: Uuid::from_u128(HID_INTERFACE_CLASS_GUID),
+            device_path: "\\\\?\\hid#vid_046d&pid_b023#8&2#{4d1e55b2-f16f-11cf-88cb-001111000030}".to_string(),
+            device_interface_properties: HashMap::new(),
+        });
+        let enumerator = PnpEnumerator::new(MemoryBackend::new(vec![root_device_node, usb_device_node, bthenum_device_node, hid_device_node]));
+
+        let cases = [
+            (EnumerateSpecifier::AllDevices, vec!["ROOT\\SYSTEM\\0000", "USB\\VID_045E&PID_0040\\6&2B7A5A7F&0&2", "BTHENUM\\DEV_A4C1385D2B1E\\8&1", "HID\\VID_046D&PID_B023\\8&2"]),
+            (EnumerateSpecifier::DeviceSetupClassGuid(Uuid::from_u128(HID_CLASS_GUID)), vec!["USB\\VID_045E&PID_0040\\6&2B7A5A7F&0&2", "HID\\VID_046D&PID_B023\\8&2"]),
+            (EnumerateSpecifier::DeviceSetupClassGuid(Uuid::from_u128(SYSTEM_CLASS_GUID)), vec!["BTHENUM\\DEV_A4C1385D2B1E\\8&1"]),
+            (EnumerateSpecifier::DeviceInterfaceClassGuid(Uuid::from_u128(HID_INTERFACE_CLASS_GUID)), vec!["HID\\VID_046D&PID_B023\\8&2"]),
+            (EnumerateSpecifier::DeviceInterfaceClassGuid(Uuid::from_u128(SYSTEM_CLASS_GUID)), vec![]),
+            // device instance ids are case-insensitive
+            (EnumerateSpecifier::PnpDeviceInstanceId("usb\\vid_045e&pid_0040\\6&2b7a5a7f&0&2".to_string(), None), vec!["USB\\VID_045E&PID_0040\\6&2B7A5A7F&0&2"]),
+            (EnumerateSpecifier::PnpDeviceInstanceId("HID\\VID_046D&PID_B023\\8&2".to_string(), Some(Uuid::from_u128(HID_INTERFACE_CLASS_GUID))), vec!["HID\\VID_046D&PID_B023\\8&2"]),
+            (EnumerateSpecifier::PnpDeviceInstanceId("USB\\VID_045E&PID_0040\\6&2B7A5A7F&0&2".to_string(), Some(Uuid::from_u128(HID_INTERFACE_CLASS_GUID))), vec![]),
+            (EnumerateSpecifier::PnpEnumeratorId("bthenum".to_string()), vec!["BTHENUM\\DEV_A4C1385D2B1E\\8&1"]),
+            // the enumerator id is a whole segment, not a prefix
+            (EnumerateSpecifier::PnpEnumeratorId("BTH".to_string()), vec![]),
+        ];
+
+        for (enumerate_specifier, expected_device_instance_ids) in cases {
+            let devices = enumerator.enumerate_present_devices_with_options(enumerate_specifier, Vec::new()).ok().expect("enumeration failed");
+            let device_instance_ids: Vec<&str> = devices.iter().map(|device| device.device_instance_id.as_str()).collect();
+            assert_eq!(device_instance_ids, expected_device_instance_ids);
+        }
+
+        // only device interfaces have a path, and a nil base container id means no container
+        let devices = enumerator.enumerate_present_devices_with_options(EnumerateSpecifier::AllDevices, Vec::new()).ok().expect("enumeration failed");
+        let base_container_ids: Vec<Option<u128>> = devices.iter().map(|device| device.base_container_id.as_ref().map(Uuid::as_u128)).collect();
+        assert_eq!(base_container_ids, vec![None, Some(0x1234), None, None]);
+        assert!(devices.iter().all(|device| device.device_path.is_none()));
+
+        let devices = enumerator.enumerate_present_devices_by_device_interface_class(Uuid::from_u128(HID_INTERFACE_CLASS_GUID)).ok().expect("enumeration failed");
+        assert_eq!(devices[0].device_path.as_deref(), Some("\\\\?\\hid#vid_046d&pid_b023#8&2#{4d1e55b2-f16f-11cf-88cb-001111000030}"));
+    }
+}
@@ -9,11 +9,12 @@ use scalefs_uuid::Uuid;
 //     Win32::UI::Shell::PropertiesSystem::PROPERTYKEY,
 // };
 
-#[derive(PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub struct PnpDevicePropertyKey {
     pub fmtid: Uuid,
     pub pid: u32,
 }
+#[cfg(target_os = "windows")]
 impl PnpDevicePropertyKey {
     pub fn to_devpropkey(&self) -> windows_sys::Win32::Devices::Properties::DEVPROPKEY {
         windows_sys::Win32::Devices::Properties::DEVPROPKEY {
@@ -22,6 +23,7 @@ impl PnpDevicePropertyKey {
         }
     }
 }
+#[cfg(target_os = "windows")]
 impl From<windows_sys::Win32::Devices::Properties::DEVPROPKEY> for PnpDevicePropertyKey {
     fn from(item: windows_sys::Win32::Devices::Properties::DEVPROPKEY) -> Self {
         PnpDevicePropertyKey {
@@ -30,6 +32,7 @@ impl From<windows_sys::Win32::Devices::Properties::DEVPROPKEY> for PnpDeviceProp
         }
     }
 }
+#[cfg(target_os = "windows")]
 impl From<windows_sys::Win32::UI::Shell::PropertiesSystem::PROPERTYKEY> for PnpDevicePropertyKey {
     fn from(item: windows_sys::Win32::UI::Shell::PropertiesSystem::PROPERTYKEY) -> Self {
         PnpDevicePropertyKey {
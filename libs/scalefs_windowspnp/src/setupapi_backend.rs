@@ -0,0 +1,545 @@
+// Copyright (c) ScaleFS LLC; used with permission
+// Licensed under the MIT License
+
+use crate::{
+    DeviceClassType,
+    EnumerateSpecifier,
+    EnumeratorBackend,
+    PnpDevicePropertyBuffer,
+    PnpDevicePropertyKey,
+};
+use scalefs_common::win32_utils;
+use scalefs_primitives::defer;
+use scalefs_uuid::Uuid;
+use windows::{
+    Win32::Devices::DeviceAndDriverInstallation::{
+        DIGCF_ALLCLASSES, DIGCF_DEVICEINTERFACE, DIGCF_PRESENT
+    },
+    Win32::Foundation::{
+        ERROR_INVALID_DATA, ERROR_INSUFFICIENT_BUFFER, ERROR_NO_MORE_ITEMS,
+    },
+};
+use windows_sys::{
+    core::GUID,
+    Win32::Devices::DeviceAndDriverInstallation::{
+        DICLASSPROP_INSTALLER,
+        DICLASSPROP_INTERFACE,
+        HDEVINFO,
+        SP_DEVICE_INTERFACE_DATA,
+        SP_DEVICE_INTERFACE_DETAIL_DATA_W,
+        SP_DEVINFO_DATA,
+        SetupDiDestroyDeviceInfoList,
+        SetupDiEnumDeviceInfo,
+        SetupDiEnumDeviceInterfaces,
+        SetupDiGetClassDevsW,
+        SetupDiGetDeviceInterfaceDetailW,
+        SetupDiGetDeviceInterfacePropertyKeys,
+        SetupDiGetDeviceInterfacePropertyW,
+        SetupDiGetClassPropertyKeys,
+        SetupDiGetClassPropertyW,
+        SetupDiGetDeviceInstanceIdW,
+        SetupDiGetDevicePropertyKeys,
+        SetupDiGetDevicePropertyW,
+        SetupDiGetDeviceRegistryPropertyW,
+    },
+    Win32::Devices::Properties::DEVPROPKEY,
+    Win32::Foundation::INVALID_HANDLE_VALUE,
+};
+
+// enumerates the present devnodes using the Win32 setup API
+#[derive(Default)]
+pub struct SetupApiBackend {
+}
+//
+impl SetupApiBackend {
+    pub fn new() -> Self {
+        SetupApiBackend { }
+    }
+}
+
+pub struct SetupApiDeviceInfoSet {
+    handle: HDEVINFO,
+}
+//
+impl Drop for SetupApiDeviceInfoSet {
+    fn drop(&mut self) {
+        // NOTE: we must clean up the device info set created by SetupDiGetClassDevsW
+        let destroy_result = unsafe { SetupDiDestroyDeviceInfoList(self.handle) };
+        debug_assert!(destroy_result != 0, "Could not clean up device info set; win32 error: {}", win32_utils::get_last_error_as_win32_error().0);
+    }
+}
+
+impl EnumeratorBackend for SetupApiBackend {
+    type DeviceInfoSet = SetupApiDeviceInfoSet;
+    type DeviceNode = SP_DEVINFO_DATA;
+    type DeviceInterface = SP_DEVICE_INTERFACE_DATA;
+
+    fn get_device_info_set(&self, enumerate_specifier: &EnumerateSpecifier) -> Result<SetupApiDeviceInfoSet, u32> {
+        // configure our variables based on the enumerate specifier
+        // NOTE: the class guid is held in this scope (rather than in the match arms) so that it remains alive until SetupDiGetClassDevsW has used the pointer to it
+        let pnp_enumerator: Option<String>;
+        let class_guid: Option<GUID>;
+        let mut flags = DIGCF_PRESENT;
+        match enumerate_specifier {
+            EnumerateSpecifier::AllDevices => {
+                pnp_enumerator = None;
+                class_guid = None;
+                flags |= DIGCF_ALLCLASSES;
+            },
+            EnumerateSpecifier::DeviceInterfaceClassGuid(interface_class_guid) => {
+                pnp_enumerator = None;
+                class_guid = Some(GUID::from(interface_class_guid.clone()));
+                flags |= DIGCF_DEVICEINTERFACE;
+            },
+            EnumerateSpecifier::DeviceSetupClassGuid(setup_class_guid) => {
+                pnp_enumerator = None;
+                class_guid = Some(GUID::from(setup_class_guid.clone()));
+                // flags |= 0;
+            },
+            EnumerateSpecifier::PnpDeviceInstanceId(instance_id, _) => {
+                pnp_enumerator = Some(instance_id.clone());
+                class_guid = None;
+                flags |= DIGCF_DEVICEINTERFACE | DIGCF_ALLCLASSES;
+            },
+            EnumerateSpecifier::PnpEnumeratorId(enumerator_id) => {
+                pnp_enumerator = Some(enumerator_id.clone());
+                class_guid = None;
+                flags |= DIGCF_ALLCLASSES;
+            }
+        };
+
+        // see: https://docs.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetclassdevsw
+        // NOTE: due to the way that the SetupDiGetClassDevsW is declared in windows-rs, we need to pass it a PCWSTR which wraps a Vec<u16>; since the underlying vector cannot be garbage collected before the PCWSTR is used, we create it here (in this scope)
+        let pnp_enumerator_as_utf16_chars: Vec<u16>; // NOTE: critically, we create the utf16 chars vector here so that it remains in scope during this function call (i.e. after we create a pointer to it).
+                                                     //       DO NOT move this variable into the "let pnp_enumerator_as_pwstr = match" block
+        let pnp_enumerator_as_pwstr = match pnp_enumerator {
+            Some(value) => {
+                pnp_enumerator_as_utf16_chars = (value + "\0").encode_utf16().collect(); // NOTE: critically, we assign the underlying vector to a variable which will remain in scope during this function call
+                pnp_enumerator_as_utf16_chars.as_ptr()
+            },
+            None => {
+                std::ptr::null()
+            }
+        };
+        //
+        let handle_to_device_info_set: HDEVINFO;
+        if let Some(ref some_class_guid) = class_guid {
+            handle_to_device_info_set = unsafe { SetupDiGetClassDevsW(some_class_guid, pnp_enumerator_as_pwstr, std::ptr::null_mut(), flags.0) };
+        } else {
+            handle_to_device_info_set = unsafe { SetupDiGetClassDevsW(std::ptr::null_mut(), pnp_enumerator_as_pwstr, std::ptr::null_mut(), flags.0) };
+        }
+        if handle_to_device_info_set as isize == INVALID_HANDLE_VALUE as isize {
+            let win32_error = win32_utils::get_last_error_as_win32_error();
+            return Err(win32_error.0);
+        }
+
+        Ok(SetupApiDeviceInfoSet { handle: handle_to_device_info_set })
+    }
+
+    fn enum_device_node(&self, device_info_set: &SetupApiDeviceInfoSet, device_index: u32) -> Result<Option<SP_DEVINFO_DATA>, u32> {
+        let mut devinfo_data: SP_DEVINFO_DATA = SP_DEVINFO_DATA { cbSize: 0, ClassGuid: GUID::from_u128(0), DevInst: 0, Reserved: 0 };
+        devinfo_data.cbSize = std::mem::size_of::<SP_DEVINFO_DATA>() as u32;
+        //
+        // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdienumdeviceinfo
+        let enum_device_info_result = unsafe { SetupDiEnumDeviceInfo(device_info_set.handle, device_index, &mut devinfo_data) };
+        if enum_device_info_result == 0 {
+            let win32_error = win32_utils::get_last_error_as_win32_error();
+            if win32_error == ERROR_NO_MORE_ITEMS {
+                // we are out of items to enumerate
+                return Ok(None);
+            }
+
+            return Err(win32_error.0);
+        }
+
+        Ok(Some(devinfo_data))
+    }
+
+    fn get_device_instance_id(&self, device_info_set: &SetupApiDeviceInfoSet, device_node: &SP_DEVINFO_DATA) -> Result<Vec<u16>, u32> {
+        get_device_instance_id_from_devinfo_data(device_info_set.handle, device_node)
+    }
+
+    fn get_device_registry_property(&self, device_info_set: &SetupApiDeviceInfoSet, device_node: &SP_DEVINFO_DATA, property: u32) -> Result<PnpDevicePropertyBuffer, u32> {
+        let mut devinfo_data = *device_node;
+        get_device_registry_property_buffer(device_info_set.handle, &mut devinfo_data, property)
+    }
+
+    fn get_device_instance_property_keys(&self, device_info_set: &SetupApiDeviceInfoSet, device_node: &SP_DEVINFO_DATA) -> Result<Vec<PnpDevicePropertyKey>, u32> {
+        let mut devinfo_data = *device_node;
+        let property_keys = get_device_instance_property_keys(device_info_set.handle, &mut devinfo_data)?;
+        Ok(property_keys.into_iter().map(PnpDevicePropertyKey::from).collect())
+    }
+
+    fn get_device_instance_property(&self, device_info_set: &SetupApiDeviceInfoSet, device_node: &SP_DEVINFO_DATA, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+        let mut devinfo_data = *device_node;
+        get_device_instance_property_buffer(device_info_set.handle, &mut devinfo_data, property_key)
+    }
+
+    fn get_device_class_property_keys(&self, class_guid: &Uuid, class_type: DeviceClassType) -> Result<Vec<PnpDevicePropertyKey>, u32> {
+        let class_guid = GUID::from(class_guid.clone());
+        let property_keys = get_device_class_property_keys(&class_guid, class_type)?;
+        Ok(property_keys.into_iter().map(PnpDevicePropertyKey::from).collect())
+    }
+
+    fn get_device_class_property(&self, class_guid: &Uuid, class_type: DeviceClassType, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+        let class_guid = GUID::from(class_guid.clone());
+        get_device_class_property_buffer(&class_guid, class_type, property_key)
+    }
+
+    fn enum_device_interface(&self, device_info_set: &SetupApiDeviceInfoSet, device_interface_class_guid: &Uuid, device_index: u32) -> Result<Option<SP_DEVICE_INTERFACE_DATA>, u32> {
+        let device_interface_class_guid = GUID::from(device_interface_class_guid.clone());
+
+        let mut device_interface_data = SP_DEVICE_INTERFACE_DATA { cbSize: 0, InterfaceClassGuid: GUID::from_u128(0), Flags: 0, Reserved: 0 };
+        device_interface_data.cbSize = std::mem::size_of::<SP_DEVICE_INTERFACE_DATA>() as u32;
+        //
+        // retrieve an SP_DEVICE_INTERFACE_DATA instance which identifies an interface which meets our search criteria
+        // https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdienumdeviceinterfaces
+        let enum_device_interfaces_result = unsafe { SetupDiEnumDeviceInterfaces(device_info_set.handle, std::ptr::null(), &device_interface_class_guid, device_index, &mut device_interface_data) };
+        if enum_device_interfaces_result == 0 {
+            let win32_error = win32_utils::get_last_error_as_win32_error();
+            if win32_error == ERROR_NO_MORE_ITEMS {
+                // we have reached the end of our list successfully OR this devnode is not a device interface
+                return Ok(None);
+            }
+
+            return Err(win32_error.0);
+        }
+
+        Ok(Some(device_interface_data))
+    }
+
+    fn get_device_interface_path(&self, device_info_set: &SetupApiDeviceInfoSet, device_interface: &SP_DEVICE_INTERFACE_DATA) -> Result<Vec<u16>, u32> {
+        get_device_path_from_device_interface_detail_data(device_info_set.handle, device_interface)
+    }
+
+    fn get_device_interface_property_keys(&self, device_info_set: &SetupApiDeviceInfoSet, device_interface: &SP_DEVICE_INTERFACE_DATA) -> Result<Vec<PnpDevicePropertyKey>, u32> {
+        let mut device_interface_data = *device_interface;
+        let property_keys = get_device_interface_property_keys(device_info_set.handle, &mut device_interface_data)?;
+        Ok(property_keys.into_iter().map(PnpDevicePropertyKey::from).collect())
+    }
+
+    fn get_device_interface_property(&self, device_info_set: &SetupApiDeviceInfoSet, device_interface: &SP_DEVICE_INTERFACE_DATA, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+        let mut device_interface_data = *device_interface;
+        get_device_interface_property_buffer(device_info_set.handle, &mut device_interface_data, property_key)
+    }
+}
+
+//
+
+fn get_device_instance_id_from_devinfo_data(handle_to_device_info_set: HDEVINFO, devinfo_data: &SP_DEVINFO_DATA) -> Result<Vec<u16>, u32> {
+    // get the size of the device instance id, null-terminated, as a count of utf-16 characters; we'll get an error code of ERROR_INSUFFICIENT_BUFFER and the required_size prarameter will contain the required size
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetdeviceinstanceidw
+    let mut required_size: u32 = 0;
+    let get_device_instance_id_result = unsafe { SetupDiGetDeviceInstanceIdW(handle_to_device_info_set, devinfo_data, std::ptr::null_mut() /* null */, 0, &mut required_size) };
+    if get_device_instance_id_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        if win32_error == ERROR_INSUFFICIENT_BUFFER {
+            // this is the expected error (i.e. the error we intentionally induced); continue
+        } else {
+            // otherwise, return the error to our caller
+            return Err(win32_error.0);
+        }
+    } else {
+        debug_assert!(false, "SetupDiGetDeviceInstanceIdW returned success when we asked it for the required buffer size; it should always return false in this circumstance (since device ids are null terminated and can therefore never be zero bytes in length)");
+        return Err(ERROR_INVALID_DATA.0);
+    }
+    //
+    if required_size == 0 {
+        debug_assert!(false, "Device instance ID has zero bytes (and is required to have at least one byte...the null terminator); aborting.");
+        return Err(ERROR_INVALID_DATA.0);
+    }
+    //
+    // allocate memory for the device instance id via a zeroed utf16 vector; then create a PWSTR instance which uses that vector as its mutable data region
+    let mut device_instance_id_as_utf16_chars = Vec::<u16>::with_capacity(required_size as usize);
+    device_instance_id_as_utf16_chars.resize(device_instance_id_as_utf16_chars.capacity(), 0);
+    let device_instance_id_as_pwstr = device_instance_id_as_utf16_chars.as_mut_ptr();
+    //
+    // get the device instance id as a PWSTR
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetdeviceinstanceidw
+    let get_device_instance_id_result = unsafe { SetupDiGetDeviceInstanceIdW(handle_to_device_info_set, devinfo_data, device_instance_id_as_pwstr, required_size, std::ptr::null_mut()) };
+    if get_device_instance_id_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        return Err(win32_error.0);
+    }
+    // NOTE: the device instance id is null-terminated, so we omit the final character (e.g. '\0')
+    device_instance_id_as_utf16_chars.truncate((required_size as usize) - 1);
+
+    Ok(device_instance_id_as_utf16_chars)
+}
+
+//
+
+fn check_setup_di_get_xxx_property_keys_required_size_result(setup_di_get_xxx_property_keys_result: i32, required_property_key_count: u32) -> Result<(), u32> {
+    if setup_di_get_xxx_property_keys_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        match win32_error {
+            ERROR_INSUFFICIENT_BUFFER => {
+                // this is the expected error condition; we'll resize our buffer to match required_property_key_count
+            },
+            _ => {
+                return Err(win32_error.0);
+            }
+        }
+    } else {
+        // return an error if required_property_key_count is non-zero; otherwise, continue with the understanding that the property has a size of zero
+        if required_property_key_count > 0 {
+            // we don't expect the operation to succeed with a null buffer and zero-length buffer size (unless there are no elements to return)
+            debug_assert!(false, "SetupDiGetXXXPropertyKeysW succeeded, even though we passed it no buffer.");
+
+            return Err(ERROR_INVALID_DATA.0);
+        }
+    }
+
+    Ok(())
+}
+
+fn get_device_class_property_keys(class_guid: *const GUID, class_type: DeviceClassType) -> Result<Vec<DEVPROPKEY>, u32> {
+    let flags: u32;
+    match class_type {
+        DeviceClassType::DeviceSetupClass => {
+            flags = DICLASSPROP_INSTALLER;
+        },
+        DeviceClassType::DeviceInterfaceClass => {
+            flags = DICLASSPROP_INTERFACE;
+        }
+    }
+
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetclasspropertykeys
+    let mut required_property_key_count: u32 = 0;
+    let get_class_property_keys_result = unsafe { SetupDiGetClassPropertyKeys(class_guid, std::ptr::null_mut(), 0, &mut required_property_key_count, flags) };
+    check_setup_di_get_xxx_property_keys_required_size_result(get_class_property_keys_result, required_property_key_count)?;
+
+    // retrieve the property keys
+    let mut property_keys_buffer: Vec::<DEVPROPKEY> = Vec::with_capacity(required_property_key_count as usize);
+    property_keys_buffer.resize(property_keys_buffer.capacity(), DEVPROPKEY { fmtid: GUID::from_u128(0), pid: 0 });
+    //
+    let get_class_property_keys_result = unsafe { SetupDiGetClassPropertyKeys(class_guid, property_keys_buffer.as_mut_ptr() as *mut DEVPROPKEY, required_property_key_count, std::ptr::null_mut(), flags) };
+    if get_class_property_keys_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        return Err(win32_error.0);
+    }
+
+    Ok(property_keys_buffer)
+}
+
+fn get_device_instance_property_keys(device_info_set: HDEVINFO, devinfo_data: *mut SP_DEVINFO_DATA) -> Result<Vec<DEVPROPKEY>, u32> {
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetdevicepropertykeys
+    let mut required_property_key_count: u32 = 0;
+    let get_device_property_keys_result = unsafe { SetupDiGetDevicePropertyKeys(device_info_set, devinfo_data, std::ptr::null_mut(), 0, &mut required_property_key_count, 0) };
+    check_setup_di_get_xxx_property_keys_required_size_result(get_device_property_keys_result, required_property_key_count)?;
+
+    // retrieve the property keys
+    let mut property_keys_buffer: Vec::<DEVPROPKEY> = Vec::with_capacity(required_property_key_count as usize);
+    property_keys_buffer.resize(property_keys_buffer.capacity(), DEVPROPKEY { fmtid: GUID::from_u128(0), pid: 0 });
+    //
+    let get_device_property_keys_result = unsafe { SetupDiGetDevicePropertyKeys(device_info_set, devinfo_data, property_keys_buffer.as_mut_ptr() as *mut DEVPROPKEY, required_property_key_count, std::ptr::null_mut(), 0) };
+    if get_device_property_keys_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        return Err(win32_error.0);
+    }
+
+    Ok(property_keys_buffer)
+}
+
+fn get_device_interface_property_keys(device_info_set: HDEVINFO, device_interface_data: *mut SP_DEVICE_INTERFACE_DATA) -> Result<Vec<DEVPROPKEY>, u32> {
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetdeviceinterfacepropertykeys
+    let mut required_property_key_count: u32 = 0;
+    let get_device_interface_property_keys_result = unsafe { SetupDiGetDeviceInterfacePropertyKeys(device_info_set, device_interface_data, std::ptr::null_mut(), 0, &mut required_property_key_count, 0) };
+    check_setup_di_get_xxx_property_keys_required_size_result(get_device_interface_property_keys_result, required_property_key_count)?;
+
+    // retrieve the property keys
+    let mut property_keys_buffer: Vec::<DEVPROPKEY> = Vec::with_capacity(required_property_key_count as usize);
+    property_keys_buffer.resize(property_keys_buffer.capacity(), DEVPROPKEY { fmtid: GUID::from_u128(0), pid: 0 });
+    //
+    let get_device_interface_property_keys_result = unsafe { SetupDiGetDeviceInterfacePropertyKeys(device_info_set, device_interface_data, property_keys_buffer.as_mut_ptr() as *mut DEVPROPKEY, required_property_key_count, std::ptr::null_mut(), 0) };
+    if get_device_interface_property_keys_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        return Err(win32_error.0);
+    }
+
+    Ok(property_keys_buffer)
+}
+
+//
+
+fn check_setup_di_get_device_xxx_property_required_size_result(setup_di_get_device_xxx_property_result: i32, required_size: u32) -> Result<(), u32> {
+    if setup_di_get_device_xxx_property_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        match win32_error {
+            ERROR_INSUFFICIENT_BUFFER => {
+                // this is the expected error condition; we'll resize our buffer to match required_size
+            },
+            _ => {
+                return Err(win32_error.0);
+            }
+        }
+    } else {
+        // we don't expect the operation to succeed with a null buffer and zero-length buffer size (as all known/supported property types have a non-zero length).
+        debug_assert!(false, "SetupDiGetDeviceXXXPropertyW succeeded, even though we passed it no buffer.");
+
+        // return an error if requiredSize is non-zero; otherwise, continue with the understanding that the property has a size of zero
+        if required_size > 0 {
+            return Err(ERROR_INVALID_DATA.0);
+        }
+    }
+
+    Ok(())
+}
+
+fn get_device_class_property_buffer(class_guid: *const GUID, class_type: DeviceClassType, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+    let flags: u32;
+    match class_type {
+        DeviceClassType::DeviceSetupClass => {
+            flags = DICLASSPROP_INSTALLER;
+        },
+        DeviceClassType::DeviceInterfaceClass => {
+            flags = DICLASSPROP_INTERFACE;
+        }
+    }
+    //
+    let property_key_as_devpropkey = property_key.to_devpropkey();
+
+    // get the type and size of the device setup/interface class property
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetclasspropertyw
+    let mut property_type: u32 = 0;
+    let mut required_size: u32 = 0;
+    let get_class_property_result = unsafe { SetupDiGetClassPropertyW(class_guid, &property_key_as_devpropkey, &mut property_type, std::ptr::null_mut(), 0, &mut required_size, flags) };
+    check_setup_di_get_device_xxx_property_required_size_result(get_class_property_result, required_size)?;
+
+    // retrieve the property value
+    let mut property_buffer = Vec::<u8>::with_capacity(required_size as usize);
+    property_buffer.resize(property_buffer.capacity(), 0);
+    //
+    let get_class_property_result = unsafe { SetupDiGetClassPropertyW(class_guid, &property_key_as_devpropkey, &mut property_type, property_buffer.as_mut_ptr() as *mut u8, required_size, std::ptr::null_mut(), flags) };
+    if get_class_property_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        return Err(win32_error.0);
+    }
+
+    Ok(PnpDevicePropertyBuffer { property_type, buffer: property_buffer })
+}
+
+fn get_device_instance_property_buffer(device_info_set: HDEVINFO, devinfo_data: *mut SP_DEVINFO_DATA, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+    let property_key_as_devpropkey = property_key.to_devpropkey();
+
+    // get the type and size of the device instance property
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetdevicepropertyw
+    let mut property_type: u32 = 0;
+    let mut required_size: u32 = 0;
+    let get_device_property_result = unsafe { SetupDiGetDevicePropertyW(device_info_set, devinfo_data, &property_key_as_devpropkey, &mut property_type, std::ptr::null_mut(), 0, &mut required_size, 0) };
+    check_setup_di_get_device_xxx_property_required_size_result(get_device_property_result, required_size)?;
+
+    // retrieve the property value
+    let mut property_buffer = Vec::<u8>::with_capacity(required_size as usize);
+    property_buffer.resize(property_buffer.capacity(), 0);
+    //
+    let get_device_property_result = unsafe { SetupDiGetDevicePropertyW(device_info_set, devinfo_data, &property_key_as_devpropkey, &mut property_type, property_buffer.as_mut_ptr() as *mut u8, required_size, std::ptr::null_mut(), 0) };
+    if get_device_property_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        return Err(win32_error.0);
+    }
+
+    Ok(PnpDevicePropertyBuffer { property_type, buffer: property_buffer })
+}
+
+fn get_device_interface_property_buffer(device_info_set: HDEVINFO, device_interface_data: *mut SP_DEVICE_INTERFACE_DATA, property_key: &PnpDevicePropertyKey) -> Result<PnpDevicePropertyBuffer, u32> {
+    let property_key_as_devpropkey = property_key.to_devpropkey();
+
+    // get the type and size of the device interface property
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetdeviceinterfacepropertyw
+    let mut property_type: u32 = 0;
+    let mut required_size: u32 = 0;
+    let get_device_interface_property_result = unsafe { SetupDiGetDeviceInterfacePropertyW(device_info_set, device_interface_data, &property_key_as_devpropkey, &mut property_type, std::ptr::null_mut(), 0, &mut required_size, 0) };
+    check_setup_di_get_device_xxx_property_required_size_result(get_device_interface_property_result, required_size)?;
+
+    // retrieve the property value
+    let mut property_buffer = Vec::<u8>::with_capacity(required_size as usize);
+    property_buffer.resize(property_buffer.capacity(), 0);
+    //
+    let get_device_interface_property_result = unsafe { SetupDiGetDeviceInterfacePropertyW(device_info_set, device_interface_data, &property_key_as_devpropkey, &mut property_type, property_buffer.as_mut_ptr() as *mut u8, required_size, std::ptr::null_mut(), 0) };
+    if get_device_interface_property_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        return Err(win32_error.0);
+    }
+
+    Ok(PnpDevicePropertyBuffer { property_type, buffer: property_buffer })
+}
+
+fn get_device_registry_property_buffer(device_info_set: HDEVINFO, device_info_data: *mut SP_DEVINFO_DATA, property_key: u32) -> Result<PnpDevicePropertyBuffer, u32> {
+    // get the type and size of the device registry property
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetdeviceregistrypropertyw
+    let mut property_registry_data_type_as_u32: u32 = 0;
+    let mut required_size: u32 = 0;
+    let get_device_registry_property_result = unsafe { SetupDiGetDeviceRegistryPropertyW(device_info_set, device_info_data, property_key, &mut property_registry_data_type_as_u32, std::ptr::null_mut(), 0, &mut required_size) };
+    check_setup_di_get_device_xxx_property_required_size_result(get_device_registry_property_result, required_size)?;
+
+    // retrieve the property value
+    let mut property_buffer = Vec::<u8>::with_capacity(required_size as usize);
+    property_buffer.resize(property_buffer.capacity(), 0);
+    //
+    let get_device_registry_property_result = unsafe { SetupDiGetDeviceRegistryPropertyW(device_info_set, device_info_data, property_key, &mut property_registry_data_type_as_u32, property_buffer.as_mut_ptr() as *mut u8, required_size, std::ptr::null_mut()) };
+    if get_device_registry_property_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        return Err(win32_error.0);
+    }
+
+    Ok(PnpDevicePropertyBuffer { property_type: property_registry_data_type_as_u32, buffer: property_buffer })
+}
+
+//
+
+fn get_device_path_from_device_interface_detail_data(handle_to_device_info_set: HDEVINFO, device_interface_data: &SP_DEVICE_INTERFACE_DATA) -> Result<Vec<u16>, u32> {
+    let mut device_path_as_utf16_chars: Vec<u16>;
+
+    // get the size of the SP_DEVICE_INTERFACE_DETAIL_DATA_W structure required to contain the device path; we'll get an error code of ERROR_INSUFFICIENT_BUFFER and the required_size parameter will contain the required size
+    // see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetdeviceinterfacedetailw
+    let mut required_size: u32 = 0;
+    let get_device_interface_detail_result = unsafe { SetupDiGetDeviceInterfaceDetailW(handle_to_device_info_set, device_interface_data, std::ptr::null_mut(), 0, &mut required_size, std::ptr::null_mut()) };
+    if get_device_interface_detail_result == 0 {
+        let win32_error = win32_utils::get_last_error_as_win32_error();
+        if win32_error == ERROR_INSUFFICIENT_BUFFER {
+            // this is the expected error (i.e. the error we intentionally induced); continue
+        } else {
+            // otherwise, return the error to our caller
+            return Err(win32_error.0);
+        }
+    } else {
+        debug_assert!(false, "SetupDiGetDeviceInterfaceDetailW returned success when we asked it for the required buffer size; it should always return false in this circumstance");
+        return Err(ERROR_INVALID_DATA.0);
+    }
+    //
+    // manually allocate memory for the SP_DEVICE_INTERFACE_DETAIL_DATA_W struct (as it has an ANYSIZE_ARRAY for the [u16] DevicePath)
+    let size_of_struct = match std::mem::size_of::<usize>() {
+        4 => (std::mem::size_of::<u32>() + std::mem::size_of::<u16>()) as u32,
+        _ => std::mem::size_of::<SP_DEVICE_INTERFACE_DETAIL_DATA_W>() as u32 // NOTE: Jan Axelson's "USB Complete 5th ed., p. 253" says to use a size of 8 for 64-bit Windows; if we get errors, we may choose to manually set this to 8 in the future
+    };
+    //
+    let device_interface_detail_data = unsafe { libc::malloc(required_size as usize) as *mut SP_DEVICE_INTERFACE_DETAIL_DATA_W };
+    unsafe { (*device_interface_detail_data).cbSize = size_of_struct; }
+    {
+        // free the manually-allocated device_interface_detail_data as soon as we're done using it
+        defer! {
+            unsafe { libc::free(device_interface_detail_data as *mut ::std::os::raw::c_void) };
+        }
+
+        let get_device_interface_detail_result = unsafe { SetupDiGetDeviceInterfaceDetailW(handle_to_device_info_set, device_interface_data, device_interface_detail_data, required_size, std::ptr::null_mut(), std::ptr::null_mut()) };
+        if get_device_interface_detail_result == 0 {
+            let win32_error = win32_utils::get_last_error_as_win32_error();
+            return Err(win32_error.0);
+        }
+
+        // sanity check: required_size must be greater than 6 (32-bit) or 10 (64-bit)
+        if (required_size as usize) < (std::mem::size_of::<u32>() /* sizeof(.cbSize) */ + std::mem::size_of::<u16>() /* sizeof(u16...null terminator) */) {
+            return Err(ERROR_INVALID_DATA.0);
+        }
+
+        // copy the device path to a utf16 vector (so that our caller can then convert it to a string)
+        let device_path_length_in_bytes = (required_size as usize) - std::mem::size_of::<u32>() /* sizeof(.cbSize) */ - std::mem::size_of::<u16>() /* sizeof(u16...null terminator) */;
+        device_path_as_utf16_chars = Vec::<u16>::with_capacity(device_path_length_in_bytes / 2);
+        device_path_as_utf16_chars.resize(device_path_as_utf16_chars.capacity(), 0);
+        //
+        unsafe { std::ptr::copy_nonoverlapping((*device_interface_detail_data).DevicePath.as_ptr(), device_path_as_utf16_chars.as_mut_ptr(), device_path_as_utf16_chars.capacity()); }
+    }
+
+    Ok(device_path_as_utf16_chars)
+}
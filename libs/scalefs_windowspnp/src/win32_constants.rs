@@ -0,0 +1,35 @@
+// Copyright (c) ScaleFS LLC; used with permission
+// Licensed under the MIT License
+
+// NOTE: these are copies of the Win32 constants which the (platform-independent) enumeration and property buffer decoding logic relies on; they are duplicated here (instead of being imported from windows-sys) so that the logic also builds on non-Windows platforms (e.g. when enumerating an in-memory device tree)
+
+// win32 errors
+// see: https://learn.microsoft.com/en-us/windows/win32/debug/system-error-codes
+pub const ERROR_INVALID_DATA: u32 = 13;
+pub const ERROR_NOT_FOUND: u32 = 1168;
+
+// device property data types
+// see: https://learn.microsoft.com/en-us/windows-hardware/drivers/install/property-data-type-identifiers
+pub const DEVPROP_TYPE_BYTE: u32 = 0x0000_0003;
+pub const DEVPROP_TYPE_UINT16: u32 = 0x0000_0005;
+pub const DEVPROP_TYPE_UINT32: u32 = 0x0000_0007;
+pub const DEVPROP_TYPE_GUID: u32 = 0x0000_000D;
+pub const DEVPROP_TYPE_BOOLEAN: u32 = 0x0000_0011;
+pub const DEVPROP_TYPE_STRING: u32 = 0x0000_0012;
+pub const DEVPROP_TYPE_SECURITY_DESCRIPTOR_STRING: u32 = 0x0000_0014;
+pub const MAX_DEVPROP_TYPE: u32 = 0x0000_0019;
+//
+pub const DEVPROP_TYPEMOD_ARRAY: u32 = 0x0000_1000;
+pub const DEVPROP_TYPEMOD_LIST: u32 = 0x0000_2000;
+pub const MAX_DEVPROP_TYPEMOD: u32 = 0x0000_2000;
+
+// registry value types
+// see: https://learn.microsoft.com/en-us/windows/win32/sysinfo/registry-value-types
+pub const REG_SZ: u32 = 1;
+pub const REG_DWORD: u32 = 4;
+pub const REG_MULTI_SZ: u32 = 7;
+
+// device registry properties
+// see: https://learn.microsoft.com/en-us/windows/win32/api/setupapi/nf-setupapi-setupdigetdeviceregistrypropertyw
+pub const SPDRP_CLASSGUID: u32 = 0x0000_0008;
+pub const SPDRP_BASE_CONTAINERID: u32 = 0x0000_0024;
@@ -0,0 +1,700 @@
+use aes::cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use aes::Aes128;
+
+use std::error::Error;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::fast_pair::FastPairTracker;
+use crate::identity::DeviceId;
+use crate::protocol::apple::{decode_proximity_pairing, APPLE_COMPANY_ID};
+use crate::protocol::beacon::{
+    decode_bthome, decode_eddystone_tlm, decode_ruuvi, BeaconBattery, VoltageCurve, BTHOME_SERVICE_UUID,
+    EDDYSTONE_SERVICE_UUID, RUUVI_COMPANY_ID,
+};
+use crate::protocol::fast_pair::{decode_fast_pair, FAST_PAIR_SERVICE_UUID};
+use crate::provider::{BatteryComponent, BatteryProvider, BluetoothInfo, ProviderResult};
+use crate::watcher::DeviceEvent;
+
+pub const BATTERY_SERVICE_UUID: u16 = 0x180F;
+
+/// Readings of a device that stopped advertising are dropped after this long.
+const READING_TTL: Duration = Duration::from_secs(300);
+
+/// One received advertisement (or scan response), as the platform scanner reports it.
+#[derive(Clone, Debug, Default)]
+pub struct Advertisement {
+    pub address: u64,
+    pub local_name: Option<String>,
+    /// (company id, data without the company id)
+    pub manufacturer_data: Vec<(u16, Vec<u8>)>,
+    /// (16-bit service UUID, data without the UUID)
+    pub service_data: Vec<(u16, Vec<u8>)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PayloadKey {
+    ManufacturerData(u16),
+    ServiceData(u16),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AdvertisedReading {
+    pub batteries: Vec<BatteryComponent>,
+    /// Fast Pair readings may be attributed through the model id the same address advertised before.
+    pub fast_pair: bool,
+    /// Fast Pair model id, only advertised while the device is pairable.
+    pub model_id: Option<u32>,
+    /// For formats that only report the cell voltage, turned into a level by the voltage curve.
+    pub voltage_mv: Option<u16>,
+    /// Sensor beacons aren't paired, they show up as devices of their own, e.g. "BTHome".
+    pub beacon: Option<&'static str>,
+    /// Shown when the paired device has no name of its own, e.g. "AirPods Pro".
+    pub model_name: Option<&'static str>,
+}
+
+pub type AdvertisementDecoder = fn(&[u8]) -> Option<AdvertisedReading>;
+
+/// Hands every manufacturer-data and service-data payload of an advertisement
+/// to the decoders registered for its company id or service UUID.
+#[derive(Default)]
+pub struct AdvertisementDispatcher {
+    decoders: Vec<(PayloadKey, AdvertisementDecoder)>,
+}
+
+impl AdvertisementDispatcher {
+    pub fn new() -> Self {
+        AdvertisementDispatcher::default()
+    }
+
+    pub fn with_default_decoders() -> Self {
+        let mut dispatcher = AdvertisementDispatcher::new();
+        dispatcher.register(PayloadKey::ManufacturerData(APPLE_COMPANY_ID), decode_apple);
+        dispatcher.register(PayloadKey::ServiceData(FAST_PAIR_SERVICE_UUID), decode_google_fast_pair);
+        dispatcher.register(PayloadKey::ServiceData(BATTERY_SERVICE_UUID), decode_battery_service_data);
+        dispatcher.register(PayloadKey::ServiceData(BTHOME_SERVICE_UUID), decode_bthome_beacon);
+        dispatcher.register(PayloadKey::ManufacturerData(RUUVI_COMPANY_ID), decode_ruuvi_beacon);
+        dispatcher.register(PayloadKey::ServiceData(EDDYSTONE_SERVICE_UUID), decode_eddystone_beacon);
+        dispatcher
+    }
+
+    pub fn register(&mut self, key: PayloadKey, decoder: AdvertisementDecoder) {
+        self.decoders.push((key, decoder));
+    }
+
+    pub fn dispatch(&self, advertisement: &Advertisement) -> Vec<AdvertisedReading> {
+        let payloads = advertisement
+            .manufacturer_data
+            .iter()
+            .map(|(company_id, data)| (PayloadKey::ManufacturerData(*company_id), data))
+            .chain(
+                advertisement
+                    .service_data
+                    .iter()
+                    .map(|(uuid, data)| (PayloadKey::ServiceData(*uuid), data)),
+            );
+
+        payloads
+            .flat_map(|(key, data)| {
+                self.decoders
+                    .iter()
+                    .filter(move |(decoder_key, _)| *decoder_key == key)
+                    .filter_map(move |(_, decode)| decode(data))
+            })
+            .collect()
+    }
+}
+
+fn decode_apple(data: &[u8]) -> Option<AdvertisedReading> {
+    let proximity_pairing = decode_proximity_pairing(data)?;
+
+    Some(AdvertisedReading {
+        batteries: proximity_pairing.batteries(),
+        model_name: proximity_pairing.model_name(),
+        ..Default::default()
+    })
+}
+
+fn decode_google_fast_pair(data: &[u8]) -> Option<AdvertisedReading> {
+    let advertisement = decode_fast_pair(data)?;
+
+    Some(AdvertisedReading {
+        batteries: advertisement.battery_components(),
+        fast_pair: true,
+        model_id: advertisement.model_id,
+        ..Default::default()
+    })
+}
+
+/// Battery Service data (0x180F) holds a plain Battery Level byte.
+fn decode_battery_service_data(data: &[u8]) -> Option<AdvertisedReading> {
+    match data.first() {
+        Some(level) if *level <= 100 => Some(AdvertisedReading {
+            batteries: vec![BatteryComponent::main(*level)],
+            ..Default::default()
+        }),
+        _ => None,
+    }
+}
+
+fn decode_bthome_beacon(data: &[u8]) -> Option<AdvertisedReading> {
+    Some(beacon_reading("BTHome", decode_bthome(data)?))
+}
+
+fn decode_ruuvi_beacon(data: &[u8]) -> Option<AdvertisedReading> {
+    Some(beacon_reading("Ruuvi", decode_ruuvi(data)?))
+}
+
+fn decode_eddystone_beacon(data: &[u8]) -> Option<AdvertisedReading> {
+    Some(beacon_reading("Eddystone", decode_eddystone_tlm(data)?))
+}
+
+fn beacon_reading(format: &'static str, battery: BeaconBattery) -> AdvertisedReading {
+    AdvertisedReading {
+        batteries: battery.level.map(BatteryComponent::main).into_iter().collect(),
+        voltage_mv: battery.voltage_mv,
+        beacon: Some(format),
+        ..Default::default()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BondedDevice {
+    /// Identity address
+    pub address: u64,
+    pub name: String,
+    /// Identity Resolving Key, most significant byte first
+    pub irk: Option<[u8; 16]>,
+    pub connected: bool,
+}
+
+/// Maps advertising addresses onto bonded devices, including resolvable private
+/// addresses that rotate every few minutes for the devices whose IRK the platform gave us.
+#[derive(Default)]
+pub struct IdentityResolver {
+    devices: Vec<BondedDevice>,
+}
+
+impl IdentityResolver {
+    pub fn set_devices(&mut self, devices: Vec<BondedDevice>) {
+        self.devices = devices;
+    }
+
+    pub fn resolve(&self, address: u64) -> Option<&BondedDevice> {
+        self
+            .devices
+            .iter()
+            .find(|device| device.address == address)
+            .or_else(|| {
+                match is_resolvable_private_address(address) {
+                    true => self
+                        .devices
+                        .iter()
+                        .find(|device| device.irk.is_some_and(|irk| resolve_private_address(&irk, address))),
+                    false => None,
+                }
+            })
+    }
+
+    pub fn find(&self, id: &DeviceId) -> Option<&BondedDevice> {
+        self.devices
+            .iter()
+            .find(|device| id.address == Some(device.address))
+    }
+
+    /// The one connected device named after `model_name`, e.g. "Anna's AirPods Pro"
+    /// for "AirPods Pro"; `None` when there are several to choose from.
+    pub fn find_connected_model(&self, model_name: &str) -> Option<&BondedDevice> {
+        let mut devices = self
+            .devices
+            .iter()
+            .filter(|device| device.connected && device.name.contains(model_name));
+
+        match (devices.next(), devices.next()) {
+            (Some(device), None) => Some(device),
+            _ => None,
+        }
+    }
+}
+
+/// The two most significant bits of a resolvable private address are 0b01.
+fn is_resolvable_private_address(address: u64) -> bool {
+    (address >> 46) & 0b11 == 0b01
+}
+
+/// Core spec Vol 3 Part H 2.2.2: the lower 24 bits of the address are
+/// ah(IRK, prand), where prand is the upper 24 bits.
+pub fn resolve_private_address(irk: &[u8; 16], address: u64) -> bool {
+    let prand = ((address >> 24) & 0xFF_FFFF) as u32;
+    let hash = (address & 0xFF_FFFF) as u32;
+
+    ah(irk, prand) == hash
+}
+
+/// The random address hash function, ah(k, r) = e(k, padding || r) mod 2^24.
+fn ah(irk: &[u8; 16], prand: u32) -> u32 {
+    let mut block = [0u8; 16];
+    block[13..].copy_from_slice(&prand.to_be_bytes()[1..]);
+
+    let mut block = GenericArray::from(block);
+    Aes128::new(&GenericArray::from(*irk)).encrypt_block(&mut block);
+
+    u32::from_be_bytes([0, block[13], block[14], block[15]])
+}
+
+/// The platform side of the advertisement subsystem, it feeds a sink from its
+/// own scanning thread or callbacks.
+pub trait AdvertisementScanner {
+    /// Devices readings may be attributed to, advertisements of everything else are ignored.
+    fn bonded_devices(&mut self) -> Result<Vec<BondedDevice>, Box<dyn Error>>;
+}
+
+struct AdvertisementState {
+    dispatcher: AdvertisementDispatcher,
+    resolver: IdentityResolver,
+    fast_pair: FastPairTracker,
+    voltage_curve: VoltageCurve,
+    readings: Vec<(BluetoothInfo, Instant)>,
+    // advertisements of bonded devices the scanner couldn't read
+    errors: Vec<(DeviceId, String)>,
+}
+
+/// Handed to the platform scanner, it decodes every advertisement right away
+/// and wakes up the refresh thread when a bonded device's reading changed.
+#[derive(Clone)]
+pub struct AdvertisementSink {
+    state: Arc<Mutex<AdvertisementState>>,
+    event_sender: Sender<DeviceEvent>,
+}
+
+impl AdvertisementSink {
+    pub fn receive(&self, advertisement: &Advertisement) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let state = &mut *state;
+
+        let paired = state
+            .resolver
+            .resolve(advertisement.address)
+            .map(|device| DeviceId::from_address(device.address));
+        for mut reading in state.dispatcher.dispatch(advertisement) {
+            if let (true, Some(voltage_mv)) = (reading.batteries.is_empty(), reading.voltage_mv) {
+                reading.batteries.push(BatteryComponent::main(state.voltage_curve.level(voltage_mv)));
+            };
+
+            let model_name = reading.model_name;
+            let info = match reading.beacon {
+                Some(format) => Some(BluetoothInfo {
+                    id: paired.unwrap_or(DeviceId::from_address(advertisement.address)),
+                    name: advertisement
+                        .local_name
+                        .clone()
+                        .unwrap_or_else(|| format!("{format} {}", format_short_address(advertisement.address))),
+                    batteries: reading.batteries,
+                    status: true,
+                }),
+                // falls back to the Fast Pair model id association for addresses that didn't resolve
+                None if reading.fast_pair => state.fast_pair.observe(
+                    advertisement.address,
+                    paired,
+                    reading.model_id,
+                    reading.batteries,
+                ),
+                // Apple and Battery Service data only count for addresses that resolve, AirPods and
+                // Beats advertise from addresses that rarely do, so those fall back to their model
+                None => {
+                    let id = paired.or_else(|| {
+                        let device = state.resolver.find_connected_model(model_name?)?;
+                        Some(DeviceId::from_address(device.address))
+                    });
+                    match (id, reading.batteries.is_empty()) {
+                        (Some(id), false) => Some(BluetoothInfo {
+                            id,
+                            name: String::new(),
+                            batteries: reading.batteries,
+                            status: true,
+                        }),
+                        _ => None,
+                    }
+                }
+            };
+            let Some(mut info) = info else {
+                continue;
+            };
+            if let Some(device) = state.resolver.find(&info.id) {
+                info.name = device.name.clone();
+            };
+            if let (true, Some(model_name)) = (info.name.is_empty(), model_name) {
+                info.name = model_name.to_string();
+            };
+
+            let id = info.id;
+            let changed = match state.readings.iter_mut().find(|(reading, _)| reading.id.matches(&id)) {
+                Some((reading, seen_at)) => {
+                    // refreshed even when nothing changed, so that it doesn't expire
+                    *seen_at = Instant::now();
+                    match reading.batteries == info.batteries && reading.name == info.name {
+                        true => false,
+                        false => {
+                            *reading = info;
+                            true
+                        }
+                    }
+                }
+                None => {
+                    state.readings.push((info, Instant::now()));
+                    true
+                }
+            };
+
+            if changed {
+                self.event_sender.send(DeviceEvent::Changed(id)).ok();
+            };
+        }
+    }
+
+    /// Only failures of bonded devices are kept, everyone else's advertisements
+    /// are none of our business; BlueZ hands them over already parsed.
+    #[cfg(any(target_os = "windows", test))]
+    pub fn receive_failed(&self, address: u64, error: impl std::fmt::Display) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let Some(id) = state
+            .resolver
+            .resolve(address)
+            .map(|device| DeviceId::from_address(device.address))
+        else {
+            return;
+        };
+
+        // a device advertises many times between two polls, its latest failure is enough
+        state.errors.retain(|(failed, _)| !failed.matches(&id));
+        state.errors.push((id, error.to_string()));
+    }
+}
+
+/// e.g. "2B1E" for A4:C1:38:5D:2B:1E, the same suffix sensor apps show
+fn format_short_address(address: u64) -> String {
+    format!("{:04X}", address & 0xFFFF)
+}
+
+pub struct AdvertisementProvider {
+    state: Arc<Mutex<AdvertisementState>>,
+    scanner: Box<dyn AdvertisementScanner + Send>,
+}
+
+impl AdvertisementProvider {
+    /// `start_scanner` gets the sink it has to feed and returns the running scanner,
+    /// which stays alive as long as the provider.
+    pub fn new<S, F>(event_sender: Sender<DeviceEvent>, start_scanner: F) -> Result<Self, Box<dyn Error>>
+    where
+        S: AdvertisementScanner + Send + 'static,
+        F: FnOnce(AdvertisementSink) -> Result<S, Box<dyn Error>>,
+    {
+        let state = Arc::new(Mutex::new(AdvertisementState {
+            dispatcher: AdvertisementDispatcher::with_default_decoders(),
+            resolver: IdentityResolver::default(),
+            fast_pair: FastPairTracker::new(),
+            voltage_curve: VoltageCurve::default(),
+            readings: Vec::new(),
+            errors: Vec::new(),
+        }));
+
+        let mut scanner = start_scanner(AdvertisementSink {
+            state: Arc::clone(&state),
+            event_sender,
+        })?;
+        let bonded_devices = scanner.bonded_devices()?;
+        state.lock().unwrap().resolver.set_devices(bonded_devices);
+
+        Ok(AdvertisementProvider {
+            state,
+            scanner: Box::new(scanner),
+        })
+    }
+
+    pub fn set_voltage_curve(&mut self, voltage_curve: VoltageCurve) {
+        if let Ok(mut state) = self.state.lock() {
+            state.voltage_curve = voltage_curve;
+        };
+    }
+}
+
+impl BatteryProvider for AdvertisementProvider {
+    fn name(&self) -> &'static str {
+        "advertisement"
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        let bonded_devices = self.scanner.bonded_devices()?;
+
+        let mut state = self.state.lock().map_err(|err| err.to_string())?;
+        state.resolver.set_devices(bonded_devices);
+        state
+            .readings
+            .retain(|(_, seen_at)| seen_at.elapsed() < READING_TTL);
+
+        let devices_info = state
+            .readings
+            .iter()
+            .map(|(info, _)| info.clone())
+            .collect();
+
+        Ok(devices_info)
+    }
+
+    fn take_device_errors(&mut self) -> Vec<(DeviceId, Box<dyn Error>)> {
+        let Ok(mut state) = self.state.lock() else {
+            return Vec::new();
+        };
+
+        state.errors.drain(..).map(|(id, error)| (id, error.into())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{BatteryLevel, LEFT_BATTERY, MAIN_BATTERY};
+    use std::sync::mpsc::{self, Receiver};
+
+    // Core spec Vol 3 Part H, Appendix D.7: ah(IRK, 0x708194) = 0x0DFBAA
+    const IRK: [u8; 16] = [
+        0xEC, 0x02, 0x34, 0xA3, 0x57, 0xC8, 0xAD, 0x05, 0x34, 0x10, 0x10, 0xA6, 0x0A, 0x39, 0x7D, 0x9B,
+    ];
+    const RESOLVABLE_ADDRESS: u64 = 0x708194_0DFBAA;
+    const IDENTITY_ADDRESS: u64 = 0x001122334455;
+
+    /// AirPods Pro, left 90%, right 80%, case unknown
+    const PROXIMITY_PAIRING: [u8; 11] = [0x07, 0x19, 0x01, 0x0E, 0x20, 0x2B, 0x89, 0x8F, 0x01, 0x0F, 0x00];
+
+    #[test]
+    fn ah_known_vector() {
+        assert_eq!(ah(&IRK, 0x708194), 0x0DFBAA);
+        assert!(resolve_private_address(&IRK, RESOLVABLE_ADDRESS));
+        assert!(!resolve_private_address(&IRK, RESOLVABLE_ADDRESS ^ 1));
+        assert!(!resolve_private_address(&[0; 16], RESOLVABLE_ADDRESS));
+    }
+
+    #[test]
+    fn resolver_matches_identity_and_private_addresses() {
+        let mut resolver = IdentityResolver::default();
+        resolver.set_devices(vec![
+            BondedDevice {
+                address: IDENTITY_ADDRESS,
+                name: "Headset".to_string(),
+                irk: None,
+                connected: false,
+            },
+            BondedDevice {
+                address: 0x66778899AABB,
+                name: "Buds".to_string(),
+                irk: Some(IRK),
+                connected: false,
+            },
+        ]);
+
+        let resolve = |address| resolver.resolve(address).map(|device| device.name.as_str());
+        assert_eq!(resolve(IDENTITY_ADDRESS), Some("Headset"));
+        assert_eq!(resolve(RESOLVABLE_ADDRESS), Some("Buds"));
+        // the same hash under a static random (0b11) or non-resolvable (0b00) prefix
+        assert_eq!(resolve(0xF08194_0DFBAA), None);
+        assert_eq!(resolve(0x308194_0DFBAA), None);
+        assert_eq!(resolve(0x708195_0DFBAA), None);
+    }
+
+    #[test]
+    fn dispatcher_routes_payloads_by_key() {
+        let dispatcher = AdvertisementDispatcher::with_default_decoders();
+        let advertisement = Advertisement {
+            address: IDENTITY_ADDRESS,
+            local_name: None,
+            manufacturer_data: vec![
+                (APPLE_COMPANY_ID, PROXIMITY_PAIRING.to_vec()),
+                (0x0075, vec![0x42, 0x04]),
+            ],
+            service_data: vec![(BATTERY_SERVICE_UUID, vec![0x37]), (0xFEAA, vec![0x00, 0xE7])],
+        };
+
+        let readings = dispatcher.dispatch(&advertisement);
+
+        assert_eq!(readings.len(), 2);
+        assert_eq!(readings[0].model_name, Some("AirPods Pro"));
+        assert_eq!(readings[0].batteries.len(), 2);
+        assert!(!readings[0].fast_pair);
+        assert_eq!(readings[1].batteries, [BatteryComponent::main(55)]);
+    }
+
+    #[test]
+    fn dispatcher_runs_every_decoder_of_a_key() {
+        let mut dispatcher = AdvertisementDispatcher::new();
+        dispatcher.register(
+            PayloadKey::ServiceData(BATTERY_SERVICE_UUID),
+            decode_battery_service_data,
+        );
+        dispatcher.register(PayloadKey::ServiceData(BATTERY_SERVICE_UUID), |data| {
+            Some(AdvertisedReading {
+                voltage_mv: Some(data.len() as u16),
+                ..Default::default()
+            })
+        });
+        let advertisement = Advertisement {
+            service_data: vec![(BATTERY_SERVICE_UUID, vec![0x65])],
+            ..Default::default()
+        };
+
+        // 101% isn't a Battery Level, only the second decoder reads it
+        let readings = dispatcher.dispatch(&advertisement);
+
+        assert_eq!(readings.len(), 1);
+        assert_eq!(readings[0].voltage_mv, Some(1));
+    }
+
+    struct FakeScanner {
+        devices: Vec<BondedDevice>,
+    }
+
+    impl AdvertisementScanner for FakeScanner {
+        fn bonded_devices(&mut self) -> Result<Vec<BondedDevice>, Box<dyn Error>> {
+            Ok(self.devices.clone())
+        }
+    }
+
+    fn start_provider(devices: Vec<BondedDevice>) -> (AdvertisementProvider, AdvertisementSink, Receiver<DeviceEvent>) {
+        let (event_sender, events) = mpsc::channel();
+        let mut sink = None;
+        let provider = AdvertisementProvider::new(event_sender, |advertisement_sink| {
+            sink = Some(advertisement_sink);
+            Ok(FakeScanner { devices })
+        })
+        .unwrap();
+
+        (provider, sink.unwrap(), events)
+    }
+
+    #[test]
+    fn apple_readings_are_attributed_to_paired_addresses() {
+        let (mut provider, sink, events) = start_provider(vec![
+            BondedDevice {
+                address: IDENTITY_ADDRESS,
+                // no name cached for it
+                name: String::new(),
+                irk: None,
+                connected: false,
+            },
+            BondedDevice {
+                address: 0x66778899AABB,
+                name: "Buds".to_string(),
+                irk: Some(IRK),
+                connected: false,
+            },
+        ]);
+        let apple = |address| Advertisement {
+            address,
+            manufacturer_data: vec![(APPLE_COMPANY_ID, PROXIMITY_PAIRING.to_vec())],
+            ..Default::default()
+        };
+
+        sink.receive(&apple(IDENTITY_ADDRESS));
+        sink.receive(&apple(0x4A0000000001));
+        sink.receive(&Advertisement {
+            address: RESOLVABLE_ADDRESS,
+            service_data: vec![(BATTERY_SERVICE_UUID, vec![0x37])],
+            ..Default::default()
+        });
+        // the same reading again isn't a change
+        sink.receive(&apple(IDENTITY_ADDRESS));
+
+        let devices_info = provider.poll().unwrap();
+
+        assert_eq!(devices_info.len(), 2);
+        assert_eq!(devices_info[0].id, DeviceId::from_address(IDENTITY_ADDRESS));
+        assert_eq!(devices_info[0].name, "AirPods Pro");
+        assert_eq!(devices_info[0].batteries[0].name, LEFT_BATTERY);
+        assert_eq!(devices_info[0].batteries[0].level, BatteryLevel::Known(90));
+        assert_eq!(devices_info[1].id, DeviceId::from_address(0x66778899AABB));
+        assert_eq!(devices_info[1].name, "Buds");
+        assert_eq!(devices_info[1].batteries[0].name, MAIN_BATTERY);
+        assert_eq!(events.try_iter().count(), 2);
+    }
+
+    #[test]
+    fn apple_readings_fall_back_to_the_one_connected_device_of_their_model() {
+        let device = |address, name: &str, connected| BondedDevice {
+            address,
+            name: name.to_string(),
+            irk: None,
+            connected,
+        };
+        let apple = Advertisement {
+            address: 0x4A0000000001,
+            manufacturer_data: vec![(APPLE_COMPANY_ID, PROXIMITY_PAIRING.to_vec())],
+            ..Default::default()
+        };
+
+        let cases = [
+            (
+                vec![
+                    device(IDENTITY_ADDRESS, "Anna's AirPods Pro", true),
+                    device(0x66778899AABB, "AirPods Pro", false),
+                    device(0x001122334466, "AirPods Max", true),
+                ],
+                vec![IDENTITY_ADDRESS],
+            ),
+            // no telling which of the two it is
+            (
+                vec![
+                    device(IDENTITY_ADDRESS, "Anna's AirPods Pro", true),
+                    device(0x66778899AABB, "AirPods Pro", true),
+                ],
+                vec![],
+            ),
+            (vec![device(IDENTITY_ADDRESS, "Anna's AirPods Pro", false)], vec![]),
+        ];
+
+        for (devices, addresses) in cases {
+            let (mut provider, sink, _events) = start_provider(devices);
+            sink.receive(&apple);
+            // Battery Service data carries no model to go by
+            sink.receive(&Advertisement {
+                address: 0x4A0000000002,
+                service_data: vec![(BATTERY_SERVICE_UUID, vec![0x37])],
+                ..Default::default()
+            });
+
+            let devices_info = provider.poll().unwrap();
+            assert_eq!(
+                devices_info.iter().map(|info| info.id).collect::<Vec<_>>(),
+                addresses.into_iter().map(DeviceId::from_address).collect::<Vec<_>>()
+            );
+            if let Some(info) = devices_info.first() {
+                assert_eq!(info.name, "Anna's AirPods Pro");
+                assert_eq!(info.batteries[0].name, LEFT_BATTERY);
+                assert_eq!(info.batteries[0].level, BatteryLevel::Known(90));
+            };
+        }
+    }
+
+    #[test]
+    fn read_failures_are_reported_for_bonded_devices_only() {
+        let (mut provider, sink, _events) = start_provider(vec![BondedDevice {
+            address: IDENTITY_ADDRESS,
+            name: "Buds".to_string(),
+            irk: Some(IRK),
+            connected: true,
+        }]);
+
+        sink.receive_failed(RESOLVABLE_ADDRESS, "bad data section");
+        sink.receive_failed(IDENTITY_ADDRESS, "bad manufacturer data");
+        sink.receive_failed(0x4A0000000001, "not ours");
+
+        let errors = provider.take_device_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, DeviceId::from_address(IDENTITY_ADDRESS));
+        assert_eq!(errors[0].1.to_string(), "bad manufacturer data");
+        assert!(provider.take_device_errors().is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+use windows::{
+    Devices::Bluetooth::BluetoothConnectionStatus,
+    Devices::Bluetooth::Advertisement::{
+        BluetoothLEAdvertisementReceivedEventArgs, BluetoothLEAdvertisementWatcher, BluetoothLEScanningMode,
+    },
+    Foundation::{EventRegistrationToken, TypedEventHandler},
+};
+
+use std::error::Error;
+
+use crate::advertisement::{Advertisement, AdvertisementScanner, AdvertisementSink, BondedDevice};
+use crate::bluetooth::{find_ble_devices, find_bt_devices};
+use crate::gatt::read_bytes;
+
+/// AD type "Service Data - 16-bit UUID"
+const SERVICE_DATA_16_BIT_UUID: u8 = 0x16;
+
+/// Listens to advertisements without sending scan requests.
+///
+/// The IRKs are only readable by SYSTEM, so bonded devices come without one and
+/// only identity addresses resolve: those Windows resolved itself, or devices that
+/// advertise from their identity address. Readings from other private addresses
+/// only reach a device through the Fast Pair model id or the Apple model name.
+pub struct BleScanner {
+    watcher: BluetoothLEAdvertisementWatcher,
+    token: EventRegistrationToken,
+}
+
+impl BleScanner {
+    pub fn start(sink: AdvertisementSink) -> windows::core::Result<Self> {
+        let watcher = BluetoothLEAdvertisementWatcher::new()?;
+        watcher.SetScanningMode(BluetoothLEScanningMode::Passive)?;
+
+        let token = watcher.Received(&TypedEventHandler::<
+            BluetoothLEAdvertisementWatcher,
+            BluetoothLEAdvertisementReceivedEventArgs,
+        >::new(move |_, args| {
+            if let Some(args) = args {
+                match (convert_advertisement(args), args.BluetoothAddress()) {
+                    (Ok(advertisement), _) => sink.receive(&advertisement),
+                    (Err(err), Ok(address)) => sink.receive_failed(address, err),
+                    (Err(_), Err(_)) => (),
+                }
+            };
+            Ok(())
+        }))?;
+        watcher.Start()?;
+
+        Ok(BleScanner { watcher, token })
+    }
+}
+
+impl AdvertisementScanner for BleScanner {
+    fn bonded_devices(&mut self) -> Result<Vec<BondedDevice>, Box<dyn Error>> {
+        let mut bonded_devices = Vec::new();
+        for bt_device in find_bt_devices()? {
+            bonded_devices.push(BondedDevice {
+                address: bt_device.BluetoothAddress()?,
+                name: bt_device.Name()?.to_string(),
+                irk: None,
+                connected: bt_device.ConnectionStatus()? == BluetoothConnectionStatus::Connected,
+            });
+        }
+        for ble_device in find_ble_devices()? {
+            bonded_devices.push(BondedDevice {
+                address: ble_device.BluetoothAddress()?,
+                name: ble_device.Name()?.to_string(),
+                irk: None,
+                connected: ble_device.ConnectionStatus()? == BluetoothConnectionStatus::Connected,
+            });
+        }
+
+        Ok(bonded_devices)
+    }
+}
+
+impl Drop for BleScanner {
+    fn drop(&mut self) {
+        self.watcher.RemoveReceived(self.token).ok();
+        self.watcher.Stop().ok();
+    }
+}
+
+fn convert_advertisement(args: &BluetoothLEAdvertisementReceivedEventArgs) -> windows::core::Result<Advertisement> {
+    let advertisement = args.Advertisement()?;
+
+    let mut manufacturer_data = Vec::new();
+    for data in advertisement.ManufacturerData()? {
+        manufacturer_data.push((data.CompanyId()?, read_bytes(&data.Data()?)?));
+    }
+
+    let mut service_data = Vec::new();
+    for section in advertisement.DataSections()? {
+        if section.DataType()? != SERVICE_DATA_16_BIT_UUID {
+            continue;
+        };
+        if let [uuid_low, uuid_high, data @ ..] = read_bytes(&section.Data()?)?.as_slice() {
+            service_data.push((u16::from_le_bytes([*uuid_low, *uuid_high]), data.to_vec()));
+        };
+    }
+
+    let local_name = advertisement.LocalName()?.to_string();
+
+    Ok(Advertisement {
+        address: args.BluetoothAddress()?,
+        local_name: match local_name.is_empty() {
+            true => None,
+            false => Some(local_name),
+        },
+        manufacturer_data,
+        service_data,
+    })
+}
@@ -1,186 +1,182 @@
 use windows::{
-    core::{Error, GUID},
-    Devices::Bluetooth::GenericAttributeProfile::{GattCharacteristicUuids, GattServiceUuids},
+    core::HSTRING,
     Devices::Bluetooth::{BluetoothConnectionStatus,BluetoothLEDevice,BluetoothDevice},
     Devices::Enumeration::DeviceInformation,
-    Storage::Streams::DataReader,
 };
 
-pub struct BluetoothInfo {
-    pub name: String,
-    pub battery: u8,
-    pub status: bool,
+use scalefs_windowspnp::{PnpEnumerator, SetupApiBackend};
+
+use std::error::Error;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::advertisement::AdvertisementProvider;
+use crate::ble_scanner::BleScanner;
+use crate::config::Config;
+use crate::device_reader::{DeviceReader, DEFAULT_DEVICE_TIMEOUT};
+use crate::galaxy_buds::GalaxyBudsProvider;
+use crate::gatt::GattBatteryProvider;
+use crate::hid::HidBatteryProvider;
+use crate::identity::{parse_device_id_address, DeviceId};
+use crate::pnp::{get_pnp_bt_devices_info, PnpBtDeviceInfo};
+use crate::provider::{
+    BatteryComponent, BatteryProvider, BluetoothInfo, ProviderRegistry, ProviderResult, UnknownReason, MAIN_BATTERY,
+};
+use crate::rfcomm::WinsockSpp;
+use crate::sony::SonyBatteryProvider;
+use crate::watcher::DeviceEvent;
+
+pub struct PnpBatteryProvider {
+    reader: DeviceReader<u64, BluetoothInfo>,
 }
 
-pub fn find_bluetooth_devices() -> windows::core::Result<(Vec<BluetoothDevice>, Vec<BluetoothLEDevice>)> {
-    let bt_aqs_filter = BluetoothDevice::GetDeviceSelectorFromPairingState(true)?;
-    let bt_le_aqs_filter = BluetoothLEDevice::GetDeviceSelectorFromPairingState(true)?;
+impl PnpBatteryProvider {
+    pub fn new(device_timeout: Duration) -> Self {
+        PnpBatteryProvider {
+            reader: DeviceReader::new(device_timeout),
+        }
+    }
 
-    let bt_devices_info_collection = 
-        DeviceInformation::FindAllAsyncAqsFilter(&bt_aqs_filter)?.get()?;
-    let ble_devices_info_collection = 
-        DeviceInformation::FindAllAsyncAqsFilter(&bt_le_aqs_filter)?.get()?;
+    fn get_bt_devices_info(&mut self, addresses: Vec<u64>) -> Result<Vec<BluetoothInfo>, Box<dyn Error>> {
+        if addresses.is_empty() {
+            return Ok(Vec::new());
+        };
 
-    Ok((
-        bt_devices_info_collection
-            .into_iter()
-            .filter_map(|device_info| {
-                BluetoothDevice::FromIdAsync(&device_info.Id().ok()?)
-                    .ok()?
-                    .get()
-                    .ok()
-            })
-            .collect(),
-
-        ble_devices_info_collection
-            .into_iter()
-            .filter_map(|device_info| {
-                BluetoothLEDevice::FromIdAsync(&device_info.Id().ok()?)
-                    .ok()?
-                    .get()
-                    .ok()
-            })
-            .collect(),
-    ))
+        let pnp_bt_devices_info = Arc::new(get_pnp_bt_devices_info(&PnpEnumerator::new(SetupApiBackend::new()))?);
+        Ok(self.reader.read_all(addresses, move |address| {
+            let bt_device = BluetoothDevice::FromBluetoothAddressAsync(*address).ok()?.get().ok()?;
+            get_bt_device_info(&bt_device, &pnp_bt_devices_info).ok()?
+        }))
+    }
 }
 
-pub fn get_bluetooth_info(
-    bt_devices: Vec<BluetoothDevice>,
-    ble_devices: Vec<BluetoothLEDevice>,
-) -> windows::core::Result<Vec<BluetoothInfo>> {
-    let mut devices_info: Vec<BluetoothInfo> = Vec::new();
-
-    if bt_devices.len() > 0 {
-        let pnp_bt_devices_info: Vec<(String, u8)> = get_pnp_bt_devices_info();
-
-        for bt_device in bt_devices {
-            let name = bt_device.Name()?.to_string();
-            for (n, battery) in &pnp_bt_devices_info {
-                // e.g. 
-                // bluetooth name: HUAWEI FreeBuds Pro
-                // pnp device name: HUAWEI FreeBuds Pro Hands-Free AG
-                if n.contains(&name) {
-                    if bt_device.ConnectionStatus()? == BluetoothConnectionStatus::Connected {
-                        devices_info.push(BluetoothInfo {
-                            name,
-                            battery: *battery,
-                            status: true,
-                        });
-                    } else {
-                        devices_info.push(BluetoothInfo {
-                            name,
-                            battery: *battery,
-                            status: false,
-                        });
-                    };
-                    break;
-                };
-            }
-        }
-    };
+impl BatteryProvider for PnpBatteryProvider {
+    fn name(&self) -> &'static str {
+        "pnp"
+    }
 
-    if ble_devices.len() > 0 {
-        for ble_device in ble_devices {
-            let name = ble_device.Name()?.to_string();
-            let battery = get_ble_battery_level(&ble_device).unwrap_or(0);
-            let status = ble_device
-                .ConnectionStatus()
-                .map(|status| matches!(status, BluetoothConnectionStatus::Connected))
-                .unwrap_or(false);
-
-            devices_info.push(BluetoothInfo {
-                name,
-                battery,
-                status,
-            });
-        }
-    };
+    fn poll(&mut self) -> ProviderResult {
+        let addresses = find_bt_addresses()?;
+        self.get_bt_devices_info(addresses)
+    }
 
+    fn poll_device(&mut self, id: &DeviceId) -> Result<Option<BluetoothInfo>, Box<dyn Error>> {
+        // not a classic device (or no longer around)
+        let Some(address) = id.address else {
+            return Ok(None);
+        };
 
-    Ok(devices_info)
+        Ok(self.get_bt_devices_info(vec![address])?.pop())
+    }
 }
 
-pub fn get_ble_battery_level(bt_le_device: &BluetoothLEDevice) -> windows::core::Result<u8> {
-    let battery_services_uuid: GUID = GattServiceUuids::Battery()?;
-    let battery_level_uuid: GUID = GattCharacteristicUuids::BatteryLevel()?;
+pub fn register_providers(registry: &mut ProviderRegistry, event_sender: &Sender<DeviceEvent>, config: &Config) {
+    let device_timeout = config.device_timeout.unwrap_or(DEFAULT_DEVICE_TIMEOUT);
+    registry.register(PnpBatteryProvider::new(device_timeout));
+    registry.register(GattBatteryProvider::new(
+        event_sender.clone(),
+        config.gatt_batteries.clone(),
+        device_timeout,
+    ));
+    registry.register(HidBatteryProvider::new());
+    registry.register(GalaxyBudsProvider::new(WinsockSpp));
+    registry.register(SonyBatteryProvider::new(WinsockSpp));
+
+    match AdvertisementProvider::new(event_sender.clone(), |sink| Ok(BleScanner::start(sink)?)) {
+        Ok(mut provider) => {
+            if let Some(voltage_curve) = &config.beacon_voltage_curve {
+                provider.set_voltage_curve(voltage_curve.clone());
+            };
+            registry.register(provider)
+        }
+        Err(err) => registry.register_unavailable("advertisement", err),
+    }
+}
 
-    let battery_services = bt_le_device
-        .GetGattServicesForUuidAsync(battery_services_uuid)
-        .and_then(|op_gatt_services_result| op_gatt_services_result.get())
-        .and_then(|gatt_services_result| gatt_services_result.Services())?;
+/// Addresses of the paired classic devices, read from their ids so none has to be opened.
+pub fn find_bt_addresses() -> windows::core::Result<Vec<u64>> {
+    find_paired_addresses(&BluetoothDevice::GetDeviceSelectorFromPairingState(true)?)
+}
 
-    let battery_service = battery_services
-        .into_iter()
-        .next()
-        .ok_or_else(|| Error::empty())?;
+/// Addresses of the paired LE devices, read from their ids so none has to be opened.
+pub fn find_ble_addresses() -> windows::core::Result<Vec<u64>> {
+    find_paired_addresses(&BluetoothLEDevice::GetDeviceSelectorFromPairingState(true)?)
+}
 
-    let battery_gatt_chars = battery_service
-        .GetCharacteristicsForUuidAsync(battery_level_uuid)
-        .and_then(|op_gatt_chars_result| op_gatt_chars_result.get())
-        .and_then(|gatt_chars_result| gatt_chars_result.Characteristics())?;
+fn find_paired_addresses(aqs_filter: &HSTRING) -> windows::core::Result<Vec<u64>> {
+    let devices_info_collection = DeviceInformation::FindAllAsyncAqsFilter(aqs_filter)?.get()?;
 
-    let battery_gatt_char = battery_gatt_chars
+    Ok(devices_info_collection
         .into_iter()
-        .next()
-        .ok_or_else(|| Error::empty())?;
-
-    let battery_level = match battery_gatt_char.Uuid()? == battery_level_uuid {
-        true => battery_gatt_char
-            .ReadValueAsync()
-            .and_then(|op_gatt_read_result| op_gatt_read_result.get())
-            .and_then(|gatt_read_result| gatt_read_result.Value())
-            .and_then(|buffer| DataReader::FromBuffer(&buffer))
-            .and_then(|date_reader| date_reader.ReadByte()),
-        false => Err(Error::empty()),
-    };
-
-    return battery_level;
+        .filter_map(|device_info| parse_device_id_address(&device_info.Id().ok()?.to_string()))
+        .collect())
 }
 
+pub fn find_bt_devices() -> windows::core::Result<Vec<BluetoothDevice>> {
+    let bt_aqs_filter = BluetoothDevice::GetDeviceSelectorFromPairingState(true)?;
 
-use scalefs_windowspnp::{PnpDeviceNodeInfo,PnpDevicePropertyValue,PnpEnumerator};
-use windows_sys::Win32::Devices::DeviceAndDriverInstallation::GUID_DEVCLASS_SYSTEM;
-use windows_sys::Win32::Devices::Properties::{DEVPKEY_Device_FriendlyName, DEVPROPKEY};
+    let bt_devices_info_collection = 
+        DeviceInformation::FindAllAsyncAqsFilter(&bt_aqs_filter)?.get()?;
 
-#[allow(non_upper_case_globals)]
-const DEVPKEY_Bluetooth_Battery: DEVPROPKEY = DEVPROPKEY { fmtid: windows_sys::core::GUID::from_u128(0x104EA319_6EE2_4701_BD47_8DDBF425BBE5), pid:2 };
-const BT_INSTANCE_ID: &str = "BTHENUM\\";
+    Ok(bt_devices_info_collection
+        .into_iter()
+        .filter_map(|device_info| {
+            BluetoothDevice::FromIdAsync(&device_info.Id().ok()?)
+                .ok()?
+                .get()
+                .ok()
+        })
+        .collect())
+}
 
-fn get_pnp_bt_devices_info() -> Vec<(String, u8)> {
-    let mut pnp_bt_devices_info: Vec<(String, u8)> = Vec::new();
-    let bt_devices = get_pnp_bt_devices(GUID_DEVCLASS_SYSTEM);
+pub fn find_ble_devices() -> windows::core::Result<Vec<BluetoothLEDevice>> {
+    let bt_le_aqs_filter = BluetoothLEDevice::GetDeviceSelectorFromPairingState(true)?;
 
-    let filter_bt_devices_properties = bt_devices.into_iter().filter_map(|i| {
-        match i.device_instance_id.contains(BT_INSTANCE_ID) {
-            true => i.device_instance_properties,
-            false => None,
-        }
-    });
-
-    for bt_device_properties in filter_bt_devices_properties {
-        let (mut name, mut battery_level) = (None, None);
-        for (key, value) in bt_device_properties {
-            if key == DEVPKEY_Device_FriendlyName.into() {
-                if let PnpDevicePropertyValue::String(v) = value {
-                    name = Some(v);
-                };
-            } else if key == DEVPKEY_Bluetooth_Battery.into() {
-                if let PnpDevicePropertyValue::Byte(v) = value {
-                    battery_level = Some(v);
-                };
-            } else if name.is_some() && battery_level.is_some() {
-                pnp_bt_devices_info.push((name.unwrap(), battery_level.unwrap()));
-                break;
-            };
-        }
-    }
+    let ble_devices_info_collection = 
+        DeviceInformation::FindAllAsyncAqsFilter(&bt_le_aqs_filter)?.get()?;
 
-    pnp_bt_devices_info
+    Ok(ble_devices_info_collection
+        .into_iter()
+        .filter_map(|device_info| {
+            BluetoothLEDevice::FromIdAsync(&device_info.Id().ok()?)
+                .ok()?
+                .get()
+                .ok()
+        })
+        .collect())
 }
 
-fn get_pnp_bt_devices(guid: windows_sys::core::GUID) -> Vec<PnpDeviceNodeInfo> {
-    match PnpEnumerator::enumerate_present_devices_by_device_setup_class(guid) {
-        Ok(devices) => devices,
-        _ => panic!("scalefs windowspnp can't find pnp devices"),
-    } 
-}
\ No newline at end of file
+fn get_bt_device_info(
+    bt_device: &BluetoothDevice,
+    pnp_bt_devices_info: &[PnpBtDeviceInfo],
+) -> windows::core::Result<Option<BluetoothInfo>> {
+    let address = bt_device.BluetoothAddress()?;
+    // a headset has one BTHENUM devnode per profile (e.g. Hands-Free AG, A2DP),
+    // they all share the address, so the first one carrying a battery level wins
+    let Some(pnp_info) = pnp_bt_devices_info
+        .iter()
+        .filter(|info| info.address == Some(address))
+        .min_by_key(|info| info.battery.is_none())
+    else {
+        return Ok(None);
+    };
+
+    let status = bt_device.ConnectionStatus()? == BluetoothConnectionStatus::Connected;
+    // a disconnected device doesn't tell whether it would report a level
+    let battery = match (pnp_info.battery, status) {
+        (Some(level), _) => BatteryComponent::main(level),
+        (None, true) => BatteryComponent::unknown(MAIN_BATTERY, UnknownReason::Unsupported),
+        (None, false) => BatteryComponent::unknown(MAIN_BATTERY, UnknownReason::Disconnected),
+    };
+
+    Ok(Some(BluetoothInfo {
+        id: DeviceId {
+            address: Some(address),
+            container_id: pnp_info.container_id,
+        },
+        name: bt_device.Name()?.to_string(),
+        batteries: vec![battery],
+        status,
+    }))
+}
@@ -0,0 +1,158 @@
+use windows::{
+    core::IInspectable,
+    Devices::Bluetooth::{BluetoothDevice, BluetoothLEDevice},
+    Devices::Enumeration::{DeviceInformation, DeviceInformationUpdate, DeviceWatcher},
+    Foundation::{EventRegistrationToken, TypedEventHandler},
+};
+
+use std::error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+
+use crate::bluetooth::{find_ble_devices, find_bt_devices};
+use crate::identity::{parse_device_id_address, DeviceId};
+use crate::watcher::{ChangeSource, DeviceEvent};
+
+pub struct BluetoothWatcher {
+    sender: Sender<DeviceEvent>,
+    device_watchers: Vec<DeviceWatcher>,
+    bt_devices: Vec<(BluetoothDevice, EventRegistrationToken)>,
+    ble_devices: Vec<(BluetoothLEDevice, EventRegistrationToken)>,
+}
+
+impl BluetoothWatcher {
+    pub fn start(sender: Sender<DeviceEvent>) -> windows::core::Result<Self> {
+        let aqs_filters = [
+            BluetoothDevice::GetDeviceSelectorFromPairingState(true)?,
+            BluetoothLEDevice::GetDeviceSelectorFromPairingState(true)?,
+        ];
+
+        let mut device_watchers = Vec::new();
+        for aqs_filter in aqs_filters {
+            device_watchers.push(watch_paired_devices(&aqs_filter, &sender)?);
+        }
+
+        let mut watcher = BluetoothWatcher {
+            sender,
+            device_watchers,
+            bt_devices: Vec::new(),
+            ble_devices: Vec::new(),
+        };
+        watcher.subscribe_devices()?;
+
+        Ok(watcher)
+    }
+
+    fn subscribe_devices(&mut self) -> windows::core::Result<()> {
+        self.unsubscribe_devices();
+
+        for bt_device in find_bt_devices()? {
+            let sender = self.sender.clone();
+            let id = DeviceId::from_address(bt_device.BluetoothAddress()?);
+            let token = bt_device.ConnectionStatusChanged(
+                &TypedEventHandler::<BluetoothDevice, IInspectable>::new(move |_, _| {
+                    sender.send(DeviceEvent::Changed(id)).ok();
+                    Ok(())
+                }),
+            )?;
+            self.bt_devices.push((bt_device, token));
+        }
+
+        for ble_device in find_ble_devices()? {
+            let sender = self.sender.clone();
+            let id = DeviceId::from_address(ble_device.BluetoothAddress()?);
+            let token = ble_device.ConnectionStatusChanged(
+                &TypedEventHandler::<BluetoothLEDevice, IInspectable>::new(move |_, _| {
+                    sender.send(DeviceEvent::Changed(id)).ok();
+                    Ok(())
+                }),
+            )?;
+            self.ble_devices.push((ble_device, token));
+        }
+
+        Ok(())
+    }
+
+    fn unsubscribe_devices(&mut self) {
+        for (bt_device, token) in self.bt_devices.drain(..) {
+            bt_device.RemoveConnectionStatusChanged(token).ok();
+        }
+        for (ble_device, token) in self.ble_devices.drain(..) {
+            ble_device.RemoveConnectionStatusChanged(token).ok();
+        }
+    }
+}
+
+impl ChangeSource for BluetoothWatcher {
+    fn refresh(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(self.subscribe_devices()?)
+    }
+}
+
+impl Drop for BluetoothWatcher {
+    fn drop(&mut self) {
+        self.unsubscribe_devices();
+        for device_watcher in &self.device_watchers {
+            device_watcher.Stop().ok();
+        }
+    }
+}
+
+pub fn start_change_sources(sender: Sender<DeviceEvent>) -> Result<Vec<Box<dyn ChangeSource + Send>>, Box<dyn Error>> {
+    Ok(vec![Box::new(BluetoothWatcher::start(sender)?)])
+}
+
+fn watch_paired_devices(
+    aqs_filter: &windows::core::HSTRING,
+    sender: &Sender<DeviceEvent>,
+) -> windows::core::Result<DeviceWatcher> {
+    let device_watcher = DeviceInformation::CreateWatcherAqsFilter(aqs_filter)?;
+    // Added also fires once for every already paired device before EnumerationCompleted
+    let enumerated = Arc::new(AtomicBool::new(false));
+
+    let (added_sender, added_enumerated) = (sender.clone(), Arc::clone(&enumerated));
+    device_watcher.Added(&TypedEventHandler::<DeviceWatcher, DeviceInformation>::new(
+        move |_, _| {
+            if added_enumerated.load(Ordering::Relaxed) {
+                added_sender.send(DeviceEvent::Rescan).ok();
+            };
+            Ok(())
+        },
+    ))?;
+
+    let updated_sender = sender.clone();
+    device_watcher.Updated(&TypedEventHandler::<DeviceWatcher, DeviceInformationUpdate>::new(
+        move |_, update| {
+            let address = update
+                .as_ref()
+                .and_then(|update| update.Id().ok())
+                .and_then(|id| parse_device_id_address(&id.to_string()));
+            let event = match address {
+                Some(address) => DeviceEvent::Changed(DeviceId::from_address(address)),
+                None => DeviceEvent::Rescan,
+            };
+            updated_sender.send(event).ok();
+            Ok(())
+        },
+    ))?;
+
+    let removed_sender = sender.clone();
+    device_watcher.Removed(&TypedEventHandler::<DeviceWatcher, DeviceInformationUpdate>::new(
+        move |_, _| {
+            removed_sender.send(DeviceEvent::Rescan).ok();
+            Ok(())
+        },
+    ))?;
+
+    device_watcher.EnumerationCompleted(&TypedEventHandler::<DeviceWatcher, IInspectable>::new(
+        move |_, _| {
+            enumerated.store(true, Ordering::Relaxed);
+            Ok(())
+        },
+    ))?;
+
+    device_watcher.Start()?;
+
+    Ok(device_watcher)
+}
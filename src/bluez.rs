@@ -0,0 +1,456 @@
+use zbus::blocking::{fdo::ObjectManagerProxy, Connection, Proxy};
+use zbus::fdo::ManagedObjects;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::advertisement::AdvertisementProvider;
+use crate::bluez_rfcomm::BluezSpp;
+use crate::bluez_scanner::BluezScanner;
+use crate::config::{parse_uuid, Config, CustomGattBattery};
+use crate::device_reader::{DeviceReader, DEFAULT_DEVICE_TIMEOUT};
+use crate::galaxy_buds::GalaxyBudsProvider;
+use crate::hid::HidBatteryProvider;
+use crate::identity::{parse_address, parse_bluez_object_path_address, DeviceId};
+use crate::power_supply::PowerSupplyBatteryProvider;
+use crate::provider::{
+    BatteryComponent, BatteryProvider, BluetoothInfo, ProviderRegistry, ProviderResult, UnknownReason, MAIN_BATTERY,
+};
+use crate::sony::SonyBatteryProvider;
+use crate::upower::UPowerBatteryProvider;
+use crate::watcher::DeviceEvent;
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+const BATTERY_INTERFACE: &str = "org.bluez.Battery1";
+const GATT_SERVICE_INTERFACE: &str = "org.bluez.GattService1";
+const GATT_CHARACTERISTIC_INTERFACE: &str = "org.bluez.GattCharacteristic1";
+
+pub struct BluezBatteryProvider {
+    connection: Option<Connection>,
+    service: String,
+    custom_batteries: Arc<Vec<CustomGattBattery>>,
+    reader: DeviceReader<OwnedObjectPath, BluetoothInfo>,
+}
+
+impl BluezBatteryProvider {
+    /// Connects to bluetoothd on the system bus on the first poll.
+    pub fn new() -> Self {
+        BluezBatteryProvider {
+            connection: None,
+            service: BLUEZ_SERVICE.to_string(),
+            custom_batteries: Arc::new(Vec::new()),
+            reader: DeviceReader::new(DEFAULT_DEVICE_TIMEOUT),
+        }
+    }
+
+    /// Talks to `service` over an existing connection instead, e.g. a session bus
+    /// stand-in that exports fake `org.bluez` objects.
+    #[cfg(test)]
+    pub fn with_connection(connection: Connection, service: &str) -> Self {
+        BluezBatteryProvider {
+            connection: Some(connection),
+            service: service.to_string(),
+            custom_batteries: Arc::new(Vec::new()),
+            reader: DeviceReader::new(DEFAULT_DEVICE_TIMEOUT),
+        }
+    }
+
+    /// Also reads the vendor characteristics declared in the config, for devices
+    /// bluetoothd's battery plugin doesn't know about.
+    pub fn with_custom_batteries(mut self, custom_batteries: Vec<CustomGattBattery>) -> Self {
+        self.custom_batteries = Arc::new(custom_batteries);
+        self
+    }
+
+    /// How long a poll waits for the characteristic reads of one device.
+    pub fn with_device_timeout(mut self, device_timeout: Duration) -> Self {
+        self.reader = DeviceReader::new(device_timeout);
+        self
+    }
+
+    fn connection(&mut self) -> zbus::Result<&Connection> {
+        if self.connection.is_none() {
+            self.connection = Some(Connection::system()?);
+        }
+        Ok(self.connection.as_ref().unwrap())
+    }
+}
+
+impl Default for BluezBatteryProvider {
+    fn default() -> Self {
+        BluezBatteryProvider::new()
+    }
+}
+
+impl BatteryProvider for BluezBatteryProvider {
+    fn name(&self) -> &'static str {
+        "bluez"
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        let service = self.service.clone();
+        let object_manager = ObjectManagerProxy::builder(self.connection()?)
+            .destination(service.as_str())?
+            .path("/")?
+            .build()?;
+
+        let objects = Arc::new(object_manager.get_managed_objects()?);
+        let device_paths: Vec<OwnedObjectPath> = objects
+            .iter()
+            .filter(|(_, interfaces)| interfaces.contains_key(DEVICE_INTERFACE))
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        // Battery1 comes with the managed objects, only the vendor characteristics
+        // are read from the device itself
+        let connection = self.connection()?.clone();
+        let bus_name = self.service.clone();
+        let custom_batteries = Arc::clone(&self.custom_batteries);
+        let devices_info = self.reader.read_all(device_paths, move |path| {
+            let interfaces = objects.get(path)?;
+            let mut batteries: Vec<BatteryComponent> = interfaces
+                .get(BATTERY_INTERFACE)
+                .and_then(|battery| get_property::<u8>(battery, "Percentage"))
+                .map(BatteryComponent::main)
+                .into_iter()
+                .collect();
+            batteries.extend(read_custom_batteries(&connection, &bus_name, &custom_batteries, path, &objects));
+
+            get_bluez_device_info(interfaces.get(DEVICE_INTERFACE)?, batteries)
+        });
+
+        Ok(devices_info)
+    }
+
+    fn take_device_errors(&mut self) -> Vec<(DeviceId, Box<dyn Error>)> {
+        self.reader
+            .take_timed_out()
+            .into_iter()
+            .filter_map(|path| parse_bluez_object_path_address(path.as_str()))
+            .map(|address| (DeviceId::from_address(address), "read timed out".into()))
+            .collect()
+    }
+}
+
+fn read_custom_batteries(
+    connection: &Connection,
+    bus_name: &str,
+    custom_batteries: &[CustomGattBattery],
+    device_path: &OwnedObjectPath,
+    objects: &ManagedObjects,
+) -> Vec<BatteryComponent> {
+    // e.g. /org/bluez/hci0/dev_A4_C1_38_5D_2B_1E/service0010/char0011
+    let characteristics: Vec<(&OwnedObjectPath, u128, u128)> = objects
+        .iter()
+        .filter(|(path, _)| path.as_str().starts_with(&format!("{}/", device_path.as_str())))
+        .filter_map(|(path, interfaces)| {
+            let characteristic = interfaces.get(GATT_CHARACTERISTIC_INTERFACE)?;
+            let service_path = get_property::<OwnedObjectPath>(characteristic, "Service")?;
+            let service = objects.get(&service_path)?.get(GATT_SERVICE_INTERFACE)?;
+            Some((
+                path,
+                parse_uuid(&get_property::<String>(service, "UUID")?)?,
+                parse_uuid(&get_property::<String>(characteristic, "UUID")?)?,
+            ))
+        })
+        .collect();
+
+    custom_batteries
+        .iter()
+        .flat_map(|custom_battery| {
+            characteristics
+                .iter()
+                .filter(|(_, service, characteristic)| {
+                    *service == custom_battery.service && *characteristic == custom_battery.characteristic
+                })
+                .map(|(path, ..)| {
+                    let label = custom_battery.label.as_deref().unwrap_or(MAIN_BATTERY);
+                    let level = Proxy::new(connection, bus_name, path.as_str(), GATT_CHARACTERISTIC_INTERFACE)
+                        .and_then(|proxy| proxy.call::<_, _, Vec<u8>>("ReadValue", &(HashMap::<&str, Value>::new(),)))
+                        .ok()
+                        .and_then(|value| custom_battery.rule.decode(&value));
+                    match level {
+                        Some(level) => BatteryComponent::new(label, level),
+                        None => BatteryComponent::unknown(label, UnknownReason::ReadFailed),
+                    }
+                })
+        })
+        .collect()
+}
+
+
+/// BlueZ goes first so that devices UPower mirrors from it keep BlueZ's reading,
+/// sysfs comes last as it still works when D-Bus isn't available.
+pub fn register_providers(registry: &mut ProviderRegistry, event_sender: &Sender<DeviceEvent>, config: &Config) {
+    registry.register(
+        BluezBatteryProvider::new()
+            .with_custom_batteries(config.gatt_batteries.clone())
+            .with_device_timeout(config.device_timeout.unwrap_or(DEFAULT_DEVICE_TIMEOUT)),
+    );
+    registry.register(UPowerBatteryProvider::new());
+    registry.register(PowerSupplyBatteryProvider::new());
+    registry.register(HidBatteryProvider::new());
+
+    // one system bus connection and one set of registered profiles for both
+    match BluezSpp::new() {
+        Ok(connector) => {
+            let connector = Arc::new(Mutex::new(connector));
+            registry.register(GalaxyBudsProvider::new(Arc::clone(&connector)));
+            registry.register(SonyBatteryProvider::new(connector));
+        }
+        Err(err) => {
+            registry.register_unavailable("galaxy_buds", &err);
+            registry.register_unavailable("sony", err);
+        }
+    }
+
+    match AdvertisementProvider::new(event_sender.clone(), |sink| Ok(BluezScanner::start(sink)?)) {
+        Ok(mut provider) => {
+            if let Some(voltage_curve) = &config.beacon_voltage_curve {
+                provider.set_voltage_curve(voltage_curve.clone());
+            };
+            registry.register(provider);
+        }
+        Err(err) => registry.register_unavailable("advertisement", err),
+    }
+}
+
+fn get_bluez_device_info(device: &HashMap<String, OwnedValue>, mut batteries: Vec<BatteryComponent>) -> Option<BluetoothInfo> {
+    if !get_property::<bool>(device, "Paired").unwrap_or(false) {
+        return None;
+    };
+
+    let status = get_property::<bool>(device, "Connected").unwrap_or(false);
+    // Battery1 is only exported while a device that reports its level is connected
+    if batteries.is_empty() {
+        batteries.push(match status {
+            true => BatteryComponent::unknown(MAIN_BATTERY, UnknownReason::Unsupported),
+            false => BatteryComponent::unknown(MAIN_BATTERY, UnknownReason::Disconnected),
+        });
+    };
+
+    let name = get_property::<String>(device, "Alias")
+        .or_else(|| get_property::<String>(device, "Name"))?;
+    let address = get_property::<String>(device, "Address").and_then(|a| parse_address(&a));
+
+    Some(BluetoothInfo {
+        id: DeviceId {
+            address,
+            container_id: None,
+        },
+        name,
+        batteries,
+        status,
+    })
+}
+
+pub(crate) fn get_property<T: TryFrom<OwnedValue>>(properties: &HashMap<String, OwnedValue>, name: &str) -> Option<T> {
+    properties
+        .get(name)
+        .and_then(|value| value.try_clone().ok())
+        .and_then(|value| T::try_from(value).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::gatt_rule::{DecodeRule, Endianness};
+    use crate::test_bus::TestBus;
+    use zbus::fdo::ObjectManager;
+
+    const FAKE_SERVICE: &str = "org.bluez.Fake";
+    const VENDOR_SERVICE: u128 = 0x6e400001_b5a3_f393_e0a9_e50e24dcca9e;
+    const VENDOR_CHARACTERISTIC: u128 = 0x6e400004_b5a3_f393_e0a9_e50e24dcca9e;
+
+    struct FakeDevice {
+        alias: &'static str,
+        address: &'static str,
+        paired: bool,
+        connected: bool,
+    }
+
+    #[zbus::interface(name = "org.bluez.Device1")]
+    impl FakeDevice {
+        #[zbus(property)]
+        fn alias(&self) -> String {
+            self.alias.to_string()
+        }
+
+        #[zbus(property)]
+        fn address(&self) -> String {
+            self.address.to_string()
+        }
+
+        #[zbus(property)]
+        fn paired(&self) -> bool {
+            self.paired
+        }
+
+        #[zbus(property)]
+        fn connected(&self) -> bool {
+            self.connected
+        }
+    }
+
+    struct FakeBattery {
+        percentage: u8,
+    }
+
+    #[zbus::interface(name = "org.bluez.Battery1")]
+    impl FakeBattery {
+        #[zbus(property)]
+        fn percentage(&self) -> u8 {
+            self.percentage
+        }
+    }
+
+    struct FakeGattService {
+        uuid: &'static str,
+    }
+
+    #[zbus::interface(name = "org.bluez.GattService1")]
+    impl FakeGattService {
+        #[zbus(property, name = "UUID")]
+        fn uuid(&self) -> String {
+            self.uuid.to_string()
+        }
+    }
+
+    struct FakeGattCharacteristic {
+        uuid: &'static str,
+        service: &'static str,
+        value: Vec<u8>,
+    }
+
+    #[zbus::interface(name = "org.bluez.GattCharacteristic1")]
+    impl FakeGattCharacteristic {
+        #[zbus(property, name = "UUID")]
+        fn uuid(&self) -> String {
+            self.uuid.to_string()
+        }
+
+        #[zbus(property)]
+        fn service(&self) -> OwnedObjectPath {
+            OwnedObjectPath::try_from(self.service).unwrap()
+        }
+
+        fn read_value(&self, _options: HashMap<String, OwnedValue>) -> Vec<u8> {
+            self.value.clone()
+        }
+    }
+
+    fn device(alias: &'static str, address: &'static str, paired: bool, connected: bool) -> FakeDevice {
+        FakeDevice {
+            alias,
+            address,
+            paired,
+            connected,
+        }
+    }
+
+    /// Exports a BlueZ-like object tree on the test bus, the returned connection
+    /// has to stay open for as long as the objects should be there.
+    fn export_fake_bluez(bus: &TestBus) -> Connection {
+        let connection = bus.connect();
+        let object_server = connection.object_server();
+        object_server.at("/", ObjectManager).unwrap();
+
+        let headset = "/org/bluez/hci0/dev_A4_C1_38_5D_2B_1E";
+        object_server
+            .at(headset, device("Headset", "A4:C1:38:5D:2B:1E", true, true))
+            .unwrap();
+        object_server.at(headset, FakeBattery { percentage: 80 }).unwrap();
+
+        let keyboard = "/org/bluez/hci0/dev_11_22_33_44_55_66";
+        object_server
+            .at(keyboard, device("Keyboard", "11:22:33:44:55:66", true, false))
+            .unwrap();
+
+        let stranger = "/org/bluez/hci0/dev_66_55_44_33_22_11";
+        object_server
+            .at(stranger, device("Stranger", "66:55:44:33:22:11", false, true))
+            .unwrap();
+        object_server.at(stranger, FakeBattery { percentage: 10 }).unwrap();
+
+        let sensor = "/org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF";
+        let service = "/org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF/service0010";
+        object_server
+            .at(sensor, device("Sensor", "AA:BB:CC:DD:EE:FF", true, true))
+            .unwrap();
+        object_server
+            .at(
+                service,
+                FakeGattService {
+                    uuid: "6e400001-b5a3-f393-e0a9-e50e24dcca9e",
+                },
+            )
+            .unwrap();
+        object_server
+            .at(
+                "/org/bluez/hci0/dev_AA_BB_CC_DD_EE_FF/service0010/char0011",
+                FakeGattCharacteristic {
+                    uuid: "6e400004-b5a3-f393-e0a9-e50e24dcca9e",
+                    service,
+                    value: vec![0x00, 0x37],
+                },
+            )
+            .unwrap();
+
+        drop(object_server);
+
+        connection.request_name(FAKE_SERVICE).unwrap();
+        connection
+    }
+
+    #[test]
+    fn reads_paired_devices_from_fake_bluez() {
+        let bus = TestBus::start();
+        let _bluez = export_fake_bluez(&bus);
+
+        let mut provider =
+            BluezBatteryProvider::with_connection(bus.connect(), FAKE_SERVICE).with_custom_batteries(vec![
+                CustomGattBattery {
+                    service: VENDOR_SERVICE,
+                    characteristic: VENDOR_CHARACTERISTIC,
+                    label: Some("left".to_string()),
+                    rule: DecodeRule::new(1, 1, Endianness::Little, 1.0, None).unwrap(),
+                },
+            ]);
+
+        let mut devices_info = provider.poll().unwrap();
+        devices_info.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let summary: Vec<_> = devices_info
+            .iter()
+            .map(|info| (info.name.as_str(), info.id.address, info.status, info.batteries.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "Headset",
+                    Some(0xA4C1_385D_2B1E),
+                    true,
+                    vec![BatteryComponent::main(80)]
+                ),
+                (
+                    "Keyboard",
+                    Some(0x1122_3344_5566),
+                    false,
+                    vec![BatteryComponent::unknown(MAIN_BATTERY, UnknownReason::Disconnected)]
+                ),
+                (
+                    "Sensor",
+                    Some(0xAABB_CCDD_EEFF),
+                    true,
+                    vec![BatteryComponent::new("left", 55)]
+                ),
+            ]
+        );
+    }
+}
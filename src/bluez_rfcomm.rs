@@ -0,0 +1,158 @@
+use zbus::blocking::{fdo::ObjectManagerProxy, Connection, Proxy};
+use zbus::zvariant::{self, OwnedObjectPath, OwnedValue, Value};
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::os::fd::OwnedFd;
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use crate::bluez::get_property;
+use crate::identity::parse_address;
+use crate::spp::{PairedDevice, SppConnector, SppStream};
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const PROFILE_MANAGER_INTERFACE: &str = "org.bluez.ProfileManager1";
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+const PROFILE_PATH_PREFIX: &str = "/io/github/bluegauge/spp_";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Registers a client profile with bluetoothd for every service it's asked to
+/// open, bluetoothd then does the SDP lookup and hands over the connected
+/// socket through `Profile1.NewConnection`.
+pub struct BluezSpp {
+    connection: Connection,
+    registered: HashSet<u128>,
+    sockets: Receiver<(OwnedObjectPath, OwnedFd)>,
+    socket_sender: Sender<(OwnedObjectPath, OwnedFd)>,
+}
+
+impl BluezSpp {
+    pub fn new() -> zbus::Result<Self> {
+        let (socket_sender, sockets) = mpsc::channel();
+
+        Ok(BluezSpp {
+            connection: Connection::system()?,
+            registered: HashSet::new(),
+            sockets,
+            socket_sender,
+        })
+    }
+
+    fn register_profile(&mut self, service_uuid: u128) -> zbus::Result<()> {
+        if self.registered.contains(&service_uuid) {
+            return Ok(());
+        };
+
+        let path = format!("{PROFILE_PATH_PREFIX}{service_uuid:032x}");
+        self.connection.object_server().at(
+            path.as_str(),
+            SppProfile {
+                sockets: self.socket_sender.clone(),
+            },
+        )?;
+
+        let profile_manager = Proxy::new(&self.connection, BLUEZ_SERVICE, "/org/bluez", PROFILE_MANAGER_INTERFACE)?;
+        let options = HashMap::from([
+            ("Role", Value::from("client")),
+            ("AutoConnect", Value::from(false)),
+        ]);
+        profile_manager.call_method(
+            "RegisterProfile",
+            &(zvariant::ObjectPath::try_from(path.as_str())?, format_uuid(service_uuid), options),
+        )?;
+
+        self.registered.insert(service_uuid);
+        Ok(())
+    }
+
+    fn find_devices(&self) -> zbus::Result<Vec<(OwnedObjectPath, HashMap<String, OwnedValue>)>> {
+        let object_manager = ObjectManagerProxy::builder(&self.connection)
+            .destination(BLUEZ_SERVICE)?
+            .path("/")?
+            .build()?;
+
+        Ok(object_manager
+            .get_managed_objects()?
+            .into_iter()
+            .filter_map(|(path, mut interfaces)| Some((path, interfaces.remove(DEVICE_INTERFACE)?)))
+            .collect())
+    }
+}
+
+impl SppConnector for BluezSpp {
+    fn paired_devices(&mut self) -> Result<Vec<PairedDevice>, Box<dyn Error>> {
+        let paired_devices = self
+            .find_devices()?
+            .into_iter()
+            .filter_map(|(_, device)| {
+                if !get_property::<bool>(&device, "Paired").unwrap_or(false) {
+                    return None;
+                };
+
+                let address = get_property::<String>(&device, "Address")?;
+                Some(PairedDevice {
+                    address: parse_address(&address)?,
+                    name: get_property::<String>(&device, "Alias").unwrap_or(address),
+                    connected: get_property::<bool>(&device, "Connected").unwrap_or(false),
+                })
+            })
+            .collect();
+
+        Ok(paired_devices)
+    }
+
+    fn connect(&mut self, address: u64, service_uuid: u128, read_timeout: Duration) -> Result<Box<dyn SppStream>, Box<dyn Error>> {
+        self.register_profile(service_uuid)?;
+
+        let device_path = self
+            .find_devices()?
+            .into_iter()
+            .find(|(_, device)| {
+                get_property::<String>(device, "Address").and_then(|a| parse_address(&a)) == Some(address)
+            })
+            .map(|(path, _)| path)
+            .ok_or("Device not found")?;
+
+        // sockets of earlier connections that timed out on our side
+        while self.sockets.try_recv().is_ok() {}
+
+        let device = Proxy::new(&self.connection, BLUEZ_SERVICE, device_path.as_str(), DEVICE_INTERFACE)?;
+        device.call_method("ConnectProfile", &(format_uuid(service_uuid),))?;
+
+        loop {
+            let (path, socket) = self.sockets.recv_timeout(CONNECT_TIMEOUT)?;
+            if path != device_path {
+                continue;
+            };
+
+            // an RFCOMM socket is a plain stream socket, UnixStream only adds
+            // read/write and SO_RCVTIMEO on top of the descriptor
+            let stream = UnixStream::from(socket);
+            stream.set_read_timeout(Some(read_timeout))?;
+            return Ok(Box::new(stream));
+        }
+    }
+}
+
+struct SppProfile {
+    sockets: Sender<(OwnedObjectPath, OwnedFd)>,
+}
+
+#[zbus::interface(name = "org.bluez.Profile1")]
+impl SppProfile {
+    fn new_connection(&self, device: OwnedObjectPath, fd: zvariant::OwnedFd, _properties: HashMap<String, OwnedValue>) {
+        self.sockets.send((device, fd.into())).ok();
+    }
+
+    fn request_disconnection(&self, _device: OwnedObjectPath) {}
+
+    fn release(&self) {}
+}
+
+/// e.g. "2e73a4ad-332d-41fc-90e2-16bef06523f2", the form bluetoothd expects.
+fn format_uuid(uuid: u128) -> String {
+    let hex = format!("{uuid:032x}");
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
@@ -0,0 +1,209 @@
+use zbus::blocking::{fdo::ObjectManagerProxy, Connection, MessageIterator, Proxy};
+use zbus::message::Type as MessageType;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+use zbus::MatchRule;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::advertisement::{Advertisement, AdvertisementScanner, AdvertisementSink, BondedDevice};
+use crate::bluez::get_property;
+use crate::identity::{parse_address, parse_bluez_object_path_address};
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+const ADAPTER_INTERFACE: &str = "org.bluez.Adapter1";
+const DEVICE_INTERFACE: &str = "org.bluez.Device1";
+const BLUETOOTH_STORAGE_ROOT: &str = "/var/lib/bluetooth";
+
+/// Runs an LE discovery session and picks ManufacturerData/ServiceData out of
+/// the Device1 property updates bluetoothd emits for every advertisement.
+///
+/// bluetoothd stops the session as soon as the connection that started it goes
+/// away, so the scanner keeps it open until it's dropped.
+///
+/// Private addresses only resolve when running as root, the IRKs are read from
+/// bluetoothd's storage (see `read_irk`).
+pub struct BluezScanner {
+    connection: Connection,
+    adapter: OwnedObjectPath,
+    storage_root: PathBuf,
+}
+
+impl BluezScanner {
+    pub fn start(sink: AdvertisementSink) -> zbus::Result<Self> {
+        let connection = Connection::system()?;
+        let adapter = find_adapter(&connection)?;
+
+        {
+            let adapter_proxy = Proxy::new(&connection, BLUEZ_SERVICE, adapter.as_str(), ADAPTER_INTERFACE)?;
+            let filter = HashMap::from([
+                ("Transport", Value::from("le")),
+                // without it bluetoothd only reports a device's first advertisement
+                ("DuplicateData", Value::from(true)),
+            ]);
+            adapter_proxy.call_method("SetDiscoveryFilter", &(filter,))?;
+            adapter_proxy.call_method("StartDiscovery", &())?;
+        }
+
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(BLUEZ_SERVICE)?
+            .build();
+        let messages = MessageIterator::for_match_rule(rule, &connection, None)?;
+
+        thread::spawn(move || {
+            for message in messages {
+                let Ok(message) = message else {
+                    continue;
+                };
+                let header = message.header();
+
+                let device = match header.member().map(|member| member.as_str()) {
+                    Some("PropertiesChanged") => message
+                        .body()
+                        .deserialize::<(String, HashMap<String, OwnedValue>, Vec<String>)>()
+                        .ok()
+                        .filter(|(interface, ..)| interface == DEVICE_INTERFACE)
+                        .zip(header.path())
+                        .map(|((_, properties, _), path)| (path.to_string(), properties)),
+                    Some("InterfacesAdded") => message
+                        .body()
+                        .deserialize::<(OwnedObjectPath, HashMap<String, HashMap<String, OwnedValue>>)>()
+                        .ok()
+                        .and_then(|(path, mut interfaces)| {
+                            Some((path.to_string(), interfaces.remove(DEVICE_INTERFACE)?))
+                        }),
+                    _ => None,
+                };
+
+                if let Some(advertisement) = device.and_then(|(path, properties)| convert_advertisement(&path, &properties)) {
+                    sink.receive(&advertisement);
+                };
+            }
+        });
+
+        Ok(BluezScanner {
+            connection,
+            adapter,
+            storage_root: PathBuf::from(BLUETOOTH_STORAGE_ROOT),
+        })
+    }
+}
+
+impl AdvertisementScanner for BluezScanner {
+    fn bonded_devices(&mut self) -> Result<Vec<BondedDevice>, Box<dyn Error>> {
+        let object_manager = ObjectManagerProxy::builder(&self.connection)
+            .destination(BLUEZ_SERVICE)?
+            .path("/")?
+            .build()?;
+
+        let bonded_devices = object_manager
+            .get_managed_objects()?
+            .into_values()
+            .filter_map(|interfaces| {
+                let device = interfaces.get(DEVICE_INTERFACE)?;
+                if !get_property::<bool>(device, "Paired").unwrap_or(false) {
+                    return None;
+                };
+
+                let address = get_property::<String>(device, "Address")?;
+
+                Some(BondedDevice {
+                    address: parse_address(&address)?,
+                    name: get_property::<String>(device, "Alias").unwrap_or_else(|| address.clone()),
+                    irk: read_irk(&self.storage_root, &address),
+                    connected: get_property::<bool>(device, "Connected").unwrap_or(false),
+                })
+            })
+            .collect();
+
+        Ok(bonded_devices)
+    }
+}
+
+impl Drop for BluezScanner {
+    fn drop(&mut self) {
+        if let Ok(adapter_proxy) = Proxy::new(&self.connection, BLUEZ_SERVICE, self.adapter.as_str(), ADAPTER_INTERFACE) {
+            adapter_proxy.call_method("StopDiscovery", &()).ok();
+        };
+    }
+}
+
+fn find_adapter(connection: &Connection) -> zbus::Result<OwnedObjectPath> {
+    let object_manager = ObjectManagerProxy::builder(connection)
+        .destination(BLUEZ_SERVICE)?
+        .path("/")?
+        .build()?;
+
+    object_manager
+        .get_managed_objects()?
+        .into_iter()
+        .find_map(|(path, interfaces)| interfaces.contains_key(ADAPTER_INTERFACE).then_some(path))
+        .ok_or_else(|| zbus::Error::Failure("No Bluetooth adapter".to_string()))
+}
+
+fn convert_advertisement(path: &str, properties: &HashMap<String, OwnedValue>) -> Option<Advertisement> {
+    let manufacturer_data: Vec<(u16, Vec<u8>)> = get_property::<HashMap<u16, OwnedValue>>(properties, "ManufacturerData")
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(company_id, data)| Some((company_id, Vec::<u8>::try_from(data).ok()?)))
+        .collect();
+
+    let service_data: Vec<(u16, Vec<u8>)> = get_property::<HashMap<String, OwnedValue>>(properties, "ServiceData")
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(uuid, data)| Some((parse_short_uuid(&uuid)?, Vec::<u8>::try_from(data).ok()?)))
+        .collect();
+
+    if manufacturer_data.is_empty() && service_data.is_empty() {
+        return None;
+    };
+
+    Some(Advertisement {
+        address: parse_bluez_object_path_address(path)?,
+        local_name: get_property::<String>(properties, "Name"),
+        manufacturer_data,
+        service_data,
+    })
+}
+
+/// e.g. "0000fe2c-0000-1000-8000-00805f9b34fb" is 0xFE2C, other UUIDs aren't on the Bluetooth base UUID.
+fn parse_short_uuid(uuid: &str) -> Option<u16> {
+    let (short_uuid, base) = uuid.split_at_checked(8)?;
+
+    match short_uuid.starts_with("0000") && base.eq_ignore_ascii_case("-0000-1000-8000-00805f9b34fb") {
+        true => u16::from_str_radix(&short_uuid[4..], 16).ok(),
+        false => None,
+    }
+}
+
+/// bluetoothd keeps the IRK of every bonded LE device in
+/// /var/lib/bluetooth/<adapter address>/<device address>/info, only readable by root:
+/// [IdentityResolvingKey]
+/// Key=9B7D390AA610103405ADC857A33402EC (least significant byte first)
+fn read_irk(storage_root: &Path, address: &str) -> Option<[u8; 16]> {
+    let info = fs::read_dir(storage_root)
+        .ok()?
+        .filter_map(|adapter| adapter.ok())
+        .find_map(|adapter| fs::read_to_string(adapter.path().join(address).join("info")).ok())?;
+
+    let key = info
+        .lines()
+        .skip_while(|line| line.trim() != "[IdentityResolvingKey]")
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .find_map(|line| line.trim().strip_prefix("Key="))?;
+
+    if key.len() != 32 {
+        return None;
+    };
+    let mut irk = [0u8; 16];
+    for (i, byte) in irk.iter_mut().rev().enumerate() {
+        *byte = u8::from_str_radix(key.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+
+    Some(irk)
+}
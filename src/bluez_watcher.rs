@@ -0,0 +1,59 @@
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::message::Type as MessageType;
+use zbus::MatchRule;
+
+use std::error::Error;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+use crate::identity::{parse_bluez_object_path_address, DeviceId};
+use crate::watcher::{ChangeSource, DeviceEvent};
+
+const BLUEZ_SERVICE: &str = "org.bluez";
+
+/// Forwards bluetoothd's PropertiesChanged (Device1.Connected, Battery1.Percentage, ...)
+/// and InterfacesAdded/InterfacesRemoved signals from a background thread.
+pub struct BluezWatcher;
+
+impl BluezWatcher {
+    pub fn start(connection: &Connection, sender: Sender<DeviceEvent>) -> zbus::Result<Self> {
+        let rule = MatchRule::builder()
+            .msg_type(MessageType::Signal)
+            .sender(BLUEZ_SERVICE)?
+            .build();
+        let messages = MessageIterator::for_match_rule(rule, connection, None)?;
+
+        thread::spawn(move || {
+            for message in messages {
+                let Ok(message) = message else {
+                    continue;
+                };
+                let header = message.header();
+
+                let event = match header.member().map(|member| member.as_str()) {
+                    Some("PropertiesChanged") => header
+                        .path()
+                        .and_then(|path| parse_bluez_object_path_address(path.as_str()))
+                        .map(|address| DeviceEvent::Changed(DeviceId::from_address(address))),
+                    Some("InterfacesAdded") | Some("InterfacesRemoved") => Some(DeviceEvent::Rescan),
+                    _ => None,
+                };
+
+                if let Some(event) = event {
+                    if sender.send(event).is_err() {
+                        break;
+                    };
+                };
+            }
+        });
+
+        Ok(BluezWatcher)
+    }
+}
+
+impl ChangeSource for BluezWatcher {}
+
+pub fn start_change_sources(sender: Sender<DeviceEvent>) -> Result<Vec<Box<dyn ChangeSource + Send>>, Box<dyn Error>> {
+    let watcher = Connection::system().and_then(|connection| BluezWatcher::start(&connection, sender))?;
+    Ok(vec![Box::new(watcher)])
+}
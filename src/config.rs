@@ -0,0 +1,295 @@
+use serde::Deserialize;
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::protocol::beacon::VoltageCurve;
+use crate::protocol::gatt_rule::{DecodeRule, Endianness};
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+/// The Bluetooth base UUID a 16-bit assigned number is placed into
+const BLUETOOTH_BASE_UUID: u128 = 0x00000000_0000_1000_8000_00805F9B34FB;
+
+/// Settings read from config.toml, e.g.
+///
+/// disabled_providers = ["hid"]
+/// beacon_voltage_curve = [[2000, 0], [3000, 100]]
+/// hfp_log = "/home/me/hfp.log"
+/// device_timeout_ms = 3000
+///
+/// [[gatt_battery]]
+/// service = "6e400001-b5a3-f393-e0a9-e50e24dcca9e"
+/// characteristic = "6e400004-b5a3-f393-e0a9-e50e24dcca9e"
+/// width = 2
+/// voltage_table = [[3300, 0], [3700, 50], [4200, 100]]
+#[derive(Default)]
+pub struct Config {
+    /// Names of the battery providers to turn off, e.g. "hid" or "advertisement"
+    pub disabled_providers: Vec<String>,
+    /// Overrides the coin cell curve for beacons that only report their voltage
+    pub beacon_voltage_curve: Option<VoltageCurve>,
+    /// Captured HFP AT traffic to replay, see `hfp::replay_log`
+    pub hfp_log: Option<PathBuf>,
+    /// How long a refresh waits for one device, `DEFAULT_DEVICE_TIMEOUT` if unset
+    pub device_timeout: Option<Duration>,
+    pub gatt_batteries: Vec<CustomGattBattery>,
+}
+
+/// A vendor characteristic holding a battery value, read from every LE device
+/// that has the service.
+#[derive(Clone, Debug)]
+pub struct CustomGattBattery {
+    pub service: u128,
+    pub characteristic: u128,
+    /// Component name, e.g. "left"
+    pub label: Option<String>,
+    pub rule: DecodeRule,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RawConfig {
+    disabled_providers: Vec<String>,
+    beacon_voltage_curve: Option<Vec<(u16, u8)>>,
+    hfp_log: Option<PathBuf>,
+    device_timeout_ms: Option<u64>,
+    gatt_battery: Vec<RawGattBattery>,
+}
+
+#[derive(Deserialize)]
+struct RawGattBattery {
+    service: String,
+    characteristic: String,
+    label: Option<String>,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_width")]
+    width: usize,
+    #[serde(default = "default_endianness")]
+    endianness: String,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    voltage_table: Option<Vec<(u16, u8)>>,
+}
+
+fn default_width() -> usize {
+    1
+}
+
+fn default_endianness() -> String {
+    "little".to_string()
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+impl Config {
+    /// Reads the config file, a missing file gives the defaults.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let Some(path) = get_config_path() else {
+            return Ok(Config::default());
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => return Err(format!("{}: {err}", path.display()).into()),
+        };
+
+        Config::parse(&text).map_err(|err| format!("{}: {err}", path.display()).into())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let raw: RawConfig = toml::from_str(text)?;
+
+        let beacon_voltage_curve = match raw.beacon_voltage_curve {
+            Some(points) => Some(VoltageCurve::new(points).ok_or("beacon_voltage_curve: expected [millivolts, percent] points")?),
+            None => None,
+        };
+
+        let device_timeout = match raw.device_timeout_ms {
+            Some(0) => return Err("device_timeout_ms: expected more than 0".into()),
+            timeout => timeout.map(Duration::from_millis),
+        };
+
+        let gatt_batteries = raw
+            .gatt_battery
+            .into_iter()
+            .enumerate()
+            .map(|(index, raw)| convert_gatt_battery(raw).map_err(|err| format!("gatt_battery #{}: {err}", index + 1)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Config {
+            disabled_providers: raw.disabled_providers,
+            beacon_voltage_curve,
+            hfp_log: raw.hfp_log,
+            device_timeout,
+            gatt_batteries,
+        })
+    }
+}
+
+fn convert_gatt_battery(raw: RawGattBattery) -> Result<CustomGattBattery, String> {
+    let endianness = match raw.endianness.to_ascii_lowercase().as_str() {
+        "little" => Endianness::Little,
+        "big" => Endianness::Big,
+        endianness => return Err(format!("unknown endianness \"{endianness}\"")),
+    };
+    let voltage_curve = match raw.voltage_table {
+        Some(points) => Some(VoltageCurve::new(points).ok_or("voltage_table: expected [millivolts, percent] points")?),
+        None => None,
+    };
+
+    Ok(CustomGattBattery {
+        service: parse_uuid(&raw.service).ok_or_else(|| format!("invalid service UUID \"{}\"", raw.service))?,
+        characteristic: parse_uuid(&raw.characteristic)
+            .ok_or_else(|| format!("invalid characteristic UUID \"{}\"", raw.characteristic))?,
+        label: raw.label,
+        rule: DecodeRule::new(raw.offset, raw.width, endianness, raw.scale, voltage_curve)
+            .ok_or_else(|| format!("width {} isn't 1 to 4 bytes", raw.width))?,
+    })
+}
+
+/// Accepts the full form, e.g. "6e400001-b5a3-f393-e0a9-e50e24dcca9e", or a
+/// 16-bit assigned number like "180F" or "0x2A19".
+pub fn parse_uuid(uuid: &str) -> Option<u128> {
+    let uuid = uuid.trim();
+    let short_uuid = uuid.strip_prefix("0x").or_else(|| uuid.strip_prefix("0X")).unwrap_or(uuid);
+    if short_uuid.len() == 4 {
+        return u16::from_str_radix(short_uuid, 16)
+            .ok()
+            .map(|short_uuid| BLUETOOTH_BASE_UUID | (short_uuid as u128) << 96);
+    };
+
+    let hex: String = uuid.chars().filter(|c| *c != '-').collect();
+    match uuid.len() == 36 && hex.len() == 32 {
+        true => u128::from_str_radix(&hex, 16).ok(),
+        false => None,
+    }
+}
+
+/// %APPDATA%\BlueGauge\config.toml on Windows, $XDG_CONFIG_HOME/bluegauge/config.toml
+/// (or ~/.config/bluegauge) on Linux.
+fn get_config_path() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    let config_dir = env::var_os("APPDATA").map(|app_data| PathBuf::from(app_data).join("BlueGauge"));
+    #[cfg(target_os = "linux")]
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|config_home| config_home.join("bluegauge"));
+
+    config_dir.map(|config_dir| config_dir.join(CONFIG_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVICE: &str = "6e400001-b5a3-f393-e0a9-e50e24dcca9e";
+
+    fn gatt_battery(service: &str, characteristic: &str, settings: &str) -> String {
+        format!("[[gatt_battery]]\nservice = \"{service}\"\ncharacteristic = \"{characteristic}\"\n{settings}")
+    }
+
+    #[test]
+    fn parses_every_setting() {
+        let config = Config::parse(
+            r#"
+            disabled_providers = ["hid"]
+            beacon_voltage_curve = [[3000, 100], [2000, 0]]
+            hfp_log = "/tmp/hfp.log"
+            device_timeout_ms = 3000
+
+            [[gatt_battery]]
+            service = "6e400001-b5a3-f393-e0a9-e50e24dcca9e"
+            characteristic = "6e400004-b5a3-f393-e0a9-e50e24dcca9e"
+            label = "left"
+            offset = 1
+            width = 2
+            endianness = "Big"
+            voltage_table = [[3300, 0], [4200, 100]]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.disabled_providers, vec!["hid"]);
+        assert_eq!(config.beacon_voltage_curve.map(|curve| curve.level(2500)), Some(50));
+        assert_eq!(config.hfp_log, Some(PathBuf::from("/tmp/hfp.log")));
+        assert_eq!(config.device_timeout, Some(Duration::from_millis(3000)));
+
+        let gatt_battery = &config.gatt_batteries[0];
+        assert_eq!(gatt_battery.service, 0x6e400001_b5a3_f393_e0a9_e50e24dcca9e);
+        assert_eq!(gatt_battery.characteristic, 0x6e400004_b5a3_f393_e0a9_e50e24dcca9e);
+        assert_eq!(gatt_battery.label.as_deref(), Some("left"));
+        // 3750 mV big-endian after a skipped byte
+        assert_eq!(gatt_battery.rule.decode(&[0xFF, 0x0E, 0xA6]), Some(50));
+    }
+
+    #[test]
+    fn gatt_battery_defaults_to_one_byte_percent() {
+        let config = Config::parse(&gatt_battery(SERVICE, "0x2A19", "")).unwrap();
+        let gatt_battery = &config.gatt_batteries[0];
+
+        assert_eq!(gatt_battery.characteristic, BLUETOOTH_BASE_UUID | 0x2A19 << 96);
+        assert_eq!(gatt_battery.rule.decode(&[42, 0xFF]), Some(42));
+    }
+
+    #[test]
+    fn parse_errors_name_the_setting() {
+        let cases = [
+            ("disabled_providers = \"hid\"".to_string(), "invalid type"),
+            ("beacon_voltage_curve = []".to_string(), "beacon_voltage_curve"),
+            (
+                "beacon_voltage_curve = [[3000, 101]]".to_string(),
+                "beacon_voltage_curve",
+            ),
+            ("device_timeout_ms = 0".to_string(), "device_timeout_ms"),
+            (
+                gatt_battery(SERVICE, "2A19", "width = 5"),
+                "gatt_battery #1: width 5 isn't 1 to 4 bytes",
+            ),
+            (
+                gatt_battery(SERVICE, "2A19", "width = 0"),
+                "gatt_battery #1: width 0 isn't 1 to 4 bytes",
+            ),
+            (
+                gatt_battery(SERVICE, "2A19", "endianness = \"middle\""),
+                "gatt_battery #1: unknown endianness \"middle\"",
+            ),
+            (
+                gatt_battery(SERVICE, "2A19", "voltage_table = [[3300, 200]]"),
+                "gatt_battery #1: voltage_table",
+            ),
+            (
+                gatt_battery("180", "2A19", ""),
+                "gatt_battery #1: invalid service UUID \"180\"",
+            ),
+            (
+                gatt_battery(SERVICE, "6e400004", ""),
+                "gatt_battery #1: invalid characteristic UUID",
+            ),
+            (
+                format!(
+                    "{}\n{}",
+                    gatt_battery(SERVICE, "2A19", ""),
+                    gatt_battery(SERVICE, "2A19", "width = 8")
+                ),
+                "gatt_battery #2: width 8",
+            ),
+        ];
+
+        for (text, expected) in cases {
+            let err = Config::parse(&text)
+                .err()
+                .map(|err| err.to_string())
+                .unwrap_or_default();
+            assert!(err.contains(expected), "{text}: {err}");
+        }
+    }
+}
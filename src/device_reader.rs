@@ -0,0 +1,203 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_DEVICE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Reads every device on its own thread, so one that doesn't answer can't hold
+/// up the others. A read that misses the timeout can't be cancelled (it's stuck
+/// in a blocking WinRT or D-Bus call), it finishes in the background while the
+/// device keeps its last value, and the device isn't read again until it has.
+pub struct DeviceReader<K, T> {
+    timeout: Duration,
+    last_values: HashMap<K, T>,
+    in_flight: Arc<Mutex<HashSet<K>>>,
+    timed_out: Vec<K>,
+}
+
+impl<K, T> DeviceReader<K, T>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    T: Clone + Send + 'static,
+{
+    pub fn new(timeout: Duration) -> Self {
+        DeviceReader {
+            timeout,
+            last_values: HashMap::new(),
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+            timed_out: Vec::new(),
+        }
+    }
+
+    /// Returns the values in the order of `devices`. A device `read` returns
+    /// `None` for is left out, as is a slow one that was never read before.
+    pub fn read_all<F>(&mut self, devices: Vec<K>, read: F) -> Vec<T>
+    where
+        F: Fn(&K) -> Option<T> + Send + Sync + 'static,
+    {
+        let read = Arc::new(read);
+        let (sender, receiver) = mpsc::channel();
+        let deadline = Instant::now() + self.timeout;
+
+        let mut pending = 0;
+        for (index, key) in devices.iter().enumerate() {
+            // still stuck in an earlier read, another thread wouldn't fare better
+            if !self.in_flight.lock().unwrap().insert(key.clone()) {
+                continue;
+            };
+
+            pending += 1;
+            let read = Arc::clone(&read);
+            let sender = sender.clone();
+            let in_flight = Arc::clone(&self.in_flight);
+            let key = key.clone();
+            thread::spawn(move || {
+                let value = read(&key);
+                in_flight.lock().unwrap().remove(&key);
+                sender.send((index, value)).ok();
+            });
+        }
+
+        let mut values: Vec<Option<Option<T>>> = vec![None; devices.len()];
+        while pending > 0 {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok((index, value)) => {
+                    values[index] = Some(value);
+                    pending -= 1;
+                }
+                Err(_) => break,
+            }
+        }
+
+        devices
+            .into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| match value {
+                Some(Some(value)) => {
+                    self.last_values.insert(key, value.clone());
+                    Some(value)
+                }
+                Some(None) => {
+                    self.last_values.remove(&key);
+                    None
+                }
+                None => {
+                    let last_value = self.last_values.get(&key).cloned();
+                    self.timed_out.push(key);
+                    last_value
+                }
+            })
+            .collect()
+    }
+
+    /// Devices whose read missed the timeout (or was still stuck) since the
+    /// last call, so the provider can report them.
+    pub fn take_timed_out(&mut self) -> Vec<K> {
+        std::mem::take(&mut self.timed_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+    const SLOW_READ: Duration = Duration::from_millis(500);
+    const SLOW_DEVICE: u64 = 2;
+
+    /// Reads every device as (key, generation), the slow one only after `SLOW_READ` while `slow` is set.
+    #[derive(Clone, Default)]
+    struct FakeDevices {
+        slow: Arc<AtomicBool>,
+        generation: Arc<AtomicU32>,
+        slow_reads: Arc<AtomicU32>,
+    }
+
+    impl FakeDevices {
+        fn read(&self) -> impl Fn(&u64) -> Option<(u64, u32)> + Send + Sync + 'static {
+            let devices = self.clone();
+            move |key| {
+                let generation = devices.generation.load(Ordering::SeqCst);
+                if *key == SLOW_DEVICE {
+                    devices.slow_reads.fetch_add(1, Ordering::SeqCst);
+                    if devices.slow.load(Ordering::SeqCst) {
+                        thread::sleep(SLOW_READ);
+                    };
+                };
+                Some((*key, generation))
+            }
+        }
+
+        fn next_generation(&self, slow: bool) {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+            self.slow.store(slow, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn slow_device_keeps_its_last_value_and_is_skipped_while_in_flight() {
+        let devices = FakeDevices::default();
+        let mut reader = DeviceReader::new(TIMEOUT);
+
+        assert_eq!(
+            reader.read_all(vec![1, 2, 3], devices.read()),
+            vec![(1, 0), (2, 0), (3, 0)]
+        );
+        assert!(reader.take_timed_out().is_empty());
+
+        devices.next_generation(true);
+        let started = Instant::now();
+        assert_eq!(
+            reader.read_all(vec![1, 2, 3], devices.read()),
+            vec![(1, 1), (2, 0), (3, 1)]
+        );
+        assert!(started.elapsed() < SLOW_READ);
+        assert_eq!(reader.take_timed_out(), vec![SLOW_DEVICE]);
+
+        // still stuck, so it isn't read again
+        devices.next_generation(false);
+        let started = Instant::now();
+        assert_eq!(
+            reader.read_all(vec![1, 2, 3], devices.read()),
+            vec![(1, 2), (2, 0), (3, 2)]
+        );
+        assert!(started.elapsed() < TIMEOUT);
+        assert_eq!(devices.slow_reads.load(Ordering::SeqCst), 2);
+        assert_eq!(reader.take_timed_out(), vec![SLOW_DEVICE]);
+
+        // read again once the stuck read has finished
+        thread::sleep(SLOW_READ);
+        devices.next_generation(false);
+        assert_eq!(
+            reader.read_all(vec![1, 2, 3], devices.read()),
+            vec![(1, 3), (2, 3), (3, 3)]
+        );
+        assert_eq!(devices.slow_reads.load(Ordering::SeqCst), 3);
+        assert!(reader.take_timed_out().is_empty());
+    }
+
+    #[test]
+    fn slow_device_without_a_value_is_left_out() {
+        let devices = FakeDevices::default();
+        devices.next_generation(true);
+        let mut reader = DeviceReader::new(TIMEOUT);
+
+        let started = Instant::now();
+        assert_eq!(reader.read_all(vec![1, 2, 3], devices.read()), vec![(1, 1), (3, 1)]);
+        assert!(started.elapsed() < SLOW_READ);
+        assert_eq!(reader.take_timed_out(), vec![SLOW_DEVICE]);
+    }
+
+    #[test]
+    fn device_without_a_reading_forgets_its_last_value() {
+        let mut reader = DeviceReader::new(TIMEOUT);
+
+        assert_eq!(reader.read_all(vec![1, 2], |key| Some(*key)), vec![1, 2]);
+        assert_eq!(reader.read_all(vec![1, 2], |key| (*key == 1).then_some(*key)), vec![1]);
+        assert!(reader.take_timed_out().is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::identity::DeviceId;
+
+/// A failure during a refresh together with where it happened; it only drops
+/// the readings of that source or device, everything else is still shown.
+#[derive(Debug)]
+pub enum BlueGaugeError {
+    /// The whole source couldn't be read, e.g. the PnP enumeration failed.
+    Source {
+        provider: &'static str,
+        error: Box<dyn Error>,
+    },
+    /// The source failed for a single device after a change notification.
+    Device {
+        provider: &'static str,
+        device: DeviceId,
+        error: Box<dyn Error>,
+    },
+    /// BlueGauge couldn't set something up and carries on without it, e.g. a
+    /// broken config.toml or no change notifications.
+    Setup {
+        what: &'static str,
+        error: Box<dyn Error>,
+    },
+}
+
+impl BlueGaugeError {
+    pub fn device(&self) -> Option<&DeviceId> {
+        match self {
+            BlueGaugeError::Source { .. } | BlueGaugeError::Setup { .. } => None,
+            BlueGaugeError::Device { device, .. } => Some(device),
+        }
+    }
+}
+
+impl fmt::Display for BlueGaugeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BlueGaugeError::Source { provider, error } => write!(f, "{provider} source unavailable: {error}"),
+            BlueGaugeError::Device { provider, device, error } => {
+                write!(f, "{provider} source failed to read {device:?}: {error}")
+            }
+            BlueGaugeError::Setup { what, error } => write!(f, "{what} unavailable: {error}"),
+        }
+    }
+}
+
+impl Error for BlueGaugeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BlueGaugeError::Source { error, .. }
+            | BlueGaugeError::Device { error, .. }
+            | BlueGaugeError::Setup { error, .. } => Some(error.as_ref()),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use crate::identity::DeviceId;
+use crate::provider::{BatteryComponent, BluetoothInfo};
+
+/// Attributes Fast Pair battery notifications to paired devices.
+///
+/// The notification itself carries no model id, so a reading is matched by the
+/// advertising address when it belongs to a paired device, otherwise through
+/// the model id the same address advertised before and the paired device that
+/// model id was last seen on.
+#[derive(Default)]
+pub struct FastPairTracker {
+    address_models: HashMap<u64, u32>,
+    paired_models: HashMap<u32, DeviceId>,
+}
+
+impl FastPairTracker {
+    pub fn new() -> Self {
+        FastPairTracker::default()
+    }
+
+    /// `paired` is the paired device the address belongs to, if the caller could
+    /// resolve it. Returns the battery reading, if any, and the device it's for.
+    pub fn observe(
+        &mut self,
+        address: u64,
+        paired: Option<DeviceId>,
+        model_id: Option<u32>,
+        batteries: Vec<BatteryComponent>,
+    ) -> Option<BluetoothInfo> {
+        if let Some(model_id) = model_id {
+            self.address_models.insert(address, model_id);
+        };
+
+        let model_id = self.address_models.get(&address).copied();
+        let id = match (paired, model_id) {
+            (Some(id), Some(model_id)) => {
+                self.paired_models.insert(model_id, id);
+                id
+            }
+            (Some(id), None) => id,
+            (None, Some(model_id)) => *self.paired_models.get(&model_id)?,
+            (None, None) => return None,
+        };
+
+        match batteries.is_empty() {
+            true => None,
+            false => Some(BluetoothInfo {
+                id,
+                // filled in by the caller, which knows the bonded devices
+                name: String::new(),
+                batteries,
+                // it's advertising, so it's in range and powered on
+                status: true,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAIRED: u64 = 0x001122334455;
+    const MODEL_ID: u32 = 0x0E30C3;
+
+    fn batteries() -> Vec<BatteryComponent> {
+        vec![BatteryComponent::main(80)]
+    }
+
+    #[test]
+    fn paired_address_is_attributed_directly() {
+        let mut tracker = FastPairTracker::new();
+        let id = DeviceId::from_address(PAIRED);
+
+        let info = tracker.observe(PAIRED, Some(id), None, batteries()).unwrap();
+
+        assert_eq!(info.id, id);
+        assert_eq!(info.batteries, batteries());
+        assert!(tracker.observe(PAIRED, Some(id), None, Vec::new()).is_none());
+    }
+
+    #[test]
+    fn unresolved_address_is_attributed_through_the_model_id() {
+        let mut tracker = FastPairTracker::new();
+        let id = DeviceId::from_address(PAIRED);
+        let (old_address, new_address) = (0x4A0000000001, 0x4A0000000002);
+
+        // nothing known about the model yet
+        assert!(tracker
+            .observe(new_address, None, Some(MODEL_ID), batteries())
+            .is_none());
+        // the model id is seen on the paired device, e.g. after reconnecting
+        tracker.observe(old_address, Some(id), Some(MODEL_ID), Vec::new());
+
+        // the address advertised the model id before, its notifications now count
+        assert_eq!(
+            tracker
+                .observe(new_address, None, None, batteries())
+                .map(|info| info.id),
+            Some(id)
+        );
+        assert!(tracker.observe(0x4A0000000003, None, None, batteries()).is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use std::error::Error;
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+use crate::identity::DeviceId;
+use crate::protocol::galaxy_buds::{decode_status, BudsModel, BudsStatus, FrameDecoder};
+use crate::provider::{BatteryProvider, BluetoothInfo, ProviderResult};
+use crate::spp::{PairedDevice, SppConnector, SppStream};
+
+/// The buds send an extended status right after the channel opens.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(3);
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Reads left, right and case batteries of connected Galaxy Buds from the
+/// status messages on their SPP channel.
+pub struct GalaxyBudsProvider<C> {
+    connector: C,
+    // kept as text, the provider has to be Send
+    device_errors: Vec<(DeviceId, String)>,
+}
+
+impl<C: SppConnector> GalaxyBudsProvider<C> {
+    pub fn new(connector: C) -> Self {
+        GalaxyBudsProvider {
+            connector,
+            device_errors: Vec::new(),
+        }
+    }
+
+    /// `None` for anything but connected Galaxy Buds, and for buds that
+    /// failed, their error is kept for `take_device_errors`.
+    fn read_device(&mut self, device: PairedDevice) -> Option<BluetoothInfo> {
+        let model = BudsModel::from_name(&device.name)?;
+        // opening the channel would page a device that's switched off or in its case
+        if !device.connected {
+            return None;
+        };
+
+        let status = self
+            .connector
+            .connect(device.address, model.service_uuid(), READ_TIMEOUT)
+            .and_then(|mut stream| read_status(stream.as_mut(), model));
+
+        let id = DeviceId::from_address(device.address);
+        match status {
+            Ok(Some(status)) => Some(BluetoothInfo {
+                id,
+                name: device.name,
+                batteries: status.batteries(),
+                status: true,
+            }),
+            Ok(None) => {
+                self.device_errors.push((id, "no status message".to_string()));
+                None
+            }
+            Err(err) => {
+                self.device_errors.push((id, err.to_string()));
+                None
+            }
+        }
+    }
+}
+
+impl<C: SppConnector> BatteryProvider for GalaxyBudsProvider<C> {
+    fn name(&self) -> &'static str {
+        "galaxy_buds"
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        let paired_devices = self.connector.paired_devices()?;
+        Ok(paired_devices
+            .into_iter()
+            .filter_map(|device| self.read_device(device))
+            .collect())
+    }
+
+    fn poll_device(&mut self, id: &DeviceId) -> Result<Option<BluetoothInfo>, Box<dyn Error>> {
+        // only paired classic devices have the channel
+        let Some(address) = id.address else {
+            return Ok(None);
+        };
+
+        let paired_device = self.connector.paired_device(address)?;
+        Ok(paired_device.and_then(|device| self.read_device(device)))
+    }
+
+    fn take_device_errors(&mut self) -> Vec<(DeviceId, Box<dyn Error>)> {
+        self.device_errors
+            .drain(..)
+            .map(|(id, error)| (id, error.into()))
+            .collect()
+    }
+}
+
+/// Reads from a freshly opened channel until the first status message,
+/// `None` if the buds don't send one within `STATUS_TIMEOUT`.
+pub fn read_status(stream: &mut dyn SppStream, model: BudsModel) -> Result<Option<BudsStatus>, Box<dyn Error>> {
+    let deadline = Instant::now() + STATUS_TIMEOUT;
+    let mut decoder = FrameDecoder::new(model);
+    let mut buf = [0u8; 512];
+
+    while Instant::now() < deadline {
+        let len = match stream.read(&mut buf) {
+            Ok(0) => return Ok(None),
+            Ok(len) => len,
+            Err(err) if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        let status = decoder
+            .push(&buf[..len])
+            .iter()
+            .find_map(|message| decode_status(model, message));
+        if status.is_some() {
+            return Ok(status);
+        };
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spp::FakeConnector;
+
+    #[test]
+    fn poll_device_connects_to_that_device_only() {
+        let mut provider = GalaxyBudsProvider::new(FakeConnector {
+            devices: vec![
+                (1, "Galaxy Buds2 Pro", true),
+                (2, "Galaxy Buds Live", true),
+                (3, "Galaxy Buds+", false),
+                (4, "WH-1000XM4", true),
+            ],
+            ..Default::default()
+        });
+
+        let cases = [
+            (DeviceId::from_address(1), vec![1]),
+            // not connected
+            (DeviceId::from_address(3), vec![]),
+            // not Galaxy Buds
+            (DeviceId::from_address(4), vec![]),
+            // not paired
+            (DeviceId::from_address(5), vec![]),
+            // an LE device
+            (
+                DeviceId {
+                    address: None,
+                    container_id: Some(2),
+                },
+                vec![],
+            ),
+        ];
+
+        for (id, connects) in cases {
+            provider.connector.connects.clear();
+            assert!(provider.poll_device(&id).unwrap().is_none());
+            assert_eq!(provider.connector.connects, connects, "{id:?}");
+        }
+
+        let device_errors: Vec<_> = provider.take_device_errors().into_iter().map(|(id, _)| id).collect();
+        assert_eq!(device_errors, vec![DeviceId::from_address(1)]);
+    }
+}
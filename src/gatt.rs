@@ -0,0 +1,448 @@
+use windows::{
+    core::{Error, GUID},
+    Devices::Bluetooth::GenericAttributeProfile::{
+        GattCharacteristic, GattCharacteristicProperties, GattCharacteristicUuids,
+        GattClientCharacteristicConfigurationDescriptorValue, GattCommunicationStatus,
+        GattDeviceService, GattServiceUuids, GattValueChangedEventArgs,
+    },
+    Devices::Bluetooth::{BluetoothConnectionStatus, BluetoothLEDevice},
+    Foundation::{EventRegistrationToken, TypedEventHandler},
+    Storage::Streams::{DataReader, IBuffer},
+};
+
+use std::collections::HashMap;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::bluetooth::find_ble_addresses;
+use crate::device_reader::DeviceReader;
+use crate::config::CustomGattBattery;
+use crate::identity::DeviceId;
+use crate::protocol::battery_service::{
+    decode_battery_information, decode_battery_level_status, BatteryInformation,
+    BATTERY_INFORMATION_UUID, BATTERY_LEVEL_STATUS_UUID,
+};
+use crate::protocol::descriptor::{
+    get_characteristic_label, number_unlabelled, PRESENTATION_FORMAT_UUID, USER_DESCRIPTION_UUID,
+};
+use crate::protocol::gatt_rule::DecodeRule;
+use crate::provider::{
+    BatteryComponent, BatteryLevel, BatteryProvider, BluetoothInfo, ProviderResult, UnknownReason, MAIN_BATTERY,
+};
+use crate::watcher::DeviceEvent;
+
+pub struct GattBatteryProvider {
+    state: Arc<GattState>,
+    reader: DeviceReader<u64, BluetoothInfo>,
+}
+
+/// Shared by the per-device reads, each of them runs on its own thread.
+struct GattState {
+    event_sender: Sender<DeviceEvent>,
+    custom_batteries: Vec<CustomGattBattery>,
+    // discovered once per connection, so polls don't redo the service discovery
+    battery_services: Mutex<HashMap<u64, Arc<Vec<BatteryService>>>>,
+    // characteristics still read fine without notifications, it's only reported
+    subscribe_errors: Mutex<Vec<(u64, String)>>,
+}
+
+/// One Battery Level instance, devices with several batteries (e.g. earbuds)
+/// expose one per component. Vendor characteristics declared in the config
+/// take the place of the Battery Level and bring their decode rule along.
+struct BatteryService {
+    label: String,
+    battery_level: Characteristic,
+    decode_rule: Option<DecodeRule>,
+    battery_level_status: Option<Characteristic>,
+    // static, read once when the service is discovered
+    battery_information: Option<BatteryInformation>,
+}
+
+/// The notification handler registration and the value it keeps up to date.
+type Subscription = (EventRegistrationToken, Arc<Mutex<Option<Vec<u8>>>>);
+
+/// A characteristic with notifications enabled when it supports them, the
+/// handler keeps `value` up to date so polls don't have to touch the peripheral.
+struct Characteristic {
+    gatt_char: GattCharacteristic,
+    subscription: Option<Subscription>,
+}
+
+impl Drop for Characteristic {
+    fn drop(&mut self) {
+        if let Some((token, _)) = self.subscription.take() {
+            self.gatt_char.RemoveValueChanged(token).ok();
+        };
+    }
+}
+
+impl GattBatteryProvider {
+    pub fn new(
+        event_sender: Sender<DeviceEvent>,
+        custom_batteries: Vec<CustomGattBattery>,
+        device_timeout: Duration,
+    ) -> Self {
+        GattBatteryProvider {
+            state: Arc::new(GattState {
+                event_sender,
+                custom_batteries,
+                battery_services: Mutex::new(HashMap::new()),
+                subscribe_errors: Mutex::new(Vec::new()),
+            }),
+            reader: DeviceReader::new(device_timeout),
+        }
+    }
+
+    fn get_ble_devices_info(&mut self, addresses: Vec<u64>) -> Vec<BluetoothInfo> {
+        let state = Arc::clone(&self.state);
+        self.reader
+            .read_all(addresses, move |address| state.get_ble_device_info(*address).ok())
+    }
+}
+
+impl GattState {
+    fn get_ble_device_info(&self, address: u64) -> windows::core::Result<BluetoothInfo> {
+        let ble_device = BluetoothLEDevice::FromBluetoothAddressAsync(address)?.get()?;
+        let name = ble_device.Name()?.to_string();
+        let status = ble_device
+            .ConnectionStatus()
+            .map(|status| matches!(status, BluetoothConnectionStatus::Connected))
+            .unwrap_or(false);
+
+        // notifications stop with the connection, so the services are discovered and
+        // subscribed again once it's back; discovering them while it's away would
+        // cache them with a failed subscription that never gets another try
+        if !status {
+            self.battery_services.lock().unwrap().remove(&address);
+            return Ok(BluetoothInfo {
+                id: DeviceId::from_address(address),
+                name,
+                batteries: vec![BatteryComponent::unknown(MAIN_BATTERY, UnknownReason::Disconnected)],
+                status,
+            });
+        };
+
+        let batteries = match self.get_ble_batteries(&ble_device, address) {
+            Ok(batteries) if batteries.is_empty() => {
+                vec![BatteryComponent::unknown(MAIN_BATTERY, UnknownReason::Unsupported)]
+            }
+            Ok(batteries) => batteries,
+            Err(_) => vec![BatteryComponent::unknown(MAIN_BATTERY, UnknownReason::ReadFailed)],
+        };
+
+        Ok(BluetoothInfo {
+            id: DeviceId::from_address(address),
+            name,
+            batteries,
+            status,
+        })
+    }
+
+    /// Empty when the device has no battery characteristic at all, a battery
+    /// that can't be read is kept with an unknown level.
+    fn get_ble_batteries(&self, ble_device: &BluetoothLEDevice, address: u64) -> windows::core::Result<Vec<BatteryComponent>> {
+        // the lock isn't held during discovery, that's the slow part
+        let cached_services = self.battery_services.lock().unwrap().get(&address).cloned();
+        let battery_services = match cached_services {
+            Some(battery_services) => battery_services,
+            None => {
+                let battery_services = discover_battery_services(ble_device, address, self)?;
+                // nothing to cache, discovery may have come up empty only because the device was busy
+                if battery_services.is_empty() {
+                    return Ok(Vec::new());
+                };
+                let battery_services = Arc::new(battery_services);
+                self.battery_services
+                    .lock()
+                    .unwrap()
+                    .insert(address, Arc::clone(&battery_services));
+                battery_services
+            }
+        };
+
+        let batteries = battery_services
+            .iter()
+            .map(|battery_service| {
+                battery_service
+                    .read()
+                    .unwrap_or_else(|_| BatteryComponent::unknown(&battery_service.label, UnknownReason::ReadFailed))
+            })
+            .collect();
+
+        Ok(batteries)
+    }
+}
+
+impl BatteryProvider for GattBatteryProvider {
+    fn name(&self) -> &'static str {
+        "gatt"
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        let addresses = find_ble_addresses()?;
+        Ok(self.get_ble_devices_info(addresses))
+    }
+
+    fn poll_device(&mut self, id: &DeviceId) -> Result<Option<BluetoothInfo>, Box<dyn std::error::Error>> {
+        // not a LE device (or no longer around)
+        let Some(address) = id.address else {
+            return Ok(None);
+        };
+
+        Ok(self.get_ble_devices_info(vec![address]).pop())
+    }
+
+    fn take_device_errors(&mut self) -> Vec<(DeviceId, Box<dyn std::error::Error>)> {
+        let timed_out = self
+            .reader
+            .take_timed_out()
+            .into_iter()
+            .map(|address| (address, "read timed out".to_string()));
+
+        self.state
+            .subscribe_errors
+            .lock()
+            .unwrap()
+            .drain(..)
+            .chain(timed_out)
+            .map(|(address, error)| (DeviceId::from_address(address), error.into()))
+            .collect()
+    }
+}
+
+impl BatteryService {
+    fn read(&self) -> windows::core::Result<BatteryComponent> {
+        let mut battery = BatteryComponent::new(&self.label, 0);
+
+        if let Some(decode_rule) = &self.decode_rule {
+            battery.level = decode_rule
+                .decode(&self.battery_level.value()?)
+                .map(BatteryLevel::Known)
+                .ok_or_else(Error::empty)?;
+            return Ok(battery);
+        };
+
+        // Battery Level Status carries the charging state and, optionally, the level as well
+        let level_status = self
+            .battery_level_status
+            .as_ref()
+            .and_then(|c| c.value().ok())
+            .and_then(|value| decode_battery_level_status(&value));
+        match level_status {
+            Some(level_status) => {
+                battery.charging = level_status.is_charging();
+                battery.critical = level_status.is_critical();
+                battery.level = BatteryLevel::Known(match level_status.level {
+                    Some(level) => level,
+                    None => read_battery_level(&self.battery_level)?,
+                });
+            }
+            None => battery.level = BatteryLevel::Known(read_battery_level(&self.battery_level)?),
+        };
+
+        battery.chemistry = self
+            .battery_information
+            .as_ref()
+            .and_then(|information| information.chemistry)
+            .map(|chemistry| chemistry.name());
+
+        Ok(battery)
+    }
+}
+
+impl Characteristic {
+    fn new(gatt_char: GattCharacteristic, address: u64, state: &GattState) -> Self {
+        let subscription = match subscribe(&gatt_char, address, state.event_sender.clone()) {
+            Ok(subscription) => subscription,
+            Err(err) => {
+                let error = format!("failed to subscribe characteristic: {err}");
+                state.subscribe_errors.lock().unwrap().push((address, error));
+                None
+            }
+        };
+
+        Characteristic {
+            gatt_char,
+            subscription,
+        }
+    }
+
+    /// The latest notified value, or a fresh read for characteristics that can't notify.
+    fn value(&self) -> windows::core::Result<Vec<u8>> {
+        if let Some((_, value)) = &self.subscription {
+            if let Some(value) = value.lock().unwrap().as_ref() {
+                return Ok(value.clone());
+            };
+        };
+
+        let value = read_value(&self.gatt_char)?;
+
+        if let Some((_, cached_value)) = &self.subscription {
+            *cached_value.lock().unwrap() = Some(value.clone());
+        };
+
+        Ok(value)
+    }
+}
+
+fn discover_battery_services(
+    bt_le_device: &BluetoothLEDevice,
+    address: u64,
+    state: &GattState,
+) -> windows::core::Result<Vec<BatteryService>> {
+    let battery_services_uuid: GUID = GattServiceUuids::Battery()?;
+    let battery_level_uuid: GUID = GattCharacteristicUuids::BatteryLevel()?;
+
+    let mut battery_services = Vec::new();
+    for gatt_service in get_services(bt_le_device, battery_services_uuid)? {
+        let mut battery_level_status = get_characteristics(&gatt_service, bluetooth_uuid(BATTERY_LEVEL_STATUS_UUID))?
+            .into_iter()
+            .next();
+        let mut battery_information = get_characteristics(&gatt_service, bluetooth_uuid(BATTERY_INFORMATION_UUID))?
+            .first()
+            .and_then(|gatt_char| read_value(gatt_char).ok())
+            .and_then(|value| decode_battery_information(&value));
+
+        // usually one Battery Service per component, but some devices put several
+        // Battery Levels into one service; the status and information belong to the first
+        for battery_level in get_characteristics(&gatt_service, battery_level_uuid)? {
+            battery_services.push(BatteryService {
+                label: get_characteristic_label(read_descriptor(&battery_level, PRESENTATION_FORMAT_UUID), || {
+                    read_descriptor(&battery_level, USER_DESCRIPTION_UUID)
+                })
+                .unwrap_or_default(),
+                battery_level: Characteristic::new(battery_level, address, state),
+                decode_rule: None,
+                battery_level_status: battery_level_status
+                    .take()
+                    .map(|gatt_char| Characteristic::new(gatt_char, address, state)),
+                battery_information: battery_information.take(),
+            });
+        }
+    }
+
+    for custom_battery in &state.custom_batteries {
+        for gatt_service in get_services(bt_le_device, GUID::from_u128(custom_battery.service))? {
+            for gatt_char in get_characteristics(&gatt_service, GUID::from_u128(custom_battery.characteristic))? {
+                battery_services.push(BatteryService {
+                    label: custom_battery.label.clone().unwrap_or_default(),
+                    battery_level: Characteristic::new(gatt_char, address, state),
+                    decode_rule: Some(custom_battery.rule.clone()),
+                    battery_level_status: None,
+                    battery_information: None,
+                });
+            }
+        }
+    }
+
+    number_unlabelled(
+        battery_services
+            .iter_mut()
+            .map(|battery_service| &mut battery_service.label),
+    );
+
+    Ok(battery_services)
+}
+
+fn get_services(bt_le_device: &BluetoothLEDevice, uuid: GUID) -> windows::core::Result<Vec<GattDeviceService>> {
+    let gatt_services = bt_le_device
+        .GetGattServicesForUuidAsync(uuid)
+        .and_then(|op_gatt_services_result| op_gatt_services_result.get())
+        .and_then(|gatt_services_result| gatt_services_result.Services())?;
+
+    Ok(gatt_services.into_iter().collect())
+}
+
+fn get_characteristics(gatt_service: &GattDeviceService, uuid: GUID) -> windows::core::Result<Vec<GattCharacteristic>> {
+    let gatt_chars = gatt_service
+        .GetCharacteristicsForUuidAsync(uuid)
+        .and_then(|op_gatt_chars_result| op_gatt_chars_result.get())
+        .and_then(|gatt_chars_result| gatt_chars_result.Characteristics())?;
+
+    Ok(gatt_chars
+        .into_iter()
+        .filter(|gatt_char| gatt_char.Uuid().ok() == Some(uuid))
+        .collect())
+}
+
+fn read_descriptor(gatt_char: &GattCharacteristic, uuid: u16) -> Option<Vec<u8>> {
+    let descriptors = gatt_char
+        .GetDescriptorsForUuidAsync(bluetooth_uuid(uuid))
+        .and_then(|op_gatt_descriptors_result| op_gatt_descriptors_result.get())
+        .and_then(|gatt_descriptors_result| gatt_descriptors_result.Descriptors())
+        .ok()?;
+
+    descriptors
+        .into_iter()
+        .next()?
+        .ReadValueAsync()
+        .and_then(|op_gatt_read_result| op_gatt_read_result.get())
+        .and_then(|gatt_read_result| gatt_read_result.Value())
+        .and_then(|buffer| read_bytes(&buffer))
+        .ok()
+}
+
+fn read_battery_level(battery_level: &Characteristic) -> windows::core::Result<u8> {
+    battery_level
+        .value()?
+        .first()
+        .copied()
+        .ok_or_else(Error::empty)
+}
+
+fn read_value(gatt_char: &GattCharacteristic) -> windows::core::Result<Vec<u8>> {
+    gatt_char
+        .ReadValueAsync()
+        .and_then(|op_gatt_read_result| op_gatt_read_result.get())
+        .and_then(|gatt_read_result| gatt_read_result.Value())
+        .and_then(|buffer| read_bytes(&buffer))
+}
+
+pub(crate) fn read_bytes(buffer: &IBuffer) -> windows::core::Result<Vec<u8>> {
+    let data_reader = DataReader::FromBuffer(buffer)?;
+    let mut bytes = vec![0; data_reader.UnconsumedBufferLength()? as usize];
+    data_reader.ReadBytes(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// Expands a 16-bit SIG assigned number onto the Bluetooth base UUID.
+fn bluetooth_uuid(uuid: u16) -> GUID {
+    GUID::from_u128(0x00000000_0000_1000_8000_00805F9B34FB | ((uuid as u128) << 96))
+}
+
+/// Returns `None` when the characteristic doesn't support notify, those keep being read on every poll.
+fn subscribe(
+    gatt_char: &GattCharacteristic,
+    address: u64,
+    event_sender: Sender<DeviceEvent>,
+) -> windows::core::Result<Option<Subscription>> {
+    if !gatt_char
+        .CharacteristicProperties()?
+        .contains(GattCharacteristicProperties::Notify)
+    {
+        return Ok(None);
+    };
+
+    let value = Arc::new(Mutex::new(None));
+    let handler_value = Arc::clone(&value);
+    let token = gatt_char.ValueChanged(
+        &TypedEventHandler::<GattCharacteristic, GattValueChangedEventArgs>::new(move |_, args| {
+            if let Some(args) = args.as_ref() {
+                *handler_value.lock().unwrap() = Some(read_bytes(&args.CharacteristicValue()?)?);
+                event_sender.send(DeviceEvent::Changed(DeviceId::from_address(address))).ok();
+            };
+            Ok(())
+        }),
+    )?;
+
+    let status = gatt_char
+        .WriteClientCharacteristicConfigurationDescriptorAsync(GattClientCharacteristicConfigurationDescriptorValue::Notify)
+        .and_then(|op_gatt_write_result| op_gatt_write_result.get());
+    if status != Ok(GattCommunicationStatus::Success) {
+        gatt_char.RemoveValueChanged(token).ok();
+        return Err(status.err().unwrap_or_else(Error::empty));
+    };
+
+    Ok(Some((token, value)))
+}
@@ -0,0 +1,194 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+use crate::identity::{parse_address, DeviceId};
+use crate::protocol::hfp::{parse_at_battery, HfpBattery};
+use crate::provider::{BatteryComponent, BatteryProvider, BluetoothInfo, ProviderResult};
+
+/// One AT line a headset sent over its HFP RFCOMM channel.
+pub struct AtCapture {
+    pub address: u64,
+    pub name: Option<String>,
+    pub line: String,
+}
+
+/// Headsets only report when the level changes, but a report this old most
+/// likely belongs to a headset that has gone away since.
+const CAPTURE_EXPIRY: Duration = Duration::from_secs(30 * 60);
+
+struct HfpDevice {
+    address: u64,
+    name: Option<String>,
+    battery: HfpBattery,
+    captured_at: Instant,
+}
+
+/// Keeps the last battery report of every headset seen in the captured AT
+/// traffic, whether it comes from a live RFCOMM hook or a replayed log.
+pub struct HfpBatteryProvider {
+    captures: Receiver<AtCapture>,
+    devices: Vec<HfpDevice>,
+}
+
+impl HfpBatteryProvider {
+    /// Returns the provider together with the sender capture sources feed lines into.
+    pub fn new() -> (Self, Sender<AtCapture>) {
+        let (sender, captures) = mpsc::channel();
+        let provider = HfpBatteryProvider {
+            captures,
+            devices: Vec::new(),
+        };
+        (provider, sender)
+    }
+
+    /// A provider that only knows the reports of a captured log, see `replay_log`.
+    pub fn from_log(path: &Path) -> io::Result<Self> {
+        let (provider, sender) = HfpBatteryProvider::new();
+        replay_log(path, &sender)?;
+        Ok(provider)
+    }
+
+    fn drain_captures(&mut self, now: Instant) {
+        while let Ok(capture) = self.captures.try_recv() {
+            let Some(battery) = parse_at_battery(&capture.line) else {
+                continue;
+            };
+
+            match self.devices.iter_mut().find(|device| device.address == capture.address) {
+                Some(device) => {
+                    device.name = capture.name.or(device.name.take());
+                    device.battery = battery;
+                    device.captured_at = now;
+                }
+                None => self.devices.push(HfpDevice {
+                    address: capture.address,
+                    name: capture.name,
+                    battery,
+                    captured_at: now,
+                }),
+            }
+        }
+
+        self.devices
+            .retain(|device| now.duration_since(device.captured_at) < CAPTURE_EXPIRY);
+    }
+}
+
+impl BatteryProvider for HfpBatteryProvider {
+    fn name(&self) -> &'static str {
+        "hfp"
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        self.drain_captures(Instant::now());
+
+        let devices_info = self
+            .devices
+            .iter()
+            .map(|device| {
+                let mut component = BatteryComponent::main(device.battery.level);
+                component.charging = device.battery.charging;
+
+                BluetoothInfo {
+                    id: DeviceId::from_address(device.address),
+                    // paired-device sources registered earlier provide the real name
+                    name: device.name.clone().unwrap_or_else(|| format_address(device.address)),
+                    batteries: vec![component],
+                    // a report doesn't say whether the headset is still connected,
+                    // the paired-device sources know
+                    status: false,
+                }
+            })
+            .collect();
+
+        Ok(devices_info)
+    }
+}
+
+/// Feeds a captured log into `sender`, one "<address> <AT line>" per line, e.g.
+/// "A4:C1:38:5D:2B:1E AT+IPHONEACCEV=2,1,8,2,0". Returns the number of lines sent.
+pub fn replay_log(path: &Path, sender: &Sender<AtCapture>) -> io::Result<usize> {
+    let log = fs::read_to_string(path)?;
+
+    let captures = log.lines().filter_map(|line| {
+        let (address, line) = line.trim().split_once(char::is_whitespace)?;
+        Some(AtCapture {
+            address: parse_address(address)?,
+            name: None,
+            line: line.trim().to_string(),
+        })
+    });
+
+    let mut count = 0;
+    for capture in captures {
+        if sender.send(capture).is_err() {
+            break;
+        };
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn format_address(address: u64) -> String {
+    (0..6)
+        .rev()
+        .map(|i| format!("{:02X}", (address >> (i * 8)) & 0xFF))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::BatteryLevel;
+
+    fn capture(address: u64, name: Option<&str>, line: &str) -> AtCapture {
+        AtCapture {
+            address,
+            name: name.map(str::to_string),
+            line: line.to_string(),
+        }
+    }
+
+    #[test]
+    fn keeps_the_last_report_of_each_headset() {
+        let (mut provider, sender) = HfpBatteryProvider::new();
+        sender
+            .send(capture(0xA4C1385D2B1E, Some("AirPods"), "AT+IPHONEACCEV=2,1,8,2,0"))
+            .unwrap();
+        sender.send(capture(0x001122334455, None, "AT+CIND?")).unwrap();
+        sender.send(capture(0x001122334455, None, "AT+BIEV=2,40")).unwrap();
+        sender
+            .send(capture(0xA4C1385D2B1E, None, "AT+IPHONEACCEV=1,1,4"))
+            .unwrap();
+
+        let devices_info = provider.poll().unwrap();
+
+        assert_eq!(devices_info.len(), 2);
+        assert_eq!(devices_info[0].name, "AirPods");
+        assert_eq!(devices_info[0].batteries[0].level, BatteryLevel::Known(44));
+        assert_eq!(devices_info[0].batteries[0].charging, None);
+        assert_eq!(devices_info[1].name, "00:11:22:33:44:55");
+        assert_eq!(devices_info[1].batteries[0].level, BatteryLevel::Known(40));
+        assert!(devices_info.iter().all(|info| !info.status));
+    }
+
+    #[test]
+    fn old_reports_expire() {
+        let (mut provider, sender) = HfpBatteryProvider::new();
+        let start = Instant::now();
+        sender.send(capture(0x001122334455, None, "AT+BIEV=2,40")).unwrap();
+        provider.drain_captures(start);
+        sender.send(capture(0x66778899AABB, None, "AT+BIEV=2,60")).unwrap();
+        provider.drain_captures(start + CAPTURE_EXPIRY / 2);
+
+        provider.drain_captures(start + CAPTURE_EXPIRY);
+
+        let addresses: Vec<u64> = provider.devices.iter().map(|device| device.address).collect();
+        assert_eq!(addresses, [0x66778899AABB]);
+    }
+}
@@ -0,0 +1,248 @@
+use hidapi::{DeviceInfo, HidApi, HidDevice};
+
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::identity::{parse_hid_serial_address, DeviceId};
+use crate::protocol::controller::{ControllerBattery, ControllerKind};
+use crate::protocol::hidpp::{
+    HidppDevice, HidppError, HidppTransport, DIRECT_DEVICE_INDEX, LONG_REPORT_ID, SHORT_REPORT_ID,
+};
+use crate::provider::{BatteryComponent, BatteryProvider, BluetoothInfo, ProviderResult};
+
+const LOGITECH_VENDOR_ID: u16 = 0x046D;
+/// HID++ lives in a vendor collection, (usage page, usage) of the one carrying the long reports:
+/// 0xFF00 / 2 on receivers and older devices, 0xFF43 / 0x0202 on newer Bluetooth devices
+const HIDPP_LONG_COLLECTIONS: [(u16, u16); 2] = [(0xFF00, 0x0002), (0xFF43, 0x0202)];
+const HIDPP_LONG_REPORT_LENGTH: usize = 20;
+/// Unifying, Lightspeed and Bolt receivers, devices behind them use indexes 1-6
+const LOGITECH_RECEIVER_PRODUCT_IDS: [u16; 7] = [0xC52B, 0xC532, 0xC534, 0xC539, 0xC53A, 0xC547, 0xC548];
+const RECEIVER_DEVICE_INDEXES: std::ops::RangeInclusive<u8> = 1..=6;
+
+/// How long to wait for a controller to send a report with its battery in it
+const CONTROLLER_READ_TIMEOUT: Duration = Duration::from_millis(300);
+const MAX_REPORT_LENGTH: usize = 128;
+
+/// Reads HID devices that report their battery only inside vendor specific
+/// reports: game controllers and Logitech HID++ mice and keyboards.
+#[derive(Default)]
+pub struct HidBatteryProvider {
+    // kept as text, the provider has to be Send
+    device_errors: Vec<(DeviceId, String)>,
+}
+
+impl HidBatteryProvider {
+    pub fn new() -> Self {
+        HidBatteryProvider::default()
+    }
+
+    /// Failed devices are kept for `take_device_errors`.
+    fn read_devices<'a>(&mut self, api: &HidApi, devices: impl Iterator<Item = &'a DeviceInfo>) -> Vec<BluetoothInfo> {
+        let mut devices_info: Vec<BluetoothInfo> = Vec::new();
+
+        for device_info in devices {
+            let (vendor_id, product_id) = (device_info.vendor_id(), device_info.product_id());
+
+            let result = match ControllerKind::from_ids(vendor_id, product_id) {
+                Some(kind) => get_controller_info(api, device_info, kind).map(|info| info.into_iter().collect()),
+                None if vendor_id == LOGITECH_VENDOR_ID
+                    && HIDPP_LONG_COLLECTIONS.contains(&(device_info.usage_page(), device_info.usage())) =>
+                {
+                    get_hidpp_devices_info(api, device_info)
+                }
+                None => continue,
+            };
+
+            match result {
+                // a device shows up once per collection (and per interface over USB)
+                Ok(new_devices_info) => {
+                    for info in new_devices_info {
+                        let known = devices_info.iter().any(|known| {
+                            known.name == info.name
+                                && (known.id.matches(&info.id) || known.id.is_empty() && info.id.is_empty())
+                        });
+                        if !known {
+                            devices_info.push(info);
+                        };
+                    }
+                }
+                Err(err) => self.device_errors.push((
+                    get_hid_device_id(device_info),
+                    format!("HID device {vendor_id:04X}:{product_id:04X}: {err}"),
+                )),
+            }
+        }
+
+        devices_info
+    }
+}
+
+impl BatteryProvider for HidBatteryProvider {
+    fn name(&self) -> &'static str {
+        "hid"
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        let api = HidApi::new()?;
+        Ok(self.read_devices(&api, api.device_list()))
+    }
+
+    fn poll_device(&mut self, id: &DeviceId) -> Result<Option<BluetoothInfo>, Box<dyn Error>> {
+        // devices behind a receiver have no address of their own, full polls read them
+        let Some(address) = id.address else {
+            return Ok(None);
+        };
+
+        let api = HidApi::new()?;
+        let devices = api
+            .device_list()
+            .filter(|device_info| get_hid_device_id(device_info).address == Some(address));
+        Ok(self.read_devices(&api, devices).into_iter().find(|info| info.id.matches(id)))
+    }
+
+    fn take_device_errors(&mut self) -> Vec<(DeviceId, Box<dyn Error>)> {
+        self.device_errors
+            .drain(..)
+            .map(|(id, error)| (id, error.into()))
+            .collect()
+    }
+}
+
+fn get_controller_info(
+    api: &HidApi,
+    device_info: &DeviceInfo,
+    kind: ControllerKind,
+) -> Result<Option<BluetoothInfo>, Box<dyn Error>> {
+    let device = device_info.open_device(api)?;
+    request_full_reports(&device, kind);
+
+    let Some(battery) = read_controller_battery(&device, kind)? else {
+        return Ok(None);
+    };
+
+    let mut component = BatteryComponent::main(battery.level);
+    component.charging = Some(battery.charging);
+
+    Ok(Some(BluetoothInfo {
+        id: get_hid_device_id(device_info),
+        name: device_info
+            .product_string()
+            .filter(|name| !name.is_empty())
+            .unwrap_or(kind.name())
+            .to_string(),
+        batteries: vec![component],
+        status: true,
+    }))
+}
+
+/// Over Bluetooth the controllers start out with a reduced report that has no
+/// battery in it, until the host asks for the full one.
+fn request_full_reports(device: &HidDevice, kind: ControllerKind) {
+    match kind {
+        // reading the calibration feature report switches to report 0x31 / 0x11
+        ControllerKind::DualSense => {
+            let mut buf = [0u8; 41];
+            buf[0] = 0x05;
+            device.get_feature_report(&mut buf).ok();
+        }
+        ControllerKind::DualShock4 => {
+            let mut buf = [0u8; 37];
+            buf[0] = 0x02;
+            device.get_feature_report(&mut buf).ok();
+        }
+        // subcommand 0x03 "set input report mode" to 0x30, behind a neutral rumble frame
+        ControllerKind::SwitchPro => {
+            let report = [0x01, 0x00, 0x00, 0x01, 0x40, 0x40, 0x00, 0x01, 0x40, 0x40, 0x03, 0x30];
+            device.write(&report).ok();
+        }
+        ControllerKind::XboxWireless => (),
+    }
+}
+
+fn read_controller_battery(device: &HidDevice, kind: ControllerKind) -> Result<Option<ControllerBattery>, Box<dyn Error>> {
+    let deadline = Instant::now() + CONTROLLER_READ_TIMEOUT;
+    let mut buf = [0u8; MAX_REPORT_LENGTH];
+
+    while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+        let len = device.read_timeout(&mut buf, timeout.as_millis() as i32)?;
+        if let Some(battery) = kind.decode_report(&buf[..len]) {
+            return Ok(Some(battery));
+        };
+    }
+
+    Ok(None)
+}
+
+fn get_hidpp_devices_info(api: &HidApi, device_info: &DeviceInfo) -> Result<Vec<BluetoothInfo>, Box<dyn Error>> {
+    let is_receiver = LOGITECH_RECEIVER_PRODUCT_IDS.contains(&device_info.product_id());
+    let device_indexes: Vec<u8> = match is_receiver {
+        true => RECEIVER_DEVICE_INDEXES.collect(),
+        false => vec![DIRECT_DEVICE_INDEX],
+    };
+
+    let hid_device = device_info.open_device(api)?;
+    let mut devices_info = Vec::new();
+    for device_index in device_indexes {
+        let mut device = HidppDevice::new(HidapiTransport(&hid_device), device_index);
+
+        // unpaired slots and switched off devices answer with an error
+        let Ok(Some(battery)) = device.read_battery() else {
+            continue;
+        };
+
+        let product = device_info.product_string().unwrap_or("Logitech").to_string();
+        let name = device
+            .read_name()
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| format!("{product} #{device_index}"));
+
+        devices_info.push(BluetoothInfo {
+            // devices behind a receiver share its serial number
+            id: match is_receiver {
+                true => DeviceId::default(),
+                false => get_hid_device_id(device_info),
+            },
+            name,
+            batteries: vec![battery.component()],
+            status: true,
+        });
+    }
+
+    Ok(devices_info)
+}
+
+fn get_hid_device_id(device_info: &DeviceInfo) -> DeviceId {
+    DeviceId {
+        address: device_info.serial_number().and_then(parse_hid_serial_address),
+        container_id: None,
+    }
+}
+
+struct HidapiTransport<'a>(&'a HidDevice);
+
+impl HidppTransport for HidapiTransport<'_> {
+    /// The long collection only takes long reports, HID++ 2.0 devices accept
+    /// every request as a long one.
+    fn write(&mut self, report: &[u8]) -> Result<(), HidppError> {
+        let mut long_report = report.to_vec();
+        if long_report.first() == Some(&SHORT_REPORT_ID) {
+            long_report[0] = LONG_REPORT_ID;
+        };
+        long_report.resize(HIDPP_LONG_REPORT_LENGTH, 0);
+
+        self.0
+            .write(&long_report)
+            .map(|_| ())
+            .map_err(|err| HidppError::Transport(err.to_string().into()))
+    }
+
+    fn read(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, HidppError> {
+        let mut buf = [0u8; HIDPP_LONG_REPORT_LENGTH];
+        match self.0.read_timeout(&mut buf, timeout.as_millis() as i32) {
+            Ok(0) => Ok(None),
+            Ok(len) => Ok(Some(buf[..len].to_vec())),
+            Err(err) => Err(HidppError::Transport(err.to_string().into())),
+        }
+    }
+}
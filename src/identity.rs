@@ -0,0 +1,203 @@
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeviceId {
+    /// 48-bit Bluetooth address in the low bits, as returned by `BluetoothAddress()`.
+    pub address: Option<u64>,
+    /// PnP `base_container_id`, shared by every devnode of one physical device.
+    pub container_id: Option<u128>,
+}
+
+impl DeviceId {
+    pub fn from_address(address: u64) -> Self {
+        DeviceId {
+            address: Some(address),
+            container_id: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.address.is_none() && self.container_id.is_none()
+    }
+
+    /// Two ids refer to the same device when any identity both of them know about agrees.
+    pub fn matches(&self, other: &DeviceId) -> bool {
+        match (self.address, other.address) {
+            (Some(a), Some(b)) => a == b,
+            _ => matches!((self.container_id, other.container_id), (Some(a), Some(b)) if a == b),
+        }
+    }
+
+    pub fn merge(&mut self, other: &DeviceId) {
+        self.address = self.address.or(other.address);
+        self.container_id = self.container_id.or(other.container_id);
+    }
+}
+
+/// Extracts the Bluetooth address from a `BTHENUM\` device instance id.
+///
+/// e.g.
+/// BTHENUM\{0000111E-0000-1000-8000-00805F9B34FB}_LOCALMFG&0002\7&2A0B2F4A&0&A4C1385D2B1E_C00000000
+/// BTHENUM\DEV_A4C1385D2B1E\7&18CFBE5A&0&BLUETOOTHDEVICE_A4C1385D2B1E
+#[cfg(any(target_os = "windows", test))]
+pub fn parse_bthenum_address(instance_id: &str) -> Option<u64> {
+    let mut segments = instance_id.split('\\');
+
+    if !segments.next()?.eq_ignore_ascii_case("BTHENUM") {
+        return None;
+    }
+
+    segments
+        .rev()
+        .find_map(|segment| segment.split(['&', '_']).rev().find_map(parse_address_hex))
+}
+
+fn parse_address_hex(s: &str) -> Option<u64> {
+    match s.len() == 12 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => u64::from_str_radix(s, 16).ok(),
+        false => None,
+    }
+}
+
+/// Parses the colon separated form BlueZ uses, e.g. "A4:C1:38:5D:2B:1E".
+pub fn parse_address(address: &str) -> Option<u64> {
+    let octets: Vec<&str> = address.split(':').collect();
+
+    match octets.len() == 6 && octets.iter().all(|o| o.len() == 2) {
+        true => parse_address_hex(&octets.concat()),
+        false => None,
+    }
+}
+
+/// HID serial numbers of Bluetooth devices are their address, either
+/// "a4:c1:38:5d:2b:1e" (hidraw) or "a4c1385d2b1e" (Windows).
+pub fn parse_hid_serial_address(serial: &str) -> Option<u64> {
+    parse_address(serial).or_else(|| parse_address_hex(serial))
+}
+
+/// e.g. "hid-a4:c1:38:5d:2b:1e-battery", the power_supply name the kernel gives
+/// batteries of HID devices connected over Bluetooth.
+#[cfg(any(target_os = "linux", test))]
+pub fn parse_hid_power_supply_address(name: &str) -> Option<u64> {
+    parse_address(name.strip_prefix("hid-")?.get(..17)?)
+}
+
+/// e.g. "/org/bluez/hci0/dev_A4_C1_38_5D_2B_1E"
+#[cfg(any(target_os = "linux", test))]
+pub fn parse_bluez_object_path_address(path: &str) -> Option<u64> {
+    let (_, device) = path.strip_prefix("/org/bluez/")?.rsplit_once("/dev_")?;
+    parse_address(&device.get(..17)?.replace('_', ":"))
+}
+
+/// Parses the remote address at the end of a WinRT Bluetooth device id, e.g.
+/// "BluetoothLE#BluetoothLE00:1a:7d:da:71:13-a4:c1:38:5d:2b:1e"
+#[cfg(any(target_os = "windows", test))]
+pub fn parse_device_id_address(device_id: &str) -> Option<u64> {
+    parse_address(device_id.rsplit_once('-')?.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: u64 = 0xA4C1_385D_2B1E;
+
+    #[test]
+    fn bthenum_address() {
+        let cases = [
+            (
+                r"BTHENUM\{0000111E-0000-1000-8000-00805F9B34FB}_LOCALMFG&0002\7&2A0B2F4A&0&A4C1385D2B1E_C00000000",
+                Some(ADDRESS),
+            ),
+            (
+                r"BTHENUM\{0000111E-0000-1000-8000-00805F9B34FB}_LOCALMFG&0002\7&2A0B2F4A&0&A4C1385D2B1E",
+                Some(ADDRESS),
+            ),
+            (
+                r"BTHENUM\DEV_A4C1385D2B1E\7&18CFBE5A&0&BLUETOOTHDEVICE_A4C1385D2B1E",
+                Some(ADDRESS),
+            ),
+            (
+                r"bthenum\dev_a4c1385d2b1e\7&18cfbe5a&0&bluetoothdevice_a4c1385d2b1e",
+                Some(ADDRESS),
+            ),
+            // not a Bluetooth devnode
+            (r"USB\VID_046D&PID_C52B\A4C1385D2B1E", None),
+            // address one digit short
+            (
+                r"BTHENUM\{0000111E-0000-1000-8000-00805F9B34FB}_LOCALMFG&0002\7&2A0B2F4A&0&A4C1385D2B1_C00000000",
+                None,
+            ),
+            (r"BTHENUM\", None),
+            ("", None),
+        ];
+
+        for (instance_id, address) in cases {
+            assert_eq!(parse_bthenum_address(instance_id), address, "{instance_id}");
+        }
+    }
+
+    #[test]
+    fn device_id_address() {
+        let cases = [
+            (
+                "BluetoothLE#BluetoothLE00:1a:7d:da:71:13-a4:c1:38:5d:2b:1e",
+                Some(ADDRESS),
+            ),
+            ("Bluetooth#Bluetooth00:1a:7d:da:71:13-A4:C1:38:5D:2B:1E", Some(ADDRESS)),
+            ("BluetoothLE#BluetoothLE00:1a:7d:da:71:13", None),
+            ("BluetoothLE#BluetoothLE00:1a:7d:da:71:13-a4:c1:38:5d:2b", None),
+            ("BluetoothLE#BluetoothLE00:1a:7d:da:71:13-a4:c1:38:5d:2b:zz", None),
+            ("BluetoothLE#BluetoothLE00:1a:7d:da:71:13-a4c1385d2b1e", None),
+            ("", None),
+        ];
+
+        for (device_id, address) in cases {
+            assert_eq!(parse_device_id_address(device_id), address, "{device_id}");
+        }
+    }
+
+    #[test]
+    fn linux_addresses() {
+        assert_eq!(parse_address("A4:C1:38:5D:2B:1E"), Some(ADDRESS));
+        assert_eq!(parse_address("A4:C1:38:5D:2B"), None);
+        assert_eq!(parse_address("A4:C1:38:5D:2B:1"), None);
+        assert_eq!(parse_hid_serial_address("a4:c1:38:5d:2b:1e"), Some(ADDRESS));
+        assert_eq!(parse_hid_serial_address("a4c1385d2b1e"), Some(ADDRESS));
+        assert_eq!(
+            parse_hid_power_supply_address("hid-a4:c1:38:5d:2b:1e-battery"),
+            Some(ADDRESS)
+        );
+        assert_eq!(parse_hid_power_supply_address("BAT0"), None);
+        assert_eq!(
+            parse_bluez_object_path_address("/org/bluez/hci0/dev_A4_C1_38_5D_2B_1E"),
+            Some(ADDRESS)
+        );
+        assert_eq!(
+            parse_bluez_object_path_address("/org/bluez/hci0/dev_A4_C1_38_5D_2B_1E/service0010"),
+            Some(ADDRESS)
+        );
+        assert_eq!(parse_bluez_object_path_address("/org/bluez/hci0"), None);
+    }
+
+    #[test]
+    fn ids_match_on_any_shared_identity() {
+        let by_address = DeviceId::from_address(ADDRESS);
+        let by_container = DeviceId {
+            address: None,
+            container_id: Some(7),
+        };
+        let both = DeviceId {
+            address: Some(ADDRESS),
+            container_id: Some(7),
+        };
+
+        assert!(both.matches(&by_address));
+        assert!(both.matches(&by_container));
+        assert!(!by_address.matches(&by_container));
+        // a differing address wins over an agreeing container id
+        assert!(!both.matches(&DeviceId {
+            address: Some(1),
+            container_id: Some(7),
+        }));
+        assert!(!DeviceId::default().matches(&DeviceId::default()));
+    }
+}
@@ -1,20 +1,69 @@
 #![allow(non_snake_case)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod advertisement;
+#[cfg(target_os = "windows")]
+mod ble_scanner;
+#[cfg(target_os = "windows")]
 mod bluetooth;
+#[cfg(target_os = "windows")]
+mod bluetooth_watcher;
+#[cfg(target_os = "linux")]
+mod bluez;
+#[cfg(target_os = "linux")]
+mod bluez_rfcomm;
+#[cfg(target_os = "linux")]
+mod bluez_scanner;
+#[cfg(target_os = "linux")]
+mod bluez_watcher;
+mod config;
+mod device_reader;
+mod error;
+mod fast_pair;
+mod galaxy_buds;
+#[cfg(target_os = "windows")]
+mod gatt;
+mod hfp;
+mod hid;
+mod identity;
+#[cfg(any(target_os = "windows", test))]
+mod pnp;
+#[cfg(target_os = "linux")]
+mod power_supply;
+mod protocol;
+mod provider;
+#[cfg(target_os = "windows")]
+mod rfcomm;
+mod sony;
+mod spp;
 mod systray;
+#[cfg(all(test, target_os = "linux"))]
+mod test_bus;
+#[cfg(target_os = "linux")]
+mod upower;
+mod watcher;
 use crate::systray::show_systray;
+#[cfg(target_os = "windows")]
 use win_toast_notify::WinToastNotify;
 
+#[cfg(target_os = "windows")]
 fn main() {
     if let Err(err) = show_systray() {
         WinToastNotify::new()
             .set_title("BlueGauge")
             .set_messages(vec![
                 "Failed to build the system tray.",
-                &err.message(),
+                &err.to_string(),
             ])
             .show()
             .expect("Failed to show toast notification")
     }
 }
+
+#[cfg(target_os = "linux")]
+fn main() {
+    if let Err(err) = show_systray() {
+        eprintln!("Failed to build the system tray: {err}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,132 @@
+use scalefs_windowspnp::{
+    EnumerateError, EnumeratorBackend, PnpDeviceNodeInfo, PnpDevicePropertyKey, PnpDevicePropertyValue, PnpEnumerator,
+    Uuid,
+};
+
+use std::error::Error;
+use std::io;
+
+use crate::identity::parse_bthenum_address;
+
+/// {4d36e97d-e325-11ce-bfc1-08002be10318}, the setup class the BTHENUM devnodes live in.
+const GUID_DEVCLASS_SYSTEM: Uuid = Uuid {
+    data1: 0x4D36E97D,
+    data2: 0xE325,
+    data3: 0x11CE,
+    data4: [0xBF, 0xC1, 0x08, 0x00, 0x2B, 0xE1, 0x03, 0x18],
+};
+#[allow(non_upper_case_globals)]
+const DEVPKEY_Bluetooth_Battery: PnpDevicePropertyKey = PnpDevicePropertyKey {
+    fmtid: Uuid {
+        data1: 0x104EA319,
+        data2: 0x6EE2,
+        data3: 0x4701,
+        data4: [0xBD, 0x47, 0x8D, 0xDB, 0xF4, 0x25, 0xBB, 0xE5],
+    },
+    pid: 2,
+};
+const BT_INSTANCE_ID: &str = "BTHENUM\\";
+
+pub struct PnpBtDeviceInfo {
+    pub address: Option<u64>,
+    pub container_id: Option<u128>,
+    pub battery: Option<u8>,
+}
+
+pub fn get_pnp_bt_devices_info<B: EnumeratorBackend>(enumerator: &PnpEnumerator<B>) -> Result<Vec<PnpBtDeviceInfo>, Box<dyn Error>> {
+    let bt_devices = get_pnp_bt_devices(enumerator, GUID_DEVCLASS_SYSTEM)?;
+
+    let pnp_bt_devices_info = bt_devices
+        .into_iter()
+        .map(|i| {
+            let battery = match i
+                .device_instance_properties
+                .as_ref()
+                .and_then(|properties| properties.get(&DEVPKEY_Bluetooth_Battery))
+            {
+                Some(PnpDevicePropertyValue::Byte(v)) => Some(*v),
+                _ => None,
+            };
+
+            PnpBtDeviceInfo {
+                address: parse_bthenum_address(&i.device_instance_id),
+                container_id: i.base_container_id.map(|id| id.as_u128()),
+                battery,
+            }
+        })
+        .collect();
+
+    Ok(pnp_bt_devices_info)
+}
+
+/// Only the BTHENUM devnodes and only their battery property, the class holds
+/// hundreds of other devnodes with dozens of properties each.
+fn get_pnp_bt_devices<B: EnumeratorBackend>(enumerator: &PnpEnumerator<B>, guid: Uuid) -> Result<Vec<PnpDeviceNodeInfo>, Box<dyn Error>> {
+    let property_keys = vec![DEVPKEY_Bluetooth_Battery];
+    enumerator.enumerate_present_devices_by_device_setup_class_with_property_keys(guid, BT_INSTANCE_ID, property_keys).map_err(|err| match err {
+        // win32 error codes are the raw os errors on Windows
+        EnumerateError::Win32Error(code) => io::Error::from_raw_os_error(code as i32).into(),
+        EnumerateError::StringDecodingError(err) => err.into(),
+        EnumerateError::StringTerminationDecodingError => "PnP property string without terminator".into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scalefs_windowspnp::{MemoryBackend, MemoryDeviceNode, PnpDevicePropertyBuffer};
+
+    fn system_device_node(instance_id: &str, container_id: u128, battery: Option<u8>) -> MemoryDeviceNode {
+        let mut device_node = MemoryDeviceNode::new(instance_id);
+        device_node.device_setup_class_guid = Some(GUID_DEVCLASS_SYSTEM);
+        device_node.base_container_id = Some(Uuid::from_u128(container_id));
+        if let Some(battery) = battery {
+            device_node.device_instance_properties.insert(
+                DEVPKEY_Bluetooth_Battery,
+                PnpDevicePropertyBuffer::from(&PnpDevicePropertyValue::Byte(battery)),
+            );
+        };
+        device_node
+    }
+
+    #[test]
+    fn pnp_bt_devices_come_from_bthenum_devnodes() {
+        let enumerator = PnpEnumerator::new(MemoryBackend::new(vec![
+            system_device_node(r"ACPI\PNP0C02\1", 0, Some(10)),
+            system_device_node(
+                r"BTHENUM\{0000111E-0000-1000-8000-00805F9B34FB}_LOCALMFG&0002\7&2A0B2F4A&0&A4C1385D2B1E_C00000000",
+                0x1234,
+                Some(80),
+            ),
+            // a device that doesn't report its battery has no property for it
+            system_device_node(
+                r"BTHENUM\DEV_001122334455\7&18CFBE5A&0&BLUETOOTHDEVICE_001122334455",
+                0x5678,
+                None,
+            ),
+            // without a container
+            system_device_node(
+                r"BTHENUM\DEV_66778899AABB\7&1&0&BLUETOOTHDEVICE_66778899AABB",
+                0,
+                Some(5),
+            ),
+        ]));
+
+        let pnp_bt_devices_info: Vec<_> = get_pnp_bt_devices_info(&enumerator)
+            .unwrap()
+            .into_iter()
+            .map(|info| (info.address, info.container_id, info.battery))
+            .collect();
+
+        assert_eq!(
+            pnp_bt_devices_info,
+            vec![
+                (Some(0xA4C1385D2B1E), Some(0x1234), Some(80)),
+                (Some(0x001122334455), Some(0x5678), None),
+                (Some(0x66778899AABB), None, Some(5)),
+            ]
+        );
+        // one battery property per BTHENUM devnode, the ACPI one isn't touched
+        assert_eq!(enumerator.backend().property_fetch_count(), 3);
+    }
+}
@@ -0,0 +1,274 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::identity::{parse_hid_power_supply_address, DeviceId};
+use crate::provider::{BatteryComponent, BatteryProvider, BluetoothInfo, ProviderResult};
+
+const POWER_SUPPLY_ROOT: &str = "/sys/class/power_supply";
+
+pub struct PowerSupplyBatteryProvider {
+    root: PathBuf,
+}
+
+impl PowerSupplyBatteryProvider {
+    pub fn new() -> Self {
+        PowerSupplyBatteryProvider::with_root(POWER_SUPPLY_ROOT)
+    }
+
+    /// Reads `<root>/*/` instead of the real sysfs class directory, e.g. a fixture tree.
+    pub fn with_root<P: Into<PathBuf>>(root: P) -> Self {
+        PowerSupplyBatteryProvider { root: root.into() }
+    }
+}
+
+impl Default for PowerSupplyBatteryProvider {
+    fn default() -> Self {
+        PowerSupplyBatteryProvider::new()
+    }
+}
+
+impl BatteryProvider for PowerSupplyBatteryProvider {
+    fn name(&self) -> &'static str {
+        "power_supply"
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        let mut devices_info = Vec::new();
+
+        for entry in fs::read_dir(&self.root)? {
+            if let Some(device_info) = get_power_supply_info(&entry?.path()) {
+                devices_info.push(device_info);
+            };
+        }
+
+        Ok(devices_info)
+    }
+}
+
+fn get_power_supply_info(path: &Path) -> Option<BluetoothInfo> {
+    // "System" is the laptop's own battery and AC adapter
+    if read_attribute(path, "scope")? != "Device" {
+        return None;
+    };
+
+    let level = read_attribute(path, "capacity")
+        .and_then(|capacity| capacity.parse::<u8>().ok())
+        .or_else(|| get_capacity_level_percentage(&read_attribute(path, "capacity_level")?))?;
+    let charging = read_attribute(path, "status").and_then(|status| get_status_charging(&status));
+    let name = read_attribute(path, "model_name")
+        .filter(|model_name| !model_name.is_empty())
+        .or_else(|| Some(path.file_name()?.to_string_lossy().to_string()))?;
+
+    Some(BluetoothInfo {
+        id: DeviceId {
+            address: get_power_supply_address(path),
+            container_id: None,
+        },
+        name,
+        batteries: vec![BatteryComponent {
+            charging,
+            ..BatteryComponent::main(level.min(100))
+        }],
+        // the kernel removes the entry once the device goes away
+        status: read_attribute(path, "present").is_none_or(|present| present == "1"),
+    })
+}
+
+/// The entry is usually named after its HID parent, e.g. "hid-a4:c1:38:5d:2b:1e-battery",
+/// otherwise the address is looked up along the resolved sysfs device path.
+fn get_power_supply_address(path: &Path) -> Option<u64> {
+    let resolved_path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    path.file_name()
+        .into_iter()
+        .chain(resolved_path.ancestors().filter_map(|ancestor| ancestor.file_name()))
+        .find_map(|name| parse_hid_power_supply_address(&name.to_string_lossy()))
+}
+
+fn read_attribute(path: &Path, attribute: &str) -> Option<String> {
+    fs::read_to_string(path.join(attribute))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+/// Devices that only report a coarse level, see: https://www.kernel.org/doc/Documentation/ABI/testing/sysfs-class-power
+fn get_capacity_level_percentage(capacity_level: &str) -> Option<u8> {
+    match capacity_level {
+        "Critical" => Some(5),
+        "Low" => Some(20),
+        "Normal" => Some(50),
+        "High" => Some(80),
+        "Full" => Some(100),
+        _ => None,
+    }
+}
+
+fn get_status_charging(status: &str) -> Option<bool> {
+    match status {
+        "Charging" => Some(true),
+        "Discharging" | "Not charging" | "Full" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::os::unix::fs::symlink;
+    use std::process;
+
+    /// A throwaway power_supply class directory.
+    struct Fixture {
+        root: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let root = env::temp_dir().join(format!("bluegauge-{name}-{}", process::id()));
+            fs::remove_dir_all(&root).ok();
+            fs::create_dir_all(&root).unwrap();
+            Fixture { root }
+        }
+
+        fn add(&self, entry: &str, attributes: &[(&str, &str)]) -> PathBuf {
+            let path = self.root.join(entry);
+            fs::create_dir_all(&path).unwrap();
+            for (attribute, value) in attributes {
+                fs::write(path.join(attribute), format!("{value}\n")).unwrap();
+            }
+            path
+        }
+
+        fn poll(&self) -> Vec<BluetoothInfo> {
+            let mut devices_info = PowerSupplyBatteryProvider::with_root(&self.root).poll().unwrap();
+            devices_info.sort_by(|a, b| a.name.cmp(&b.name));
+            devices_info
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.root).ok();
+        }
+    }
+
+    #[test]
+    fn reads_device_batteries() {
+        let fixture = Fixture::new("power-supply-devices");
+        fixture.add(
+            "hid-a4:c1:38:5d:2b:1e-battery",
+            &[
+                ("scope", "Device"),
+                ("capacity", "75"),
+                ("status", "Charging"),
+                ("model_name", "MX Master 3"),
+                ("present", "1"),
+            ],
+        );
+        fixture.add(
+            "BAT0",
+            &[("scope", "System"), ("capacity", "90"), ("status", "Discharging")],
+        );
+        fixture.add("AC", &[("scope", "System")]);
+        fixture.add(
+            "wacom_battery_0",
+            &[
+                ("scope", "Device"),
+                ("capacity_level", "Low"),
+                ("status", "Discharging"),
+            ],
+        );
+        fixture.add(
+            "gone_battery",
+            &[("scope", "Device"), ("capacity", "120"), ("present", "0")],
+        );
+        fixture.add("no_level_battery", &[("scope", "Device"), ("status", "Unknown")]);
+
+        let summary: Vec<_> = fixture
+            .poll()
+            .into_iter()
+            .map(|info| (info.name, info.id.address, info.status, info.batteries))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "MX Master 3".to_string(),
+                    Some(0xA4C1_385D_2B1E),
+                    true,
+                    vec![BatteryComponent {
+                        charging: Some(true),
+                        ..BatteryComponent::main(75)
+                    }]
+                ),
+                (
+                    "gone_battery".to_string(),
+                    None,
+                    false,
+                    vec![BatteryComponent::main(100)]
+                ),
+                (
+                    "wacom_battery_0".to_string(),
+                    None,
+                    true,
+                    vec![BatteryComponent {
+                        charging: Some(false),
+                        ..BatteryComponent::main(20)
+                    }]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_address_along_the_device_path() {
+        let fixture = Fixture::new("power-supply-symlink");
+        let device = fixture.add(
+            "devices/0005:054C:0CE6.0003/hid-11:22:33:44:55:66-input/power_supply/ps-controller-battery",
+            &[("scope", "Device"), ("capacity", "40")],
+        );
+        fs::create_dir(fixture.root.join("class")).unwrap();
+        symlink(&device, fixture.root.join("class/ps-controller-battery")).unwrap();
+
+        let devices_info = PowerSupplyBatteryProvider::with_root(fixture.root.join("class"))
+            .poll()
+            .unwrap();
+        assert_eq!(devices_info.len(), 1);
+        assert_eq!(devices_info[0].id, DeviceId::from_address(0x1122_3344_5566));
+    }
+
+    #[test]
+    fn missing_root_fails_the_poll() {
+        let fixture = Fixture::new("power-supply-missing");
+        assert!(PowerSupplyBatteryProvider::with_root(fixture.root.join("missing"))
+            .poll()
+            .is_err());
+    }
+
+    #[test]
+    fn maps_coarse_levels_and_status() {
+        let levels = [
+            ("Critical", Some(5)),
+            ("Low", Some(20)),
+            ("Normal", Some(50)),
+            ("High", Some(80)),
+            ("Full", Some(100)),
+            ("Unknown", None),
+        ];
+        for (capacity_level, level) in levels {
+            assert_eq!(get_capacity_level_percentage(capacity_level), level, "{capacity_level}");
+        }
+
+        let statuses = [
+            ("Charging", Some(true)),
+            ("Discharging", Some(false)),
+            ("Not charging", Some(false)),
+            ("Full", Some(false)),
+            ("Unknown", None),
+        ];
+        for (status, charging) in statuses {
+            assert_eq!(get_status_charging(status), charging, "{status}");
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! Apple "proximity pairing" advertisements that AirPods and Beats broadcast
+//! while the lid is open or the buds are in use.
+//!
+//! Manufacturer data of company 0x004C, without the company id:
+//! 07 19 01 0E20 2B 99 8F 01 ...
+//! |  |  |  |    |  |  |  lid open counter, bit 3 set while closed
+//! |  |  |  |    |  |  charging flags (high nibble) and case level (low nibble)
+//! |  |  |  |    |  right (high nibble) and left (low nibble) level, swapped when flipped
+//! |  |  |  |    status, in-ear flags in the low nibble, bit 5 clear when flipped
+//! |  |  |  model, e.g. 0x0E20 AirPods Pro
+//! |  |  paired
+//! |  length of the rest
+//! proximity pairing message type
+
+use crate::provider::{BatteryComponent, CASE_BATTERY, LEFT_BATTERY, MAIN_BATTERY, RIGHT_BATTERY};
+
+pub const APPLE_COMPANY_ID: u16 = 0x004C;
+
+const PROXIMITY_PAIRING_TYPE: u8 = 0x07;
+/// paired flag, model, status, levels, charging flags and case level, lid
+const PROXIMITY_PAIRING_LENGTH: usize = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModelKind {
+    /// Two buds and a charging case
+    Earbuds,
+    /// One battery for the whole device, e.g. AirPods Max, Beats Solo
+    Headphones,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bud {
+    /// 0-100, `None` when the device reports 0xF (not in range, or not known yet)
+    pub level: Option<u8>,
+    pub charging: bool,
+    pub in_ear: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProximityPairing {
+    pub model: u16,
+    pub left: Bud,
+    pub right: Bud,
+    pub case_level: Option<u8>,
+    pub case_charging: bool,
+    pub lid_open: bool,
+}
+
+impl ProximityPairing {
+    /// e.g. "AirPods Pro", for devices whose paired name isn't known.
+    pub fn model_name(&self) -> Option<&'static str> {
+        get_model(self.model).map(|(name, _)| name)
+    }
+
+    pub fn model_kind(&self) -> ModelKind {
+        get_model(self.model).map_or(ModelKind::Earbuds, |(_, kind)| kind)
+    }
+
+    /// Maps the reading onto the provider battery components, skipping unknown levels.
+    pub fn batteries(&self) -> Vec<BatteryComponent> {
+        let component = |name: &str, level: Option<u8>, charging: bool| {
+            level.map(|level| {
+                let mut component = BatteryComponent::new(name, level);
+                component.charging = Some(charging);
+                component
+            })
+        };
+
+        match self.model_kind() {
+            // headphones report their single battery in whichever side slot is filled
+            ModelKind::Headphones => {
+                let bud = match self.right.level.is_some() {
+                    true => self.right,
+                    false => self.left,
+                };
+                component(MAIN_BATTERY, bud.level, bud.charging).into_iter().collect()
+            }
+            ModelKind::Earbuds => [
+                component(LEFT_BATTERY, self.left.level, self.left.charging),
+                component(RIGHT_BATTERY, self.right.level, self.right.charging),
+                component(CASE_BATTERY, self.case_level, self.case_charging),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+        }
+    }
+}
+
+/// Decodes the manufacturer data of company 0x004C, `None` for any other
+/// Apple message (Handoff, Nearby, iBeacon...) or a truncated payload.
+pub fn decode_proximity_pairing(data: &[u8]) -> Option<ProximityPairing> {
+    let [message_type, length, rest @ ..] = data else {
+        return None;
+    };
+
+    if *message_type != PROXIMITY_PAIRING_TYPE
+        || (*length as usize) < PROXIMITY_PAIRING_LENGTH
+        || rest.len() < PROXIMITY_PAIRING_LENGTH
+    {
+        return None;
+    };
+
+    let model = u16::from_be_bytes([rest[1], rest[2]]);
+    let status = rest[3];
+    let levels = rest[4];
+    let flags_and_case = rest[5];
+    let lid = rest[6];
+
+    // bit 5 tells which bud is broadcasting, all side-specific fields are
+    // relative to that bud rather than fixed to left and right
+    let flipped = status & 0x20 == 0;
+    let (primary, secondary) = (levels & 0x0F, levels >> 4);
+    let charging_flags = flags_and_case >> 4;
+    let (primary_in_ear, secondary_in_ear) = (status & 0x02 != 0, status & 0x08 != 0);
+
+    let primary = Bud {
+        level: scale_level(primary),
+        charging: charging_flags & 0x01 != 0,
+        in_ear: primary_in_ear,
+    };
+    let secondary = Bud {
+        level: scale_level(secondary),
+        charging: charging_flags & 0x02 != 0,
+        in_ear: secondary_in_ear,
+    };
+    let (left, right) = match flipped {
+        true => (secondary, primary),
+        false => (primary, secondary),
+    };
+
+    Some(ProximityPairing {
+        model,
+        left,
+        right,
+        case_level: scale_level(flags_and_case & 0x0F),
+        case_charging: charging_flags & 0x04 != 0,
+        lid_open: lid & 0x08 == 0,
+    })
+}
+
+/// Levels come in steps of 10%, 0xF means unknown.
+fn scale_level(level: u8) -> Option<u8> {
+    match level {
+        0..=10 => Some(level * 10),
+        _ => None,
+    }
+}
+
+fn get_model(model: u16) -> Option<(&'static str, ModelKind)> {
+    let model = match model {
+        0x0220 => ("AirPods", ModelKind::Earbuds),
+        0x0F20 => ("AirPods (2nd generation)", ModelKind::Earbuds),
+        0x1320 => ("AirPods (3rd generation)", ModelKind::Earbuds),
+        0x1920 => ("AirPods 4", ModelKind::Earbuds),
+        0x1B20 => ("AirPods 4 (ANC)", ModelKind::Earbuds),
+        0x0E20 => ("AirPods Pro", ModelKind::Earbuds),
+        0x1420 | 0x2420 => ("AirPods Pro (2nd generation)", ModelKind::Earbuds),
+        0x0A20 | 0x1F20 => ("AirPods Max", ModelKind::Headphones),
+        0x0320 => ("Powerbeats3", ModelKind::Headphones),
+        0x0B20 => ("Powerbeats Pro", ModelKind::Earbuds),
+        0x0D20 => ("Powerbeats4", ModelKind::Headphones),
+        0x0520 => ("BeatsX", ModelKind::Headphones),
+        0x1020 => ("Beats Flex", ModelKind::Headphones),
+        0x0620 => ("Beats Solo3", ModelKind::Headphones),
+        0x0C20 => ("Beats Solo Pro", ModelKind::Headphones),
+        0x0920 => ("Beats Studio3", ModelKind::Headphones),
+        0x1720 => ("Beats Studio Pro", ModelKind::Headphones),
+        0x1120 => ("Beats Studio Buds", ModelKind::Earbuds),
+        0x1620 => ("Beats Studio Buds+", ModelKind::Earbuds),
+        0x1220 => ("Beats Fit Pro", ModelKind::Earbuds),
+        0x1D20 => ("Powerbeats Pro 2", ModelKind::Earbuds),
+        _ => return None,
+    };
+
+    Some(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::BatteryLevel;
+
+    /// Proximity pairing frame with the 16 encrypted trailing bytes zeroed.
+    fn frame(model: u16, status: u8, levels: u8, flags_and_case: u8, lid: u8) -> Vec<u8> {
+        let [model_high, model_low] = model.to_be_bytes();
+        let mut data = vec![
+            0x07,
+            0x19,
+            0x01,
+            model_high,
+            model_low,
+            status,
+            levels,
+            flags_and_case,
+            lid,
+            0x0F,
+            0x00,
+        ];
+        data.extend([0; 16]);
+        data
+    }
+
+    fn bud(level: Option<u8>, charging: bool, in_ear: bool) -> Bud {
+        Bud {
+            level,
+            charging,
+            in_ear,
+        }
+    }
+
+    #[test]
+    fn airpods_pro_in_ears() {
+        let pairing = decode_proximity_pairing(&frame(0x0E20, 0x2B, 0x99, 0x8F, 0x01)).unwrap();
+
+        assert_eq!(
+            pairing,
+            ProximityPairing {
+                model: 0x0E20,
+                left: bud(Some(90), false, true),
+                right: bud(Some(90), false, true),
+                case_level: None,
+                case_charging: false,
+                lid_open: true,
+            }
+        );
+        assert_eq!(pairing.model_name(), Some("AirPods Pro"));
+        assert_eq!(
+            pairing.batteries(),
+            [
+                BatteryComponent::new(LEFT_BATTERY, 90),
+                BatteryComponent::new(RIGHT_BATTERY, 90)
+            ]
+            .map(|mut component| {
+                component.charging = Some(false);
+                component
+            })
+        );
+    }
+
+    #[test]
+    fn flipped_layout_swaps_the_sides() {
+        // broadcast by the right bud: its level, charging and in-ear bits come first
+        let pairing = decode_proximity_pairing(&frame(0x1420, 0x02, 0x57, 0x16, 0x01)).unwrap();
+
+        assert_eq!(pairing.left, bud(Some(50), false, false));
+        assert_eq!(pairing.right, bud(Some(70), true, true));
+        assert_eq!(pairing.case_level, Some(60));
+
+        // the same reading broadcast by the left bud
+        let unflipped = decode_proximity_pairing(&frame(0x1420, 0x28, 0x75, 0x26, 0x01)).unwrap();
+        assert_eq!((unflipped.left, unflipped.right), (pairing.left, pairing.right));
+    }
+
+    #[test]
+    fn charging_bits() {
+        let cases = [
+            (0x05, (false, false, false)),
+            (0x15, (true, false, false)),
+            (0x25, (false, true, false)),
+            (0x45, (false, false, true)),
+            (0x75, (true, true, true)),
+        ];
+
+        for (flags_and_case, (left, right, case)) in cases {
+            let pairing = decode_proximity_pairing(&frame(0x0F20, 0x20, 0x88, flags_and_case, 0x09)).unwrap();
+            assert_eq!(
+                (pairing.left.charging, pairing.right.charging, pairing.case_charging),
+                (left, right, case),
+                "{flags_and_case:#04x}"
+            );
+            assert!(!pairing.lid_open);
+        }
+    }
+
+    #[test]
+    fn unknown_levels_are_skipped() {
+        // right bud out of range and the case lid closed long ago
+        let pairing = decode_proximity_pairing(&frame(0x0F20, 0x22, 0xF8, 0x0F, 0x09)).unwrap();
+
+        assert_eq!(
+            (pairing.left.level, pairing.right.level, pairing.case_level),
+            (Some(80), None, None)
+        );
+        let names: Vec<_> = pairing
+            .batteries()
+            .into_iter()
+            .map(|component| component.name)
+            .collect();
+        assert_eq!(names, [LEFT_BATTERY]);
+        // only 0-10 are levels
+        assert_eq!(
+            decode_proximity_pairing(&frame(0x0F20, 0x22, 0xBB, 0x0C, 0x09))
+                .unwrap()
+                .batteries(),
+            []
+        );
+    }
+
+    #[test]
+    fn headphones_have_one_battery() {
+        let cases = [
+            // AirPods Max, level in the left slot
+            (0x0A20, 0x21, 0xF7, Some(70)),
+            // Beats Studio Pro, level in the right slot
+            (0x1720, 0x21, 0x6F, Some(60)),
+            (0x0620, 0x21, 0xFF, None),
+        ];
+
+        for (model, status, levels, level) in cases {
+            let pairing = decode_proximity_pairing(&frame(model, status, levels, 0x0F, 0x01)).unwrap();
+            assert_eq!(pairing.model_kind(), ModelKind::Headphones);
+            let batteries = pairing.batteries();
+            assert_eq!(
+                batteries
+                    .first()
+                    .map(|component| (component.name.as_str(), component.level)),
+                level.map(|level| (MAIN_BATTERY, BatteryLevel::Known(level))),
+                "{model:#06x}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_model_counts_as_earbuds() {
+        let pairing = decode_proximity_pairing(&frame(0x7720, 0x20, 0x55, 0x05, 0x01)).unwrap();
+
+        assert_eq!(pairing.model_name(), None);
+        assert_eq!(pairing.model_kind(), ModelKind::Earbuds);
+        assert_eq!(pairing.batteries().len(), 3);
+    }
+
+    #[test]
+    fn other_messages() {
+        // Nearby Info, Handoff
+        assert_eq!(
+            decode_proximity_pairing(&[0x10, 0x05, 0x01, 0x18, 0x44, 0x5A, 0x2B]),
+            None
+        );
+        assert_eq!(decode_proximity_pairing(&[0x0C, 0x0E, 0x00, 0x5A, 0x4B]), None);
+        assert_eq!(
+            decode_proximity_pairing(&frame(0x0E20, 0x2B, 0x99, 0x8F, 0x01)[..8]),
+            None
+        );
+        assert_eq!(decode_proximity_pairing(&[0x07, 0x03, 0x01, 0x0E, 0x20]), None);
+        assert_eq!(decode_proximity_pairing(&[]), None);
+    }
+}
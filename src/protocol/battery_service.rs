@@ -0,0 +1,362 @@
+//! Battery Service 1.1 characteristics beyond the single byte Battery Level (0x2A19).
+//!
+//! see: https://www.bluetooth.com/specifications/specs/battery-service/ and the GATT Specification Supplement
+
+use super::ByteReader;
+
+pub const BATTERY_LEVEL_STATUS_UUID: u16 = 0x2BED;
+pub const BATTERY_INFORMATION_UUID: u16 = 0x2BEC;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChargeState {
+    Unknown,
+    Charging,
+    DischargingActive,
+    DischargingInactive,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChargeLevel {
+    Unknown,
+    Good,
+    Low,
+    Critical,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChargingType {
+    Unknown,
+    ConstantCurrent,
+    ConstantVoltage,
+    Trickle,
+    Float,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatteryLevelStatus {
+    pub battery_present: bool,
+    /// `None` when the device reports the power source state as unknown.
+    pub wired_external_power: Option<bool>,
+    pub wireless_external_power: Option<bool>,
+    pub charge_state: ChargeState,
+    pub charge_level: ChargeLevel,
+    pub charging_type: ChargingType,
+    pub fault_battery: bool,
+    pub fault_external_power: bool,
+    pub fault_other: bool,
+    pub identifier: Option<u16>,
+    pub level: Option<u8>,
+    pub service_required: Option<bool>,
+    pub battery_fault: Option<bool>,
+}
+
+impl BatteryLevelStatus {
+    pub fn is_charging(&self) -> Option<bool> {
+        match self.charge_state {
+            ChargeState::Charging => Some(true),
+            ChargeState::DischargingActive | ChargeState::DischargingInactive => Some(false),
+            ChargeState::Unknown => None,
+        }
+    }
+
+    pub fn is_critical(&self) -> bool {
+        self.charge_level == ChargeLevel::Critical
+    }
+}
+
+/// Decodes a Battery Level Status (0x2BED) value.
+pub fn decode_battery_level_status(data: &[u8]) -> Option<BatteryLevelStatus> {
+    let mut reader = ByteReader::new(data);
+    let flags = reader.read_u8()?;
+    let power_state = reader.read_u16()?;
+
+    let identifier = reader.read_if(flags & 0x01 != 0, ByteReader::read_u16)?;
+    let level = reader.read_if(flags & 0x02 != 0, ByteReader::read_u8)?;
+    let additional_status = reader.read_if(flags & 0x04 != 0, ByteReader::read_u8)?;
+
+    Some(BatteryLevelStatus {
+        battery_present: power_state & 0x0001 != 0,
+        wired_external_power: decode_tristate((power_state >> 1) & 0b11),
+        wireless_external_power: decode_tristate((power_state >> 3) & 0b11),
+        charge_state: match (power_state >> 5) & 0b11 {
+            1 => ChargeState::Charging,
+            2 => ChargeState::DischargingActive,
+            3 => ChargeState::DischargingInactive,
+            _ => ChargeState::Unknown,
+        },
+        charge_level: match (power_state >> 7) & 0b11 {
+            1 => ChargeLevel::Good,
+            2 => ChargeLevel::Low,
+            3 => ChargeLevel::Critical,
+            _ => ChargeLevel::Unknown,
+        },
+        charging_type: match (power_state >> 9) & 0b111 {
+            1 => ChargingType::ConstantCurrent,
+            2 => ChargingType::ConstantVoltage,
+            3 => ChargingType::Trickle,
+            4 => ChargingType::Float,
+            _ => ChargingType::Unknown,
+        },
+        fault_battery: power_state & 0x1000 != 0,
+        fault_external_power: power_state & 0x2000 != 0,
+        fault_other: power_state & 0x4000 != 0,
+        identifier,
+        level: level.filter(|level| *level <= 100),
+        service_required: additional_status.and_then(|status| decode_tristate((status & 0b11).into())),
+        battery_fault: additional_status.map(|status| status & 0x04 != 0),
+    })
+}
+
+/// 0 = no, 1 = yes, 2 = unknown, 3 = reserved
+fn decode_tristate(value: u16) -> Option<bool> {
+    match value {
+        0 => Some(false),
+        1 => Some(true),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatteryChemistry {
+    Unknown,
+    Alkaline,
+    LeadAcid,
+    LithiumIronDisulfide,
+    LithiumManganeseDioxide,
+    LithiumIon,
+    LithiumPolymer,
+    NickelOxyhydroxide,
+    NickelCadmium,
+    NickelMetalHydride,
+    SilverOxide,
+    ZincChloride,
+    ZincAir,
+    ZincCarbon,
+    Other,
+}
+
+impl BatteryChemistry {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => BatteryChemistry::Alkaline,
+            2 => BatteryChemistry::LeadAcid,
+            3 => BatteryChemistry::LithiumIronDisulfide,
+            4 => BatteryChemistry::LithiumManganeseDioxide,
+            5 => BatteryChemistry::LithiumIon,
+            6 => BatteryChemistry::LithiumPolymer,
+            7 => BatteryChemistry::NickelOxyhydroxide,
+            8 => BatteryChemistry::NickelCadmium,
+            9 => BatteryChemistry::NickelMetalHydride,
+            10 => BatteryChemistry::SilverOxide,
+            11 => BatteryChemistry::ZincChloride,
+            12 => BatteryChemistry::ZincAir,
+            13 => BatteryChemistry::ZincCarbon,
+            255 => BatteryChemistry::Other,
+            _ => BatteryChemistry::Unknown,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BatteryChemistry::Unknown => "Unknown",
+            BatteryChemistry::Alkaline => "Alkaline",
+            BatteryChemistry::LeadAcid => "Lead Acid",
+            BatteryChemistry::LithiumIronDisulfide => "LiFeS2",
+            BatteryChemistry::LithiumManganeseDioxide => "LiMnO2",
+            BatteryChemistry::LithiumIon => "Li-ion",
+            BatteryChemistry::LithiumPolymer => "Li-Po",
+            BatteryChemistry::NickelOxyhydroxide => "NiOOH",
+            BatteryChemistry::NickelCadmium => "NiCd",
+            BatteryChemistry::NickelMetalHydride => "NiMH",
+            BatteryChemistry::SilverOxide => "Silver Oxide",
+            BatteryChemistry::ZincChloride => "Zinc Chloride",
+            BatteryChemistry::ZincAir => "Zinc Air",
+            BatteryChemistry::ZincCarbon => "Zinc Carbon",
+            BatteryChemistry::Other => "Other",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatteryInformation {
+    pub replaceable: bool,
+    pub rechargeable: bool,
+    /// Days since 1970-01-01.
+    pub manufacture_date: Option<u32>,
+    pub expiration_date: Option<u32>,
+    /// kWh
+    pub designed_capacity: Option<f32>,
+    pub low_energy: Option<f32>,
+    pub critical_energy: Option<f32>,
+    pub chemistry: Option<BatteryChemistry>,
+    /// Volts
+    pub nominal_voltage: Option<f32>,
+    pub aggregation_group: Option<u8>,
+}
+
+/// Decodes a Battery Information (0x2BEC) value.
+pub fn decode_battery_information(data: &[u8]) -> Option<BatteryInformation> {
+    let mut reader = ByteReader::new(data);
+    let flags = reader.read_u16()?;
+    let features = reader.read_u8()?;
+
+    let manufacture_date = reader.read_if(flags & 0x0001 != 0, ByteReader::read_u24)?;
+    let expiration_date = reader.read_if(flags & 0x0002 != 0, ByteReader::read_u24)?;
+    let designed_capacity = reader.read_if(flags & 0x0004 != 0, ByteReader::read_u16)?;
+    let low_energy = reader.read_if(flags & 0x0008 != 0, ByteReader::read_u16)?;
+    let critical_energy = reader.read_if(flags & 0x0010 != 0, ByteReader::read_u16)?;
+    let chemistry = reader.read_if(flags & 0x0020 != 0, ByteReader::read_u8)?;
+    let nominal_voltage = reader.read_if(flags & 0x0040 != 0, ByteReader::read_u16)?;
+    let aggregation_group = reader.read_if(flags & 0x0080 != 0, ByteReader::read_u8)?;
+
+    Some(BatteryInformation {
+        replaceable: features & 0x01 != 0,
+        rechargeable: features & 0x02 != 0,
+        manufacture_date,
+        expiration_date,
+        designed_capacity: designed_capacity.and_then(decode_medfloat16),
+        low_energy: low_energy.and_then(decode_medfloat16),
+        critical_energy: critical_energy.and_then(decode_medfloat16),
+        chemistry: chemistry.map(BatteryChemistry::from_u8),
+        nominal_voltage: nominal_voltage.and_then(decode_medfloat16),
+        aggregation_group,
+    })
+}
+
+/// IEEE 11073 16-bit SFLOAT: 4-bit signed exponent, 12-bit signed mantissa.
+/// NaN, NRes, ±INFINITY and the reserved value decode to `None`, they are only
+/// special with an exponent of 0.
+pub fn decode_medfloat16(raw: u16) -> Option<f32> {
+    if matches!(raw, 0x07FE..=0x0802) {
+        return None;
+    };
+
+    let mantissa = (((raw & 0x0FFF) << 4) as i16 >> 4) as f32;
+    let exponent = (raw as i16) >> 12;
+
+    Some(mantissa * 10f32.powi(exponent.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: Option<f32>, expected: f32) {
+        let value = value.unwrap();
+        assert!((value - expected).abs() < 1e-4, "{value} != {expected}");
+    }
+
+    #[test]
+    fn level_status_with_every_field() {
+        // present, wired power yes, wireless power unknown, charging, critical,
+        // constant current, other fault; identifier 0x0106, level 5, service required + battery fault
+        let status = decode_battery_level_status(&[0x07, 0xB3, 0x43, 0x06, 0x01, 0x05, 0x05]).unwrap();
+
+        assert_eq!(
+            status,
+            BatteryLevelStatus {
+                battery_present: true,
+                wired_external_power: Some(true),
+                wireless_external_power: None,
+                charge_state: ChargeState::Charging,
+                charge_level: ChargeLevel::Critical,
+                charging_type: ChargingType::ConstantCurrent,
+                fault_battery: false,
+                fault_external_power: false,
+                fault_other: true,
+                identifier: Some(0x0106),
+                level: Some(5),
+                service_required: Some(true),
+                battery_fault: Some(true),
+            }
+        );
+        assert_eq!(status.is_charging(), Some(true));
+        assert!(status.is_critical());
+    }
+
+    #[test]
+    fn level_status_without_optional_fields() {
+        // discharging (inactive), low
+        let status = decode_battery_level_status(&[0x00, 0x61, 0x01]).unwrap();
+
+        assert_eq!(status.charge_state, ChargeState::DischargingInactive);
+        assert_eq!(status.charge_level, ChargeLevel::Low);
+        assert_eq!(status.wired_external_power, Some(false));
+        assert_eq!(
+            (
+                status.identifier,
+                status.level,
+                status.service_required,
+                status.battery_fault
+            ),
+            (None, None, None, None)
+        );
+        assert_eq!(status.is_charging(), Some(false));
+        assert!(!status.is_critical());
+    }
+
+    #[test]
+    fn level_status_rejects_bad_values() {
+        // level flagged but missing
+        assert_eq!(decode_battery_level_status(&[0x02, 0x01, 0x00]), None);
+        assert_eq!(decode_battery_level_status(&[0x00, 0x01]), None);
+        assert_eq!(decode_battery_level_status(&[]), None);
+        // levels above 100% are prohibited
+        assert_eq!(
+            decode_battery_level_status(&[0x02, 0x01, 0x00, 0x65]).unwrap().level,
+            None
+        );
+    }
+
+    #[test]
+    fn battery_information() {
+        // manufactured 19000 days after the epoch, 0.015 kWh, Li-ion, 3.7 V
+        let information =
+            decode_battery_information(&[0x65, 0x00, 0x03, 0x38, 0x4A, 0x00, 0x0F, 0xD0, 0x05, 0x25, 0xF0]).unwrap();
+
+        assert!(information.replaceable);
+        assert!(information.rechargeable);
+        assert_eq!(information.manufacture_date, Some(19000));
+        assert_eq!(information.expiration_date, None);
+        assert_close(information.designed_capacity, 0.015);
+        assert_eq!(information.chemistry, Some(BatteryChemistry::LithiumIon));
+        assert_eq!(information.chemistry.map(|chemistry| chemistry.name()), Some("Li-ion"));
+        assert_close(information.nominal_voltage, 3.7);
+        assert_eq!(information.aggregation_group, None);
+
+        // the chemistry is flagged but missing
+        assert_eq!(decode_battery_information(&[0x20, 0x00, 0x00]), None);
+        assert_eq!(decode_battery_information(&[0x00, 0x00]), None);
+    }
+
+    #[test]
+    fn chemistry_codes() {
+        assert_eq!(BatteryChemistry::from_u8(0), BatteryChemistry::Unknown);
+        assert_eq!(BatteryChemistry::from_u8(6), BatteryChemistry::LithiumPolymer);
+        assert_eq!(BatteryChemistry::from_u8(13), BatteryChemistry::ZincCarbon);
+        assert_eq!(BatteryChemistry::from_u8(14), BatteryChemistry::Unknown);
+        assert_eq!(BatteryChemistry::from_u8(255), BatteryChemistry::Other);
+    }
+
+    #[test]
+    fn medfloat16() {
+        // NaN, NRes, +INFINITY, reserved, -INFINITY
+        for raw in [0x07FF, 0x0800, 0x07FE, 0x0801, 0x0802] {
+            assert_eq!(decode_medfloat16(raw), None, "{raw:#06x}");
+        }
+
+        let cases = [
+            (0x0000, 0.0),
+            (0x0FFF, -1.0),
+            (0xF025, 3.7),
+            (0xD00F, 0.015),
+            // the special mantissas are regular numbers with any other exponent
+            (0x17FE, 20460.0),
+            (0x1800, -20480.0),
+            (0xF7FF, 204.7),
+        ];
+        for (raw, value) in cases {
+            assert_close(decode_medfloat16(raw), value);
+        }
+    }
+}
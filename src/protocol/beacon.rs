@@ -0,0 +1,331 @@
+//! Sensor beacons that advertise their battery instead of being paired.
+//!
+//! see: https://bthome.io/format/
+//! see: https://docs.ruuvi.com/communication/bluetooth-advertisements/data-format-5-rawv2
+//! see: https://github.com/google/eddystone/blob/master/eddystone-tlm/tlm-plain.md
+
+use super::ByteReader;
+
+pub const BTHOME_SERVICE_UUID: u16 = 0xFCD2;
+pub const RUUVI_COMPANY_ID: u16 = 0x0499;
+pub const EDDYSTONE_SERVICE_UUID: u16 = 0xFEAA;
+
+const BTHOME_VERSION: u8 = 2;
+const BTHOME_ENCRYPTED: u8 = 0x01;
+const BTHOME_BATTERY: u8 = 0x01;
+const BTHOME_VOLTAGE_MILLIVOLT: u8 = 0x0C;
+const BTHOME_VOLTAGE_DECIVOLT: u8 = 0x4A;
+const BTHOME_TEXT: u8 = 0x53;
+const BTHOME_RAW: u8 = 0x54;
+
+const RUUVI_RAWV5: u8 = 0x05;
+const RUUVI_INVALID_VOLTAGE: u16 = 0x07FF;
+
+const EDDYSTONE_TLM_FRAME: u8 = 0x20;
+const EDDYSTONE_TLM_PLAIN: u8 = 0x00;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BeaconBattery {
+    pub level: Option<u8>,
+    pub voltage_mv: Option<u16>,
+}
+
+/// Decodes BTHome v2 service data (0xFCD2). Encrypted payloads can't be read
+/// without the bind key and are skipped.
+pub fn decode_bthome(data: &[u8]) -> Option<BeaconBattery> {
+    let mut reader = ByteReader::new(data);
+
+    let device_information = reader.read_u8()?;
+    if device_information >> 5 != BTHOME_VERSION || device_information & BTHOME_ENCRYPTED != 0 {
+        return None;
+    };
+
+    let mut battery = BeaconBattery::default();
+    while let Some(object_id) = reader.read_u8() {
+        match object_id {
+            BTHOME_BATTERY => battery.level = Some(reader.read_u8()?.min(100)),
+            BTHOME_VOLTAGE_MILLIVOLT => battery.voltage_mv = Some(reader.read_u16()?),
+            BTHOME_VOLTAGE_DECIVOLT => battery.voltage_mv = Some(reader.read_u16()?.saturating_mul(100)),
+            BTHOME_TEXT | BTHOME_RAW => {
+                let len = reader.read_u8()? as usize;
+                reader.read_bytes(len)?;
+            }
+            object_id => match get_bthome_object_size(object_id) {
+                Some(size) => {
+                    reader.read_bytes(size)?;
+                }
+                // objects are in ascending id order, but an unknown one can't be skipped
+                None => break,
+            },
+        }
+    }
+
+    match battery == BeaconBattery::default() {
+        true => None,
+        false => Some(battery),
+    }
+}
+
+fn get_bthome_object_size(object_id: u8) -> Option<usize> {
+    let size = match object_id {
+        0x00 | 0x09 | 0x0F..=0x11 | 0x15..=0x2F | 0x3A | 0x46 | 0x57..=0x59 | 0x60 => 1,
+        0x02 | 0x03 | 0x06..=0x08 | 0x0D | 0x0E | 0x12..=0x14 | 0x3C | 0x3D | 0x3F..=0x41 | 0x43..=0x45
+        | 0x47..=0x49 | 0x51 | 0x52 | 0x56 | 0x5A | 0x5D..=0x5F | 0x61 | 0xF0 => 2,
+        0x04 | 0x05 | 0x0A | 0x0B | 0x42 | 0x4B | 0xF2 => 3,
+        0x3E | 0x4C..=0x50 | 0x55 | 0x5B | 0x5C | 0xF1 => 4,
+        _ => return None,
+    };
+
+    Some(size)
+}
+
+/// Decodes Ruuvi data format 5 (RAWv2) manufacturer data of company 0x0499.
+pub fn decode_ruuvi(data: &[u8]) -> Option<BeaconBattery> {
+    if *data.first()? != RUUVI_RAWV5 {
+        return None;
+    };
+
+    // 11 bits of millivolts above 1.6 V, followed by 5 bits of tx power
+    let power_info = u16::from_be_bytes([*data.get(13)?, *data.get(14)?]);
+    let voltage = power_info >> 5;
+
+    match voltage == RUUVI_INVALID_VOLTAGE {
+        true => None,
+        false => Some(BeaconBattery {
+            level: None,
+            voltage_mv: Some(voltage + 1600),
+        }),
+    }
+}
+
+/// Decodes an unencrypted Eddystone-TLM frame of the 0xFEAA service data.
+pub fn decode_eddystone_tlm(data: &[u8]) -> Option<BeaconBattery> {
+    let [EDDYSTONE_TLM_FRAME, EDDYSTONE_TLM_PLAIN, voltage_high, voltage_low, ..] = *data else {
+        return None;
+    };
+
+    // 0 means the beacon doesn't measure its battery
+    match u16::from_be_bytes([voltage_high, voltage_low]) {
+        0 => None,
+        voltage => Some(BeaconBattery {
+            level: None,
+            voltage_mv: Some(voltage),
+        }),
+    }
+}
+
+/// Piecewise linear mapping of a cell voltage onto a percentage, for formats
+/// that only report the voltage.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoltageCurve {
+    /// (millivolts, percent), sorted by ascending voltage
+    points: Vec<(u16, u8)>,
+}
+
+impl VoltageCurve {
+    pub fn new(mut points: Vec<(u16, u8)>) -> Option<Self> {
+        points.sort_by_key(|(voltage, _)| *voltage);
+
+        match points.is_empty() || points.iter().any(|(_, level)| *level > 100) {
+            true => None,
+            false => Some(VoltageCurve { points }),
+        }
+    }
+
+    pub fn level(&self, voltage_mv: u16) -> u8 {
+        let first = self.points[0];
+        let last = self.points[self.points.len() - 1];
+        if voltage_mv <= first.0 {
+            return first.1;
+        };
+        if voltage_mv >= last.0 {
+            return last.1;
+        };
+
+        let (low, high) = self
+            .points
+            .windows(2)
+            .map(|points| (points[0], points[1]))
+            .find(|(_, high)| voltage_mv <= high.0)
+            .unwrap_or((last, last));
+
+        let span = (high.0 - low.0).max(1) as i32;
+        let level = low.1 as i32 + (high.1 as i32 - low.1 as i32) * (voltage_mv - low.0) as i32 / span;
+        level as u8
+    }
+}
+
+impl Default for VoltageCurve {
+    /// A CR2032 coin cell under the light load of a beacon.
+    fn default() -> Self {
+        VoltageCurve {
+            points: vec![(2000, 0), (2600, 10), (2800, 40), (2900, 70), (3000, 100)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(level: u8) -> Option<BeaconBattery> {
+        Some(BeaconBattery {
+            level: Some(level),
+            voltage_mv: None,
+        })
+    }
+
+    fn voltage(voltage_mv: u16) -> Option<BeaconBattery> {
+        Some(BeaconBattery {
+            level: None,
+            voltage_mv: Some(voltage_mv),
+        })
+    }
+
+    #[test]
+    fn bthome_objects_are_skipped_by_size() {
+        let cases = [
+            // packet id, humidity, button
+            (0x00, 1),
+            (0x2E, 1),
+            (0x3A, 1),
+            // temperature, humidity, count, device type id
+            (0x02, 2),
+            (0x03, 2),
+            (0x3D, 2),
+            (0xF0, 2),
+            // pressure, illuminance, firmware version
+            (0x04, 3),
+            (0x05, 3),
+            (0xF2, 3),
+            // count, timestamp, firmware version
+            (0x3E, 4),
+            (0x50, 4),
+            (0xF1, 4),
+        ];
+
+        for (object_id, size) in cases {
+            assert_eq!(get_bthome_object_size(object_id), Some(size), "{object_id:#04x}");
+
+            let mut data = vec![0x40, object_id];
+            data.extend(vec![0xAA; size]);
+            data.extend([BTHOME_BATTERY, 0x55]);
+            assert_eq!(decode_bthome(&data), level(85), "{object_id:#04x}");
+        }
+    }
+
+    #[test]
+    fn bthome_payloads() {
+        let cases: [(&[u8], Option<BeaconBattery>); 10] = [
+            // packet id, battery, temperature 23.45 °C
+            (&[0x40, 0x00, 0x0C, 0x01, 0x4B, 0x02, 0x29, 0x09], level(75)),
+            (&[0x44, 0x01, 0xFF], level(100)),
+            (&[0x40, 0x0C, 0xB8, 0x0B], voltage(3000)),
+            (&[0x40, 0x4A, 0x1E, 0x00], voltage(3000)),
+            (
+                &[0x40, 0x01, 0x64, 0x0C, 0x02, 0x0C],
+                Some(BeaconBattery {
+                    level: Some(100),
+                    voltage_mv: Some(3074),
+                }),
+            ),
+            // text, then the battery
+            (&[0x40, 0x53, 0x03, b'a', b'b', b'c', 0x01, 0x10], level(16)),
+            // encrypted, or BTHome v1
+            (&[0x41, 0x01, 0x64], None),
+            (&[0x20, 0x01, 0x64], None),
+            // an unknown object ends the payload
+            (&[0x40, 0xE0, 0x01, 0x01, 0x64], None),
+            // truncated temperature
+            (&[0x40, 0x01, 0x64, 0x02, 0x29], None),
+        ];
+
+        for (data, expected) in cases {
+            assert_eq!(decode_bthome(data), expected, "{data:02X?}");
+        }
+    }
+
+    #[test]
+    fn ruuvi_rawv2() {
+        // valid, maximum and minimum values of the format's test vectors
+        let cases: [(&str, Option<BeaconBattery>); 3] = [
+            ("0512FC5394C37C0004FFFC040CAC364200CDCBB8334C884F", voltage(2977)),
+            ("057FFFFFFEFFFE7FFF7FFF7FFFFFDEFEFFFECBB8334C884F", voltage(3646)),
+            ("058001000000008001800180010000000000CBB8334C884F", voltage(1600)),
+        ];
+
+        for (hex, expected) in cases {
+            let data: Vec<u8> = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+                .collect();
+            assert_eq!(decode_ruuvi(&data), expected, "{hex}");
+        }
+
+        // invalid voltage, another data format, truncated
+        let mut invalid = vec![0x05; 24];
+        invalid[13..15].copy_from_slice(&[0xFF, 0xE0]);
+        assert_eq!(decode_ruuvi(&invalid), None);
+        assert_eq!(decode_ruuvi(&[0x03, 0x29, 0x1A, 0x1E, 0xCE, 0x1E]), None);
+        assert_eq!(decode_ruuvi(&[0x05, 0x12, 0xFC]), None);
+    }
+
+    #[test]
+    fn eddystone_tlm() {
+        let cases: [(&[u8], Option<BeaconBattery>); 5] = [
+            (
+                &[
+                    0x20, 0x00, 0x0B, 0xB8, 0x19, 0x00, 0x00, 0x00, 0x01, 0x2C, 0x00, 0x00, 0x17, 0x70,
+                ],
+                voltage(3000),
+            ),
+            // no battery measurement
+            (&[0x20, 0x00, 0x00, 0x00, 0x80, 0x00], None),
+            // encrypted TLM, UID frame, truncated
+            (&[0x20, 0x01, 0x0B, 0xB8], None),
+            (&[0x00, 0xE7, 0x0B, 0xB8], None),
+            (&[0x20, 0x00, 0x0B], None),
+        ];
+
+        for (data, expected) in cases {
+            assert_eq!(decode_eddystone_tlm(data), expected, "{data:02X?}");
+        }
+    }
+
+    #[test]
+    fn voltage_curve_interpolation() {
+        let curve = VoltageCurve::default();
+        let cases = [
+            (1900, 0),
+            (2000, 0),
+            (2300, 5),
+            (2600, 10),
+            (2700, 25),
+            (2850, 55),
+            (2950, 85),
+            (3000, 100),
+            (3300, 100),
+        ];
+
+        for (voltage_mv, level) in cases {
+            assert_eq!(curve.level(voltage_mv), level, "{voltage_mv} mV");
+        }
+    }
+
+    #[test]
+    fn voltage_curve_points() {
+        // sorted on creation
+        let curve = VoltageCurve::new(vec![(4200, 100), (3300, 0), (3700, 50)]).unwrap();
+        assert_eq!(curve.level(3500), 25);
+        assert_eq!(curve.level(3950), 75);
+
+        let single = VoltageCurve::new(vec![(3000, 60)]).unwrap();
+        assert_eq!(
+            (single.level(2000), single.level(3000), single.level(4000)),
+            (60, 60, 60)
+        );
+
+        assert_eq!(VoltageCurve::new(Vec::new()), None);
+        assert_eq!(VoltageCurve::new(vec![(3000, 101)]), None);
+    }
+}
@@ -0,0 +1,293 @@
+//! Battery fields in the HID input reports of game controllers.
+//!
+//! see: Linux drivers/hid/hid-playstation.c, hid-nintendo.c and xpadneo
+
+pub const SONY_VENDOR_ID: u16 = 0x054C;
+pub const NINTENDO_VENDOR_ID: u16 = 0x057E;
+pub const MICROSOFT_VENDOR_ID: u16 = 0x045E;
+
+const DUALSENSE_USB_REPORT_ID: u8 = 0x01;
+const DUALSENSE_BT_REPORT_ID: u8 = 0x31;
+const DUALSENSE_USB_STATUS_OFFSET: usize = 53;
+const DUALSENSE_BT_STATUS_OFFSET: usize = 54;
+
+const DUALSHOCK4_USB_REPORT_ID: u8 = 0x01;
+const DUALSHOCK4_BT_REPORT_ID: u8 = 0x11;
+const DUALSHOCK4_USB_STATUS_OFFSET: usize = 30;
+const DUALSHOCK4_BT_STATUS_OFFSET: usize = 32;
+const DUALSHOCK4_CABLE_CONNECTED: u8 = 0x10;
+
+/// Standard full report, and the replies to subcommands which carry the same header
+const SWITCH_PRO_REPORT_IDS: [u8; 3] = [0x30, 0x31, 0x21];
+const SWITCH_PRO_CHARGING: u8 = 0x10;
+
+const XBOX_BATTERY_REPORT_ID: u8 = 0x04;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControllerKind {
+    DualSense,
+    DualShock4,
+    SwitchPro,
+    XboxWireless,
+}
+
+impl ControllerKind {
+    pub fn from_ids(vendor_id: u16, product_id: u16) -> Option<Self> {
+        let kind = match (vendor_id, product_id) {
+            (SONY_VENDOR_ID, 0x0CE6 | 0x0DF2) => ControllerKind::DualSense,
+            (SONY_VENDOR_ID, 0x05C4 | 0x09CC) => ControllerKind::DualShock4,
+            (NINTENDO_VENDOR_ID, 0x2009) => ControllerKind::SwitchPro,
+            (MICROSOFT_VENDOR_ID, 0x02E0 | 0x02FD | 0x0B05 | 0x0B13 | 0x0B20 | 0x0B21 | 0x0B22) => {
+                ControllerKind::XboxWireless
+            }
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControllerKind::DualSense => "DualSense",
+            ControllerKind::DualShock4 => "DualShock 4",
+            ControllerKind::SwitchPro => "Switch Pro Controller",
+            ControllerKind::XboxWireless => "Xbox Wireless Controller",
+        }
+    }
+
+    /// Decodes one input report (report id included), `None` for reports
+    /// without a battery field.
+    pub fn decode_report(&self, report: &[u8]) -> Option<ControllerBattery> {
+        match self {
+            ControllerKind::DualSense => decode_dualsense(report),
+            ControllerKind::DualShock4 => decode_dualshock4(report),
+            ControllerKind::SwitchPro => decode_switch_pro(report),
+            ControllerKind::XboxWireless => decode_xbox_wireless(report),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ControllerBattery {
+    pub level: u8,
+    pub charging: bool,
+}
+
+/// Status byte: low nibble level 0-10, high nibble 0 discharging, 1 charging,
+/// 2 full, 0xA/0xB voltage or temperature out of range, 0xF charging error.
+pub fn decode_dualsense(report: &[u8]) -> Option<ControllerBattery> {
+    let status = match *report.first()? {
+        DUALSENSE_USB_REPORT_ID => *report.get(DUALSENSE_USB_STATUS_OFFSET)?,
+        DUALSENSE_BT_REPORT_ID => *report.get(DUALSENSE_BT_STATUS_OFFSET)?,
+        _ => return None,
+    };
+
+    let level = ((status & 0x0F) * 10 + 5).min(100);
+    match status >> 4 {
+        0x0 => Some(ControllerBattery { level, charging: false }),
+        0x1 => Some(ControllerBattery { level, charging: true }),
+        0x2 => Some(ControllerBattery {
+            level: 100,
+            charging: false,
+        }),
+        _ => None,
+    }
+}
+
+/// Status byte: low nibble level, bit 4 cable connected. On cable 0-10 means
+/// charging and 11 full, on battery the level only goes up to 10.
+pub fn decode_dualshock4(report: &[u8]) -> Option<ControllerBattery> {
+    let status = match *report.first()? {
+        DUALSHOCK4_USB_REPORT_ID => *report.get(DUALSHOCK4_USB_STATUS_OFFSET)?,
+        DUALSHOCK4_BT_REPORT_ID => *report.get(DUALSHOCK4_BT_STATUS_OFFSET)?,
+        _ => return None,
+    };
+
+    let level = status & 0x0F;
+    match (status & DUALSHOCK4_CABLE_CONNECTED != 0, level) {
+        (true, 0..=9) => Some(ControllerBattery {
+            level: level * 10 + 5,
+            charging: true,
+        }),
+        (true, 10) => Some(ControllerBattery {
+            level: 100,
+            charging: true,
+        }),
+        (true, 11) => Some(ControllerBattery {
+            level: 100,
+            charging: false,
+        }),
+        (false, level) if level <= 10 => Some(ControllerBattery {
+            level: (level * 10 + 5).min(100),
+            charging: false,
+        }),
+        _ => None,
+    }
+}
+
+/// Byte 2: bits 5-7 level (4 full, 3 medium, 2 low, 1 critical, 0 empty), bit 4 charging.
+pub fn decode_switch_pro(report: &[u8]) -> Option<ControllerBattery> {
+    if !SWITCH_PRO_REPORT_IDS.contains(report.first()?) {
+        return None;
+    };
+
+    let status = *report.get(2)?;
+    let level = match status >> 5 {
+        4 => 100,
+        3 => 70,
+        2 => 40,
+        1 => 10,
+        _ => 0,
+    };
+
+    Some(ControllerBattery {
+        level,
+        charging: status & SWITCH_PRO_CHARGING != 0,
+    })
+}
+
+/// Report 0x04 byte 1: bit 7 online, bit 4 charging, bits 2-3 battery type
+/// (0 none, i.e. powered over USB), bits 0-1 level (empty, low, medium, full).
+pub fn decode_xbox_wireless(report: &[u8]) -> Option<ControllerBattery> {
+    let [XBOX_BATTERY_REPORT_ID, battery, ..] = *report else {
+        return None;
+    };
+
+    if battery & 0x0C == 0 {
+        return None;
+    };
+
+    Some(ControllerBattery {
+        level: match battery & 0x03 {
+            0 => 5,
+            1 => 30,
+            2 => 65,
+            _ => 100,
+        },
+        charging: battery & 0x10 != 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A zeroed input report of the real length with the given bytes filled in.
+    fn report(report_id: u8, length: usize, bytes: &[(usize, u8)]) -> Vec<u8> {
+        let mut report = vec![0; length];
+        report[0] = report_id;
+        for (offset, value) in bytes {
+            report[*offset] = *value;
+        }
+        report
+    }
+
+    fn battery(level: u8, charging: bool) -> Option<ControllerBattery> {
+        Some(ControllerBattery { level, charging })
+    }
+
+    #[test]
+    fn dualsense() {
+        let cases = [
+            (report(0x01, 64, &[(53, 0x08)]), battery(85, false)),
+            (report(0x01, 64, &[(53, 0x00)]), battery(5, false)),
+            (report(0x01, 64, &[(53, 0x14)]), battery(45, true)),
+            (report(0x01, 64, &[(53, 0x1A)]), battery(100, true)),
+            (report(0x01, 64, &[(53, 0x2A)]), battery(100, false)),
+            (report(0x31, 78, &[(54, 0x06)]), battery(65, false)),
+            // voltage out of range, charging error
+            (report(0x01, 64, &[(53, 0xA3)]), None),
+            (report(0x31, 78, &[(54, 0xF0)]), None),
+            // the reduced Bluetooth report, truncated
+            (report(0x01, 10, &[(9, 0x08)]), None),
+            (report(0x31, 54, &[(53, 0x08)]), None),
+        ];
+
+        for (report, expected) in cases {
+            assert_eq!(decode_dualsense(&report), expected, "{:02X?}", &report[..1]);
+        }
+    }
+
+    #[test]
+    fn dualshock4() {
+        let cases = [
+            (report(0x01, 64, &[(30, 0x07)]), battery(75, false)),
+            (report(0x01, 64, &[(30, 0x0A)]), battery(100, false)),
+            (report(0x01, 64, &[(30, 0x15)]), battery(55, true)),
+            (report(0x01, 64, &[(30, 0x1A)]), battery(100, true)),
+            (report(0x01, 64, &[(30, 0x1B)]), battery(100, false)),
+            (report(0x11, 78, &[(32, 0x08)]), battery(85, false)),
+            // 11 only exists on cable
+            (report(0x01, 64, &[(30, 0x0B)]), None),
+            (report(0x01, 64, &[(30, 0x1C)]), None),
+            (report(0x11, 30, &[]), None),
+            (report(0x05, 64, &[(30, 0x07)]), None),
+        ];
+
+        for (report, expected) in cases {
+            assert_eq!(decode_dualshock4(&report), expected, "{:02X?}", &report[..1]);
+        }
+    }
+
+    #[test]
+    fn switch_pro() {
+        let cases = [
+            (report(0x30, 49, &[(2, 0x8E)]), battery(100, false)),
+            (report(0x30, 49, &[(2, 0x90)]), battery(100, true)),
+            (report(0x30, 49, &[(2, 0x60)]), battery(70, false)),
+            (report(0x31, 362, &[(2, 0x50)]), battery(40, true)),
+            (report(0x21, 49, &[(2, 0x20)]), battery(10, false)),
+            (report(0x30, 49, &[(2, 0x00)]), battery(0, false)),
+            // the simple HID report before the mode switch
+            (report(0x3F, 12, &[(2, 0x8E)]), None),
+            (report(0x30, 2, &[]), None),
+        ];
+
+        for (report, expected) in cases {
+            assert_eq!(decode_switch_pro(&report), expected, "{:02X?}", &report[..1]);
+        }
+    }
+
+    #[test]
+    fn xbox_wireless() {
+        let cases: [(&[u8], Option<ControllerBattery>); 7] = [
+            (&[0x04, 0x8F], battery(100, false)),
+            (&[0x04, 0x86], battery(65, false)),
+            (&[0x04, 0x95], battery(30, true)),
+            (&[0x04, 0x8C], battery(5, false)),
+            // no batteries, powered over USB
+            (&[0x04, 0x93], None),
+            (&[0x01, 0x8F], None),
+            (&[0x04], None),
+        ];
+
+        for (report, expected) in cases {
+            assert_eq!(decode_xbox_wireless(report), expected, "{report:02X?}");
+        }
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(
+            ControllerKind::from_ids(SONY_VENDOR_ID, 0x0DF2),
+            Some(ControllerKind::DualSense)
+        );
+        assert_eq!(
+            ControllerKind::from_ids(SONY_VENDOR_ID, 0x09CC),
+            Some(ControllerKind::DualShock4)
+        );
+        assert_eq!(
+            ControllerKind::from_ids(NINTENDO_VENDOR_ID, 0x2009),
+            Some(ControllerKind::SwitchPro)
+        );
+        assert_eq!(
+            ControllerKind::from_ids(MICROSOFT_VENDOR_ID, 0x0B13),
+            Some(ControllerKind::XboxWireless)
+        );
+        assert_eq!(ControllerKind::from_ids(SONY_VENDOR_ID, 0x0268), None);
+
+        let report = report(0x31, 78, &[(54, 0x14)]);
+        assert_eq!(ControllerKind::DualSense.decode_report(&report), battery(45, true));
+        assert_eq!(ControllerKind::DualShock4.decode_report(&report), None);
+    }
+}
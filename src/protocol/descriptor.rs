@@ -0,0 +1,218 @@
+//! Descriptors that tell several instances of the same characteristic apart,
+//! e.g. one Battery Level per earbud.
+
+use super::ByteReader;
+use crate::provider::{CASE_BATTERY, LEFT_BATTERY, MAIN_BATTERY, RIGHT_BATTERY};
+
+pub const USER_DESCRIPTION_UUID: u16 = 0x2901;
+pub const PRESENTATION_FORMAT_UUID: u16 = 0x2904;
+
+const BLUETOOTH_SIG_NAMESPACE: u8 = 0x01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PresentationFormat {
+    pub format: u8,
+    pub exponent: i8,
+    pub unit: u16,
+    pub namespace: u8,
+    pub description: u16,
+}
+
+impl PresentationFormat {
+    /// Human-readable name of the description field, only defined for the Bluetooth SIG namespace.
+    pub fn label(&self) -> Option<String> {
+        match self.namespace == BLUETOOTH_SIG_NAMESPACE {
+            true => get_sig_description_label(self.description),
+            false => None,
+        }
+    }
+}
+
+/// Decodes a Characteristic Presentation Format (0x2904) descriptor value.
+pub fn decode_presentation_format(data: &[u8]) -> Option<PresentationFormat> {
+    let mut reader = ByteReader::new(data);
+
+    Some(PresentationFormat {
+        format: reader.read_u8()?,
+        exponent: reader.read_u8()? as i8,
+        unit: reader.read_u16()?,
+        namespace: reader.read_u8()?,
+        description: reader.read_u16()?,
+    })
+}
+
+/// Decodes a Characteristic User Description (0x2901) descriptor value, some
+/// devices pad it with NULs.
+pub fn decode_user_description(data: &[u8]) -> Option<String> {
+    let description = String::from_utf8_lossy(data)
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string();
+
+    match description.is_empty() {
+        true => None,
+        false => Some(description),
+    }
+}
+
+/// Label of one Battery Level instance: the Presentation Format description
+/// (e.g. "left") first, then the free-form User Description, which is only read
+/// when needed.
+pub fn get_characteristic_label(
+    presentation_format: Option<Vec<u8>>,
+    user_description: impl FnOnce() -> Option<Vec<u8>>,
+) -> Option<String> {
+    presentation_format
+        .and_then(|value| decode_presentation_format(&value))
+        .and_then(|presentation_format| presentation_format.label())
+        .or_else(|| user_description().and_then(|value| decode_user_description(&value)))
+        .map(|label| normalize_battery_label(&label))
+}
+
+/// Unlabelled instances are numbered, unless there's only the one.
+pub fn number_unlabelled<'a>(labels: impl ExactSizeIterator<Item = &'a mut String>) {
+    let count = labels.len();
+    for (index, label) in labels.enumerate() {
+        if label.is_empty() {
+            *label = match count {
+                1 => MAIN_BATTERY.to_string(),
+                _ => format!("#{}", index + 1),
+            };
+        };
+    }
+}
+
+/// see: Bluetooth SIG Assigned Numbers, "GATT Characteristic Presentation Format Description"
+fn get_sig_description_label(description: u16) -> Option<String> {
+    let label = match description {
+        0x0001..=0x00FF => return Some(format!("#{description}")),
+        0x0100 => "front",
+        0x0101 => "back",
+        0x0102 => "top",
+        0x0103 => "bottom",
+        0x0104 => "upper",
+        0x0105 => "lower",
+        0x0106 => MAIN_BATTERY,
+        0x0107 => "backup",
+        0x0108 => "auxiliary",
+        0x0109 => "supplementary",
+        0x010A => "flash",
+        0x010B => "inside",
+        0x010C => "outside",
+        0x010D => LEFT_BATTERY,
+        0x010E => RIGHT_BATTERY,
+        0x010F => "internal",
+        0x0110 => "external",
+        _ => return None,
+    };
+
+    Some(label.to_string())
+}
+
+/// Maps free-form labels such as "Left Bud" or "Charging Case" onto the
+/// component names the tray knows how to abbreviate.
+pub fn normalize_battery_label(label: &str) -> String {
+    let lowercase_label = label.to_lowercase();
+
+    match lowercase_label.as_str() {
+        "l" => LEFT_BATTERY.to_string(),
+        "r" => RIGHT_BATTERY.to_string(),
+        l if l.contains("left") => LEFT_BATTERY.to_string(),
+        l if l.contains("right") => RIGHT_BATTERY.to_string(),
+        l if l.contains("case") => CASE_BATTERY.to_string(),
+        _ => lowercase_label,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presentation_format(namespace: u8, description: u16) -> Option<Vec<u8>> {
+        // uint8 percentage, as Battery Level declares it
+        let [low, high] = description.to_le_bytes();
+        Some(vec![0x04, 0x00, 0xAD, 0x27, namespace, low, high])
+    }
+
+    fn user_description(description: &str) -> impl FnOnce() -> Option<Vec<u8>> + '_ {
+        move || Some(description.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn presentation_format_descriptions() {
+        let cases = [
+            (0x010D, Some(LEFT_BATTERY.to_string())),
+            (0x010E, Some(RIGHT_BATTERY.to_string())),
+            (0x0106, Some(MAIN_BATTERY.to_string())),
+            (0x0107, Some("backup".to_string())),
+            (0x0002, Some("#2".to_string())),
+            (0x0000, None),
+            (0x0111, None),
+        ];
+
+        for (description, label) in cases {
+            assert_eq!(
+                get_characteristic_label(presentation_format(BLUETOOTH_SIG_NAMESPACE, description), || None),
+                label,
+                "{description:#06x}"
+            );
+        }
+    }
+
+    #[test]
+    fn user_description_is_the_fallback() {
+        assert_eq!(
+            get_characteristic_label(presentation_format(BLUETOOTH_SIG_NAMESPACE, 0x010D), || {
+                panic!("the User Description is read although the Presentation Format has a label")
+            }),
+            Some(LEFT_BATTERY.to_string())
+        );
+        // vendor namespace, the description means nothing to us
+        assert_eq!(
+            get_characteristic_label(presentation_format(0x02, 0x010D), user_description("Charging Case\0\0")),
+            Some(CASE_BATTERY.to_string())
+        );
+        assert_eq!(
+            get_characteristic_label(None, user_description("Right Bud")),
+            Some(RIGHT_BATTERY.to_string())
+        );
+        assert_eq!(
+            get_characteristic_label(None, user_description("Stylus")),
+            Some("stylus".to_string())
+        );
+        assert_eq!(get_characteristic_label(None, user_description("\0 ")), None);
+        assert_eq!(get_characteristic_label(Some(vec![0x04, 0x00]), || None), None);
+    }
+
+    #[test]
+    fn presentation_format_fields() {
+        assert_eq!(
+            decode_presentation_format(&[0x04, 0xFE, 0xAD, 0x27, 0x01, 0x0D, 0x01]),
+            Some(PresentationFormat {
+                format: 0x04,
+                exponent: -2,
+                unit: 0x27AD,
+                namespace: 0x01,
+                description: 0x010D,
+            })
+        );
+    }
+
+    #[test]
+    fn unlabelled_instances_are_numbered() {
+        let cases: [(&[&str], &[&str]); 4] = [
+            (&[""], &[MAIN_BATTERY]),
+            (&["", ""], &["#1", "#2"]),
+            (
+                &[LEFT_BATTERY, "", RIGHT_BATTERY, ""],
+                &[LEFT_BATTERY, "#2", RIGHT_BATTERY, "#4"],
+            ),
+            (&[LEFT_BATTERY, RIGHT_BATTERY], &[LEFT_BATTERY, RIGHT_BATTERY]),
+        ];
+
+        for (labels, expected) in cases {
+            let mut labels: Vec<String> = labels.iter().map(|label| label.to_string()).collect();
+            number_unlabelled(labels.iter_mut());
+            assert_eq!(labels, expected, "{expected:?}");
+        }
+    }
+}
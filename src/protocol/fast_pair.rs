@@ -0,0 +1,184 @@
+//! Google Fast Pair service data (UUID 0xFE2C).
+//!
+//! While pairable the service data is just the 3 byte model id, afterwards it
+//! is a version byte followed by length/type prefixed fields:
+//! 00 | 60 <account key filter> | 11 <salt> | 33 E4 5A 7F
+//!                                           |  |  |  case unknown
+//!                                           |  |  right 90%, not charging
+//!                                           |  left 100%, charging
+//!                                           3 levels, show the battery in the UI
+//!
+//! see: https://developers.google.com/nearby/fast-pair/specifications/extensions/batterynotification
+
+use crate::provider::{BatteryComponent, CASE_BATTERY, LEFT_BATTERY, RIGHT_BATTERY};
+
+pub const FAST_PAIR_SERVICE_UUID: u16 = 0xFE2C;
+
+const MODEL_ID_LENGTH: usize = 3;
+const BATTERY_SHOW_UI_TYPE: u8 = 0b0011;
+const BATTERY_HIDE_UI_TYPE: u8 = 0b0100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FastPairBattery {
+    /// 0-100, `None` when the device reports 0x7F (unknown)
+    pub level: Option<u8>,
+    pub charging: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FastPairAdvertisement {
+    /// Only advertised while the device is in pairing mode.
+    pub model_id: Option<u32>,
+    /// Left bud, right bud and case, in that order; single-battery devices send one value.
+    pub batteries: Vec<FastPairBattery>,
+    /// Whether the device asks the phone to pop up its battery levels.
+    pub show_ui: bool,
+}
+
+impl FastPairAdvertisement {
+    /// Maps the battery notification onto the provider battery components, skipping unknown levels.
+    pub fn battery_components(&self) -> Vec<BatteryComponent> {
+        [LEFT_BATTERY, RIGHT_BATTERY, CASE_BATTERY]
+            .into_iter()
+            .zip(&self.batteries)
+            .filter_map(|(name, battery)| {
+                let mut component = BatteryComponent::new(name, battery.level?);
+                component.charging = Some(battery.charging);
+                Some(component)
+            })
+            .collect()
+    }
+}
+
+/// Decodes the 0xFE2C service data, `None` for a malformed payload.
+pub fn decode_fast_pair(data: &[u8]) -> Option<FastPairAdvertisement> {
+    if data.len() == MODEL_ID_LENGTH {
+        return Some(FastPairAdvertisement {
+            model_id: Some(u32::from_be_bytes([0, data[0], data[1], data[2]])),
+            ..Default::default()
+        });
+    };
+
+    // only version 0 of the account key data is defined
+    let (0, mut fields) = data.split_first()? else {
+        return None;
+    };
+
+    let mut advertisement = FastPairAdvertisement::default();
+    while let Some((header, rest)) = fields.split_first() {
+        let (length, field_type) = ((header >> 4) as usize, header & 0x0F);
+        let value = rest.get(..length)?;
+        fields = &rest[length..];
+
+        if matches!(field_type, BATTERY_SHOW_UI_TYPE | BATTERY_HIDE_UI_TYPE) {
+            advertisement.show_ui = field_type == BATTERY_SHOW_UI_TYPE;
+            advertisement.batteries = value.iter().map(|value| decode_battery(*value)).collect();
+        };
+    }
+
+    Some(advertisement)
+}
+
+/// Bit 7 is the charging flag, bits 0-6 the level.
+fn decode_battery(value: u8) -> FastPairBattery {
+    let level = value & 0x7F;
+
+    FastPairBattery {
+        level: match level {
+            0..=100 => Some(level),
+            // 0x7F (unknown) or out of range
+            _ => None,
+        },
+        charging: value & 0x80 != 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battery(level: Option<u8>, charging: bool) -> FastPairBattery {
+        FastPairBattery { level, charging }
+    }
+
+    #[test]
+    fn model_id_while_pairable() {
+        assert_eq!(
+            decode_fast_pair(&[0x0E, 0x30, 0xC3]),
+            Some(FastPairAdvertisement {
+                model_id: Some(0x0E30C3),
+                ..Default::default()
+            })
+        );
+    }
+
+    #[test]
+    fn battery_notification() {
+        // account key filter, salt, then the battery levels
+        let data = [
+            0x00, 0x60, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E, 0x6F, 0x11, 0x42, 0x33, 0xE4, 0x5A, 0x7F,
+        ];
+        let advertisement = decode_fast_pair(&data).unwrap();
+
+        assert_eq!(advertisement.model_id, None);
+        assert!(advertisement.show_ui);
+        assert_eq!(
+            advertisement.batteries,
+            [battery(Some(100), true), battery(Some(90), false), battery(None, false)]
+        );
+        let components: Vec<_> = advertisement
+            .battery_components()
+            .into_iter()
+            .map(|component| (component.name, component.charging))
+            .collect();
+        assert_eq!(
+            components,
+            [
+                (LEFT_BATTERY.to_string(), Some(true)),
+                (RIGHT_BATTERY.to_string(), Some(false))
+            ]
+        );
+    }
+
+    #[test]
+    fn battery_values() {
+        let cases = [
+            (0x00, battery(Some(0), false)),
+            (0x64, battery(Some(100), false)),
+            (0x65, battery(None, false)),
+            (0x7F, battery(None, false)),
+            (0xB2, battery(Some(50), true)),
+            (0xFF, battery(None, true)),
+        ];
+
+        for (value, expected) in cases {
+            // after a salt, the hide UI type carries the same levels
+            let advertisement = decode_fast_pair(&[0x00, 0x11, 0x42, 0x14, value]).unwrap();
+            assert!(!advertisement.show_ui);
+            assert_eq!(advertisement.batteries, [expected], "{value:#04x}");
+        }
+    }
+
+    #[test]
+    fn malformed_payloads() {
+        let cases: [&[u8]; 5] = [
+            &[],
+            &[0x0E, 0x30],
+            // version 1 isn't defined
+            &[0x01, 0x33, 0xE4, 0x5A, 0x7F],
+            // the field is longer than the payload
+            &[0x00, 0x60, 0x1A, 0x2B],
+            &[0x00, 0x33, 0xE4, 0x5A],
+        ];
+
+        for data in cases {
+            assert_eq!(decode_fast_pair(data), None, "{data:02X?}");
+        }
+
+        // account key data without a battery notification
+        assert_eq!(
+            decode_fast_pair(&[0x00, 0x60, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E, 0x6F]),
+            Some(FastPairAdvertisement::default())
+        );
+    }
+}
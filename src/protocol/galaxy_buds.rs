@@ -0,0 +1,531 @@
+//! Samsung Galaxy Buds messages on their SPP (RFCOMM) channel.
+//!
+//! FD 0B10 61 01 00 4B 50 01 01 13 3C 2E08 DD
+//! |  |    |  |                        |    end of message, 0xEE on the first Buds
+//! |  |    |  |                        CRC16 over id and payload, little-endian
+//! |  |    |  payload
+//! |  |    message id
+//! |  little-endian header, bits 0-9 size of id + payload + CRC, bit 12 response, bit 13 fragment
+//! start of message, 0xFE on the first Buds
+//!
+//! see: https://github.com/timschneeb/GalaxyBudsClient
+
+use crate::provider::{BatteryComponent, CASE_BATTERY, LEFT_BATTERY, RIGHT_BATTERY};
+
+/// Service the first Buds listen on. Not an assigned number: the Bluetooth base
+/// UUID with its last byte changed to 0xFD (SPP itself is 0x1101).
+pub const BUDS_SERVICE_UUID: u128 = 0x00001102_0000_1000_8000_00805F9B34FD;
+/// Samsung's own service used from the Buds+ on.
+pub const BUDS_PLUS_SERVICE_UUID: u128 = 0x2E73A4AD_332D_41FC_90E2_16BEF06523F2;
+
+pub const STATUS_UPDATED: u8 = 0x60;
+pub const EXTENDED_STATUS_UPDATED: u8 = 0x61;
+
+const BUDS_START_OF_MESSAGE: u8 = 0xFE;
+const BUDS_END_OF_MESSAGE: u8 = 0xEE;
+const START_OF_MESSAGE: u8 = 0xFD;
+const END_OF_MESSAGE: u8 = 0xDD;
+const SIZE_MASK: u16 = 0x03FF;
+const FRAGMENT_FLAG: u16 = 0x2000;
+const CRC_LENGTH: usize = 2;
+/// start of message, header and end of message around the sized part
+const FRAME_OVERHEAD: usize = 4;
+
+const WEARING_LEFT: u8 = 0x10;
+const WEARING_RIGHT: u8 = 0x01;
+const PLACEMENT_DISCONNECTED: u8 = 0;
+const PLACEMENT_WEARING: u8 = 1;
+const PLACEMENT_IN_OPEN_CASE: u8 = 3;
+const PLACEMENT_IN_CLOSED_CASE: u8 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BudsModel {
+    Buds,
+    BudsPlus,
+    BudsLive,
+    Buds2,
+}
+
+impl BudsModel {
+    /// Picks the model from the Bluetooth name, e.g. "Galaxy Buds+ (A1B2)".
+    pub fn from_name(name: &str) -> Option<Self> {
+        let model = name.strip_prefix("Galaxy Buds")?;
+
+        let model = match model {
+            model if model.starts_with('+') => BudsModel::BudsPlus,
+            model if model.starts_with(" Live") || model.starts_with(" Pro") => BudsModel::BudsLive,
+            model if model.starts_with('2') || model.starts_with(" FE") => BudsModel::Buds2,
+            _ => BudsModel::Buds,
+        };
+
+        Some(model)
+    }
+
+    pub fn service_uuid(&self) -> u128 {
+        match self {
+            BudsModel::Buds => BUDS_SERVICE_UUID,
+            _ => BUDS_PLUS_SERVICE_UUID,
+        }
+    }
+
+    fn markers(&self) -> (u8, u8) {
+        match self {
+            BudsModel::Buds => (BUDS_START_OF_MESSAGE, BUDS_END_OF_MESSAGE),
+            _ => (START_OF_MESSAGE, END_OF_MESSAGE),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BudsMessage {
+    pub id: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Cuts the byte stream of one connection into messages, dropping bytes until
+/// the next start of message whenever a frame is malformed or its CRC is wrong.
+pub struct FrameDecoder {
+    model: BudsModel,
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn new(model: BudsModel) -> Self {
+        FrameDecoder {
+            model,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Appends received bytes and returns every message completed by them.
+    pub fn push(&mut self, data: &[u8]) -> Vec<BudsMessage> {
+        self.buffer.extend_from_slice(data);
+
+        let (start, end) = self.model.markers();
+        let mut messages = Vec::new();
+        loop {
+            match self.buffer.iter().position(|byte| *byte == start) {
+                Some(position) => {
+                    self.buffer.drain(..position);
+                }
+                None => {
+                    self.buffer.clear();
+                    break;
+                }
+            };
+
+            let Some(&[low, high]) = self.buffer.get(1..3) else {
+                break;
+            };
+            let header = u16::from_le_bytes([low, high]);
+            let size = (header & SIZE_MASK) as usize;
+            let Some(frame) = self.buffer.get(..size + FRAME_OVERHEAD) else {
+                break;
+            };
+
+            // nothing BlueGauge reads is long enough to be split into fragments
+            let message = match header & FRAGMENT_FLAG == 0 && frame[frame.len() - 1] == end {
+                true => decode_body(&frame[3..frame.len() - 1]),
+                false => None,
+            };
+
+            match message {
+                Some(message) => {
+                    messages.push(message);
+                    self.buffer.drain(..size + FRAME_OVERHEAD);
+                }
+                None => {
+                    self.buffer.remove(0);
+                }
+            }
+        }
+
+        messages
+    }
+}
+
+/// Splits id, payload and CRC, `None` when the CRC doesn't match.
+fn decode_body(body: &[u8]) -> Option<BudsMessage> {
+    if body.len() < 1 + CRC_LENGTH {
+        return None;
+    };
+
+    let (data, crc) = body.split_at(body.len() - CRC_LENGTH);
+    match crc16(data) == u16::from_le_bytes([crc[0], crc[1]]) {
+        true => Some(BudsMessage {
+            id: data[0],
+            payload: data[1..].to_vec(),
+        }),
+        false => None,
+    }
+}
+
+/// CRC-16/XMODEM: polynomial 0x1021, initial value 0, no reflection.
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 != 0 {
+            true => (crc << 1) ^ 0x1021,
+            false => crc << 1,
+        })
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bud {
+    /// 0-100, `None` while the bud isn't connected
+    pub level: Option<u8>,
+    /// Only known from the Buds Live on, which report whether a bud sits in the case
+    pub charging: Option<bool>,
+    pub wearing: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudsStatus {
+    pub left: Bud,
+    pub right: Bud,
+    /// The first Buds don't report their case
+    pub case_level: Option<u8>,
+}
+
+impl BudsStatus {
+    /// Maps the status onto the provider battery components, skipping unknown levels.
+    pub fn batteries(&self) -> Vec<BatteryComponent> {
+        let component = |name: &str, level: Option<u8>, charging: Option<bool>| {
+            level.map(|level| {
+                let mut component = BatteryComponent::new(name, level);
+                component.charging = charging;
+                component
+            })
+        };
+
+        [
+            component(LEFT_BATTERY, self.left.level, self.left.charging),
+            component(RIGHT_BATTERY, self.right.level, self.right.charging),
+            component(CASE_BATTERY, self.case_level, None),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+/// Decodes a status (0x60) or extended status (0x61) message, `None` for any
+/// other message id.
+///
+/// Status:          [revision], left, right, coupled, main connection, wear state or placement, [case]
+/// Extended status: revision, ear type, left, right, coupled, main connection, wear state or placement, [case], ...
+///
+/// Fields in brackets are missing on the first Buds. The first Buds and the Buds+
+/// report which buds are worn, later models where each bud is placed instead.
+pub fn decode_status(model: BudsModel, message: &BudsMessage) -> Option<BudsStatus> {
+    let offset = match (message.id, model) {
+        (STATUS_UPDATED, BudsModel::Buds) => 0,
+        (STATUS_UPDATED, _) => 1,
+        (EXTENDED_STATUS_UPDATED, _) => 2,
+        _ => return None,
+    };
+
+    let fields = message.payload.get(offset..)?;
+    let [left_level, right_level, coupled, _main_connection, wear, ..] = *fields else {
+        return None;
+    };
+    let case_level = match model {
+        BudsModel::Buds => None,
+        _ => Some(*fields.get(5)?),
+    };
+
+    let (left, right) = match model {
+        BudsModel::Buds | BudsModel::BudsPlus => {
+            // the bud that isn't coupled to the main one reports 0
+            let level = |level: u8| match coupled == 0 && level == 0 {
+                true => None,
+                false => Some(level.min(100)),
+            };
+            (
+                Bud {
+                    level: level(left_level),
+                    charging: None,
+                    wearing: wear & WEARING_LEFT != 0,
+                },
+                Bud {
+                    level: level(right_level),
+                    charging: None,
+                    wearing: wear & WEARING_RIGHT != 0,
+                },
+            )
+        }
+        BudsModel::BudsLive | BudsModel::Buds2 => {
+            let bud = |level: u8, placement: u8| Bud {
+                level: match placement {
+                    PLACEMENT_DISCONNECTED => None,
+                    _ => Some(level.min(100)),
+                },
+                charging: Some(matches!(placement, PLACEMENT_IN_OPEN_CASE | PLACEMENT_IN_CLOSED_CASE)),
+                wearing: placement == PLACEMENT_WEARING,
+            };
+            (bud(left_level, wear >> 4), bud(right_level, wear & 0x0F))
+        }
+    };
+
+    Some(BudsStatus {
+        left,
+        right,
+        // the case reports 0 while it's out of reach of both buds
+        case_level: case_level.filter(|level| *level > 0).map(|level| level.min(100)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Extended status of a pair of Buds2, as in the module docs
+    const CAPTURED_FRAME: [u8; 15] = [
+        0xFD, 0x0B, 0x10, 0x61, 0x01, 0x00, 0x4B, 0x50, 0x01, 0x01, 0x13, 0x3C, 0x2E, 0x08, 0xDD,
+    ];
+
+    fn frame(model: BudsModel, id: u8, payload: &[u8]) -> Vec<u8> {
+        let (start, end) = model.markers();
+        let mut data = vec![id];
+        data.extend_from_slice(payload);
+        let size = (data.len() + CRC_LENGTH) as u16;
+
+        let mut frame = vec![start];
+        frame.extend(size.to_le_bytes());
+        frame.extend(crc16(&data).to_le_bytes());
+        frame.splice(3..3, data);
+        frame.push(end);
+        frame
+    }
+
+    fn message(id: u8, payload: &[u8]) -> BudsMessage {
+        BudsMessage {
+            id,
+            payload: payload.to_vec(),
+        }
+    }
+
+    #[test]
+    fn crc16_xmodem() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+        assert_eq!(crc16(&CAPTURED_FRAME[3..12]), 0x082E);
+    }
+
+    #[test]
+    fn captured_frame() {
+        let mut decoder = FrameDecoder::new(BudsModel::Buds2);
+
+        assert_eq!(
+            decoder.push(&CAPTURED_FRAME),
+            [message(
+                EXTENDED_STATUS_UPDATED,
+                &[0x01, 0x00, 0x4B, 0x50, 0x01, 0x01, 0x13, 0x3C]
+            )]
+        );
+        assert_eq!(
+            frame(BudsModel::Buds2, EXTENDED_STATUS_UPDATED, &CAPTURED_FRAME[4..12])[3..],
+            CAPTURED_FRAME[3..]
+        );
+    }
+
+    #[test]
+    fn frames_split_across_reads() {
+        let mut decoder = FrameDecoder::new(BudsModel::BudsLive);
+
+        assert_eq!(decoder.push(&CAPTURED_FRAME[..2]), []);
+        assert_eq!(decoder.push(&CAPTURED_FRAME[2..9]), []);
+        assert_eq!(decoder.push(&CAPTURED_FRAME[9..]).len(), 1);
+        assert_eq!(decoder.push(&[]), []);
+    }
+
+    #[test]
+    fn resyncs_after_garbage_and_bad_frames() {
+        let mut corrupted = frame(
+            BudsModel::BudsPlus,
+            STATUS_UPDATED,
+            &[0x00, 0x46, 0x3C, 0x01, 0x00, 0x01, 0x32],
+        );
+        corrupted[5] ^= 0xFF;
+        let mut fragment = frame(BudsModel::BudsPlus, 0x42, &[0x01]);
+        fragment[2] |= (FRAGMENT_FLAG >> 8) as u8;
+        let mut wrong_end = frame(BudsModel::BudsPlus, 0x42, &[0x02]);
+        *wrong_end.last_mut().unwrap() = 0x00;
+
+        let mut data = vec![0x00, 0x13, 0xDD];
+        for bytes in [
+            corrupted,
+            fragment,
+            wrong_end,
+            frame(BudsModel::BudsPlus, 0x42, &[0x03]),
+        ] {
+            data.extend(bytes);
+        }
+
+        let mut decoder = FrameDecoder::new(BudsModel::BudsPlus);
+        assert_eq!(decoder.push(&data), [message(0x42, &[0x03])]);
+        // nothing of the bad frames is left over to be read again
+        assert_eq!(decoder.push(&CAPTURED_FRAME).len(), 1);
+    }
+
+    #[test]
+    fn legacy_markers() {
+        let legacy = frame(BudsModel::Buds, STATUS_UPDATED, &[0x5A, 0x50, 0x01, 0x00, 0x11]);
+        assert_eq!(legacy[0], 0xFE);
+        assert_eq!(*legacy.last().unwrap(), 0xEE);
+
+        let mut decoder = FrameDecoder::new(BudsModel::Buds);
+        assert_eq!(decoder.push(&CAPTURED_FRAME), []);
+        assert_eq!(
+            decoder.push(&legacy),
+            [message(STATUS_UPDATED, &[0x5A, 0x50, 0x01, 0x00, 0x11])]
+        );
+
+        let mut decoder = FrameDecoder::new(BudsModel::Buds2);
+        assert_eq!(decoder.push(&legacy), []);
+    }
+
+    fn bud(level: Option<u8>, charging: Option<bool>, wearing: bool) -> Bud {
+        Bud {
+            level,
+            charging,
+            wearing,
+        }
+    }
+
+    #[test]
+    fn status_layouts() {
+        let cases = [
+            (
+                BudsModel::Buds,
+                message(STATUS_UPDATED, &[0x5A, 0x50, 0x01, 0x00, 0x11]),
+                (bud(Some(90), None, true), bud(Some(80), None, true), None),
+            ),
+            // the right bud isn't coupled
+            (
+                BudsModel::Buds,
+                message(STATUS_UPDATED, &[0x5A, 0x00, 0x00, 0x00, 0x10]),
+                (bud(Some(90), None, true), bud(None, None, false), None),
+            ),
+            (
+                BudsModel::BudsPlus,
+                message(STATUS_UPDATED, &[0x0D, 0x46, 0x3C, 0x01, 0x00, 0x01, 0x32]),
+                (bud(Some(70), None, false), bud(Some(60), None, true), Some(50)),
+            ),
+            (
+                BudsModel::BudsPlus,
+                message(
+                    EXTENDED_STATUS_UPDATED,
+                    &[0x0D, 0x00, 0x46, 0x3C, 0x01, 0x00, 0x10, 0x32, 0x01],
+                ),
+                (bud(Some(70), None, true), bud(Some(60), None, false), Some(50)),
+            ),
+            // both buds in the case, the case out of their reach
+            (
+                BudsModel::BudsLive,
+                message(
+                    EXTENDED_STATUS_UPDATED,
+                    &[0x05, 0x00, 0x64, 0x5F, 0x01, 0x00, 0x34, 0x00],
+                ),
+                (
+                    bud(Some(100), Some(true), false),
+                    bud(Some(95), Some(true), false),
+                    None,
+                ),
+            ),
+            (
+                BudsModel::Buds2,
+                message(STATUS_UPDATED, &[0x02, 0x32, 0x00, 0x00, 0x00, 0x10, 0x28]),
+                (
+                    bud(Some(50), Some(false), true),
+                    bud(None, Some(false), false),
+                    Some(40),
+                ),
+            ),
+            (
+                BudsModel::Buds2,
+                message(EXTENDED_STATUS_UPDATED, &CAPTURED_FRAME[4..12]),
+                (
+                    bud(Some(75), Some(false), true),
+                    bud(Some(80), Some(true), false),
+                    Some(60),
+                ),
+            ),
+        ];
+
+        for (model, message, (left, right, case_level)) in cases {
+            assert_eq!(
+                decode_status(model, &message),
+                Some(BudsStatus {
+                    left,
+                    right,
+                    case_level,
+                }),
+                "{model:?} {message:02X?}"
+            );
+        }
+    }
+
+    #[test]
+    fn status_rejects_other_messages() {
+        // another message id, truncated, the case level missing
+        assert_eq!(
+            decode_status(
+                BudsModel::Buds2,
+                &message(0x62, &[0x02, 0x32, 0x32, 0x01, 0x00, 0x11, 0x28])
+            ),
+            None
+        );
+        assert_eq!(
+            decode_status(BudsModel::Buds, &message(STATUS_UPDATED, &[0x5A, 0x50, 0x01])),
+            None
+        );
+        assert_eq!(
+            decode_status(
+                BudsModel::BudsPlus,
+                &message(STATUS_UPDATED, &[0x0D, 0x46, 0x3C, 0x01, 0x00, 0x01])
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn batteries() {
+        let status = decode_status(
+            BudsModel::Buds2,
+            &message(STATUS_UPDATED, &[0x02, 0x32, 0x00, 0x00, 0x00, 0x10, 0x28]),
+        )
+        .unwrap();
+        let batteries: Vec<_> = status
+            .batteries()
+            .into_iter()
+            .map(|component| (component.name, component.charging))
+            .collect();
+
+        assert_eq!(
+            batteries,
+            [
+                (LEFT_BATTERY.to_string(), Some(false)),
+                (CASE_BATTERY.to_string(), None)
+            ]
+        );
+    }
+
+    #[test]
+    fn models_from_names() {
+        let cases = [
+            ("Galaxy Buds (1A2B)", Some(BudsModel::Buds)),
+            ("Galaxy Buds+ (1A2B)", Some(BudsModel::BudsPlus)),
+            ("Galaxy Buds Live (1A2B)", Some(BudsModel::BudsLive)),
+            ("Galaxy Buds Pro (1A2B)", Some(BudsModel::BudsLive)),
+            ("Galaxy Buds2 Pro (1A2B)", Some(BudsModel::Buds2)),
+            ("Galaxy Buds FE (1A2B)", Some(BudsModel::Buds2)),
+            ("Galaxy Watch4 (1A2B)", None),
+        ];
+
+        for (name, model) in cases {
+            assert_eq!(BudsModel::from_name(name), model, "{name}");
+        }
+        assert_eq!(BudsModel::Buds.service_uuid(), BUDS_SERVICE_UUID);
+        assert_eq!(BudsModel::Buds2.service_uuid(), BUDS_PLUS_SERVICE_UUID);
+    }
+}
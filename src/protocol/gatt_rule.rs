@@ -0,0 +1,146 @@
+//! User-declared decoding of vendor battery characteristics, e.g. a custom
+//! service that holds the cell voltage in millivolts as a little-endian u16.
+
+use super::beacon::VoltageCurve;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeRule {
+    offset: usize,
+    /// 1 to 4 bytes
+    width: usize,
+    endianness: Endianness,
+    /// The raw value times `scale` is the level in percent, or the voltage in
+    /// millivolts when there's a voltage curve.
+    scale: f64,
+    voltage_curve: Option<VoltageCurve>,
+}
+
+impl DecodeRule {
+    /// `None` for a width that doesn't fit into a u32.
+    pub fn new(
+        offset: usize,
+        width: usize,
+        endianness: Endianness,
+        scale: f64,
+        voltage_curve: Option<VoltageCurve>,
+    ) -> Option<Self> {
+        match (1..=4).contains(&width) {
+            true => Some(DecodeRule {
+                offset,
+                width,
+                endianness,
+                scale,
+                voltage_curve,
+            }),
+            false => None,
+        }
+    }
+
+    /// `None` when the value is too short for the declared field.
+    pub fn decode(&self, value: &[u8]) -> Option<u8> {
+        let bytes = value.get(self.offset..self.offset.checked_add(self.width)?)?;
+
+        let raw = match self.endianness {
+            Endianness::Little => bytes.iter().rev().fold(0u32, |raw, byte| raw << 8 | *byte as u32),
+            Endianness::Big => bytes.iter().fold(0u32, |raw, byte| raw << 8 | *byte as u32),
+        };
+        let value = (raw as f64 * self.scale).round();
+
+        match &self.voltage_curve {
+            Some(voltage_curve) => Some(voltage_curve.level(value.clamp(0.0, u16::MAX as f64) as u16)),
+            None => Some(value.clamp(0.0, 100.0) as u8),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(offset: usize, width: usize, endianness: Endianness, scale: f64) -> DecodeRule {
+        DecodeRule::new(offset, width, endianness, scale, None).unwrap()
+    }
+
+    #[test]
+    fn fields_are_read_at_their_offset_and_width() {
+        let value = [0x10, 0x2A, 0x00, 0x01, 0x00, 0x00, 0x00];
+        let cases = [
+            (rule(0, 1, Endianness::Little, 1.0), Some(16)),
+            (rule(1, 1, Endianness::Little, 1.0), Some(42)),
+            (rule(1, 2, Endianness::Little, 1.0), Some(42)),
+            // 0x2A00
+            (rule(1, 2, Endianness::Big, 1.0), Some(100)),
+            // 0x00000001 and 0x01000000
+            (rule(3, 4, Endianness::Little, 1.0), Some(1)),
+            (rule(3, 4, Endianness::Big, 1.0), Some(100)),
+            // 0x002A10 / 1000 = 10.8
+            (rule(0, 3, Endianness::Little, 0.001), Some(11)),
+            (rule(0, 1, Endianness::Little, 0.5), Some(8)),
+            // running past the end
+            (rule(6, 2, Endianness::Little, 1.0), None),
+            (rule(7, 1, Endianness::Little, 1.0), None),
+            (rule(usize::MAX, 1, Endianness::Little, 1.0), None),
+        ];
+
+        for (rule, expected) in cases {
+            assert_eq!(rule.decode(&value), expected, "{rule:?}");
+        }
+    }
+
+    #[test]
+    fn levels_are_clamped() {
+        let cases = [
+            // 250%
+            (rule(0, 1, Endianness::Little, 1.0), Some(100)),
+            (rule(0, 1, Endianness::Little, -1.0), Some(0)),
+            // exactly full
+            (rule(0, 1, Endianness::Little, 0.4), Some(100)),
+        ];
+
+        for (rule, expected) in cases {
+            assert_eq!(rule.decode(&[250]), expected, "{rule:?}");
+        }
+    }
+
+    #[test]
+    fn voltages_go_through_the_curve() {
+        let voltage_curve = VoltageCurve::new(vec![(3300, 0), (3700, 50), (4200, 100)]);
+        let rule = |scale| DecodeRule::new(0, 2, Endianness::Little, scale, voltage_curve.clone()).unwrap();
+        let cases = [
+            (3700u16, 1.0, Some(50)),
+            (3950, 1.0, Some(75)),
+            (3000, 1.0, Some(0)),
+            // centivolts
+            (395, 10.0, Some(75)),
+            // past u16::MAX once scaled, clamped to the top of the curve
+            (50000, 2.0, Some(100)),
+            (100, -1.0, Some(0)),
+        ];
+
+        for (raw, scale, expected) in cases {
+            assert_eq!(rule(scale).decode(&raw.to_le_bytes()), expected, "{raw} * {scale}");
+        }
+    }
+
+    #[test]
+    fn width_is_one_to_four_bytes() {
+        for width in [0, 5, 8] {
+            assert!(
+                DecodeRule::new(0, width, Endianness::Little, 1.0, None).is_none(),
+                "{width}"
+            );
+        }
+        for width in 1..=4 {
+            assert!(
+                DecodeRule::new(0, width, Endianness::Little, 1.0, None).is_some(),
+                "{width}"
+            );
+        }
+    }
+}
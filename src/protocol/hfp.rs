@@ -0,0 +1,175 @@
+//! Battery reports headsets send to the phone (audio gateway) over the
+//! Hands-Free Profile RFCOMM channel.
+//!
+//! e.g.
+//! AT+IPHONEACCEV=2,1,8,2,0       Apple: 2 pairs, battery (key 1) = 8 of 0-9, docked (key 2) = 0
+//! AT+XEVENT=BATTERY,6,11,461,0   Plantronics: level 6 of 11 levels, 461 minutes of talk time, not charging
+//! AT+BIEV=2,85                   HFP 1.7 HF indicator 2 (battery level) = 85%
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HfpBattery {
+    pub level: u8,
+    pub charging: Option<bool>,
+}
+
+const IPHONEACCEV_BATTERY_KEY: u8 = 1;
+const IPHONEACCEV_DOCK_KEY: u8 = 2;
+const BIEV_BATTERY_INDICATOR: u8 = 2;
+
+/// Parses one AT line, with or without the "AT" prefix and trailing CR/LF.
+/// Returns `None` for anything that isn't a battery report.
+pub fn parse_at_battery(line: &str) -> Option<HfpBattery> {
+    let line = line.trim();
+    let line = line
+        .strip_prefix("AT")
+        .or_else(|| line.strip_prefix("at"))
+        .unwrap_or(line);
+    let (command, arguments) = line.split_once('=')?;
+    let arguments: Vec<&str> = arguments.split(',').map(str::trim).collect();
+
+    match command.to_ascii_uppercase().as_str() {
+        "+IPHONEACCEV" => parse_iphoneaccev(&arguments),
+        "+XEVENT" => parse_xevent(&arguments),
+        "+BIEV" => parse_biev(&arguments),
+        _ => None,
+    }
+}
+
+fn parse_iphoneaccev(arguments: &[&str]) -> Option<HfpBattery> {
+    let (count, pairs) = arguments.split_first()?;
+    let count = count.parse::<usize>().ok()?;
+    if pairs.len() < count * 2 {
+        return None;
+    };
+
+    let (mut level, mut docked) = (None, None);
+    for pair in pairs.chunks_exact(2).take(count) {
+        let (key, value) = (pair[0].parse::<u8>().ok()?, pair[1].parse::<u8>().ok()?);
+        match key {
+            IPHONEACCEV_BATTERY_KEY => level = Some(scale_level(value, 9)?),
+            IPHONEACCEV_DOCK_KEY => docked = Some(value == 1),
+            _ => (),
+        }
+    }
+
+    Some(HfpBattery {
+        level: level?,
+        // a docked accessory is sitting on its charger
+        charging: docked,
+    })
+}
+
+fn parse_xevent(arguments: &[&str]) -> Option<HfpBattery> {
+    let (event, arguments) = arguments.split_first()?;
+    if !event.eq_ignore_ascii_case("BATTERY") {
+        return None;
+    };
+
+    let level = arguments.first()?.parse::<u8>().ok()?;
+    let levels = arguments.get(1)?.parse::<u8>().ok().filter(|levels| *levels > 1)?;
+    let charging = arguments
+        .get(3)
+        .and_then(|charging| charging.parse::<u8>().ok())
+        .map(|charging| charging == 1);
+
+    Some(HfpBattery {
+        level: scale_level(level, levels - 1)?,
+        charging,
+    })
+}
+
+fn parse_biev(arguments: &[&str]) -> Option<HfpBattery> {
+    let indicator = arguments.first()?.parse::<u8>().ok()?;
+    let value = arguments.get(1)?.parse::<u8>().ok()?;
+
+    match indicator == BIEV_BATTERY_INDICATOR && value <= 100 {
+        true => Some(HfpBattery {
+            level: value,
+            charging: None,
+        }),
+        false => None,
+    }
+}
+
+/// Scales a level in 0..=max to a percentage, e.g. Apple's 0-9 where 9 means 100%.
+fn scale_level(value: u8, max: u8) -> Option<u8> {
+    match max > 0 && value <= max {
+        true => Some(((value as u16 * 100 + max as u16 / 2) / max as u16) as u8),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn battery(level: u8, charging: Option<bool>) -> Option<HfpBattery> {
+        Some(HfpBattery { level, charging })
+    }
+
+    #[test]
+    fn iphoneaccev() {
+        let cases = [
+            ("AT+IPHONEACCEV=2,1,8,2,0", battery(89, Some(false))),
+            ("AT+IPHONEACCEV=2,2,1,1,9\r\n", battery(100, Some(true))),
+            ("AT+IPHONEACCEV=1,1,0", battery(0, None)),
+            ("at+iphoneaccev=1,1,5", battery(56, None)),
+            ("+IPHONEACCEV=1,1,5", battery(56, None)),
+            // unknown keys are skipped
+            ("AT+IPHONEACCEV=3,3,1,1,2,2,0", battery(22, Some(false))),
+            // only the announced number of pairs is read
+            ("AT+IPHONEACCEV=1,2,1,1,7", None),
+            ("AT+IPHONEACCEV=2,1,8", None),
+            ("AT+IPHONEACCEV=1,1,10", None),
+            ("AT+IPHONEACCEV=1,2,1", None),
+            ("AT+IPHONEACCEV=x,1,8", None),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(parse_at_battery(line), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn xevent_battery() {
+        let cases = [
+            ("AT+XEVENT=BATTERY,6,11,461,0", battery(60, Some(false))),
+            ("AT+XEVENT=BATTERY,10,11,461,1", battery(100, Some(true))),
+            ("AT+XEVENT=battery,2,5,120,0", battery(50, Some(false))),
+            ("AT+XEVENT=BATTERY,3,4", battery(100, None)),
+            // the number of levels is required
+            ("AT+XEVENT=BATTERY,6", None),
+            ("AT+XEVENT=BATTERY,6,1,461,0", None),
+            ("AT+XEVENT=BATTERY,12,11,461,0", None),
+            ("AT+XEVENT=DON,1", None),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(parse_at_battery(line), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn biev() {
+        let cases = [
+            ("AT+BIEV=2,85", battery(85, None)),
+            ("AT+BIEV=2,0", battery(0, None)),
+            ("AT+BIEV=2, 100", battery(100, None)),
+            ("AT+BIEV=2,101", None),
+            // indicator 1 is enhanced safety
+            ("AT+BIEV=1,1", None),
+            ("AT+BIEV=2", None),
+        ];
+
+        for (line, expected) in cases {
+            assert_eq!(parse_at_battery(line), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn other_lines() {
+        for line in ["AT+CIND?", "AT+BRSF=959", "OK", "", "AT+VGS=8"] {
+            assert_eq!(parse_at_battery(line), None, "{line:?}");
+        }
+    }
+}
@@ -0,0 +1,597 @@
+//! Logitech HID++ 2.0, spoken by Bolt/Unifying receivers and Bluetooth mice
+//! and keyboards that don't implement the Battery Service.
+//!
+//! Every message is [report id, device index, feature index, function << 4 | software id, parameters...]
+//! where the feature index is looked up per device through IRoot (always index 0).
+//!
+//! see: https://lekensteyn.nl/files/logitech/ and Solaar's hidpp20 module
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::beacon::VoltageCurve;
+use crate::provider::BatteryComponent;
+
+pub const SHORT_REPORT_ID: u8 = 0x10;
+pub const LONG_REPORT_ID: u8 = 0x11;
+const SHORT_REPORT_LENGTH: usize = 7;
+const LONG_REPORT_LENGTH: usize = 20;
+const ERROR_FEATURE_INDEX: u8 = 0xFF;
+const HIDPP10_ERROR: u8 = 0x8F;
+
+/// Device index of a device connected directly over Bluetooth or USB.
+pub const DIRECT_DEVICE_INDEX: u8 = 0xFF;
+/// Any non-zero value, zero is reserved for notifications the device sends on its own.
+const SOFTWARE_ID: u8 = 0x0B;
+const REPLY_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub const DEVICE_NAME: u16 = 0x0005;
+pub const BATTERY_STATUS: u16 = 0x1000;
+pub const BATTERY_VOLTAGE: u16 = 0x1001;
+pub const UNIFIED_BATTERY: u16 = 0x1004;
+
+/// IRoot always sits at feature index 0
+const ROOT_FEATURE_INDEX: u8 = 0;
+const ROOT_GET_FEATURE: u8 = 0;
+const DEVICE_NAME_GET_COUNT: u8 = 0;
+const DEVICE_NAME_GET_NAME: u8 = 1;
+const BATTERY_STATUS_GET_LEVEL_STATUS: u8 = 0;
+const BATTERY_VOLTAGE_GET_VOLTAGE: u8 = 0;
+const UNIFIED_BATTERY_GET_CAPABILITIES: u8 = 0;
+const UNIFIED_BATTERY_GET_STATUS: u8 = 1;
+
+#[derive(Debug)]
+pub enum HidppError {
+    Transport(Box<dyn Error + Send + Sync>),
+    /// The device answered with an HID++ 2.0 error code, e.g. 0x05 invalid argument.
+    Device(u8),
+    Timeout,
+}
+
+impl fmt::Display for HidppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HidppError::Transport(err) => write!(f, "HID++ transport error: {err}"),
+            HidppError::Device(code) => write!(f, "HID++ error 0x{code:02X}"),
+            HidppError::Timeout => write!(f, "HID++ device didn't reply"),
+        }
+    }
+}
+
+impl Error for HidppError {}
+
+/// Raw HID access to one receiver or device, implemented over hidraw/HID APIs
+/// or a scripted fake.
+pub trait HidppTransport {
+    fn write(&mut self, report: &[u8]) -> Result<(), HidppError>;
+
+    /// Returns the next input report, or `None` when nothing arrived within `timeout`.
+    fn read(&mut self, timeout: Duration) -> Result<Option<Vec<u8>>, HidppError>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HidppBattery {
+    pub level: u8,
+    pub charging: bool,
+    pub critical: bool,
+}
+
+impl HidppBattery {
+    pub fn component(&self) -> BatteryComponent {
+        let mut component = BatteryComponent::main(self.level);
+        component.charging = Some(self.charging);
+        component.critical = self.critical;
+        component
+    }
+}
+
+pub fn build_request(device_index: u8, feature_index: u8, function: u8, params: &[u8]) -> Vec<u8> {
+    let (report_id, length) = match params.len() <= SHORT_REPORT_LENGTH - 4 {
+        true => (SHORT_REPORT_ID, SHORT_REPORT_LENGTH),
+        false => (LONG_REPORT_ID, LONG_REPORT_LENGTH),
+    };
+
+    let mut report = vec![0; length];
+    report[..4].copy_from_slice(&[report_id, device_index, feature_index, (function << 4) | SOFTWARE_ID]);
+    let params_length = params.len().min(length - 4);
+    report[4..4 + params_length].copy_from_slice(&params[..params_length]);
+    report
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Response<'a> {
+    Reply { feature_index: u8, function: u8, params: &'a [u8] },
+    Error { feature_index: u8, function: u8, code: u8 },
+    /// Anything unsolicited, e.g. a battery notification with software id 0
+    Other,
+}
+
+pub fn parse_response(device_index: u8, report: &[u8]) -> Response<'_> {
+    let [report_id, index, feature_index, function_and_software_id, params @ ..] = report else {
+        return Response::Other;
+    };
+
+    if !matches!(*report_id, SHORT_REPORT_ID | LONG_REPORT_ID) || *index != device_index {
+        return Response::Other;
+    };
+
+    match (*feature_index, params) {
+        // [.., 0xFF, feature index, function | software id, error code], receivers answer
+        // with the HID++ 1.0 error 0x8F for devices that are switched off
+        (ERROR_FEATURE_INDEX | HIDPP10_ERROR, [error_function_and_software_id, code, ..])
+            if error_function_and_software_id & 0x0F == SOFTWARE_ID =>
+        {
+            Response::Error {
+                feature_index: *function_and_software_id,
+                function: error_function_and_software_id >> 4,
+                code: *code,
+            }
+        }
+        (ERROR_FEATURE_INDEX | HIDPP10_ERROR, _) => Response::Other,
+        (feature_index, params) if function_and_software_id & 0x0F == SOFTWARE_ID => Response::Reply {
+            feature_index,
+            function: function_and_software_id >> 4,
+            params,
+        },
+        _ => Response::Other,
+    }
+}
+
+/// Decodes a BATTERY_STATUS GetBatteryLevelStatus reply.
+pub fn decode_battery_status(params: &[u8]) -> Option<HidppBattery> {
+    let [level, _next_level, status, ..] = *params else {
+        return None;
+    };
+
+    // 0 discharging, 1 recharging, 2 almost full, 3 charged, 4 slow recharge,
+    // 5 invalid battery, 6 thermal error
+    match status {
+        0..=4 => Some(HidppBattery {
+            level: level.min(100),
+            charging: matches!(status, 1 | 2 | 4),
+            critical: false,
+        }),
+        _ => None,
+    }
+}
+
+/// Decodes a BATTERY_VOLTAGE GetBatteryInfo reply, the level comes from a Li-ion discharge curve.
+pub fn decode_battery_voltage(params: &[u8]) -> Option<HidppBattery> {
+    let [voltage_high, voltage_low, flags, ..] = *params else {
+        return None;
+    };
+
+    let voltage_mv = u16::from_be_bytes([voltage_high, voltage_low]);
+    if voltage_mv == 0 {
+        return None;
+    };
+
+    Some(HidppBattery {
+        level: li_ion_curve().level(voltage_mv),
+        // bit 7 is external power, bits 0-2 its state: 0 charging, 1 end of charge, 2 charging stopped
+        charging: flags & 0x80 != 0 && flags & 0x07 == 0,
+        critical: flags & 0x80 == 0 && voltage_mv < 3600,
+    })
+}
+
+/// Decodes a UNIFIED_BATTERY GetStatus reply. Devices without the state of
+/// charge capability report 0% and only set the level flags.
+pub fn decode_unified_battery(params: &[u8], state_of_charge_supported: bool) -> Option<HidppBattery> {
+    let [state_of_charge, level_flags, charging_status, ..] = *params else {
+        return None;
+    };
+
+    // bit 0 critical, 1 low, 2 good, 3 full
+    let level = match state_of_charge_supported {
+        true => state_of_charge.min(100),
+        false => match level_flags {
+            flags if flags & 0x08 != 0 => 100,
+            flags if flags & 0x04 != 0 => 60,
+            flags if flags & 0x02 != 0 => 20,
+            flags if flags & 0x01 != 0 => 5,
+            _ => return None,
+        },
+    };
+
+    // 0 discharging, 1 charging, 2 slow charging, 3 complete, 4 error
+    Some(HidppBattery {
+        level,
+        charging: matches!(charging_status, 1 | 2),
+        critical: level_flags & 0x01 != 0,
+    })
+}
+
+fn li_ion_curve() -> VoltageCurve {
+    VoltageCurve::new(vec![
+        (3500, 0),
+        (3579, 2),
+        (3646, 5),
+        (3671, 10),
+        (3717, 20),
+        (3751, 30),
+        (3778, 40),
+        (3811, 50),
+        (3859, 60),
+        (3922, 70),
+        (3989, 80),
+        (4067, 90),
+        (4186, 100),
+    ])
+    .unwrap_or_default()
+}
+
+pub struct HidppDevice<T: HidppTransport> {
+    transport: T,
+    device_index: u8,
+    /// `None` for features IRoot reported as unsupported
+    feature_indexes: HashMap<u16, Option<u8>>,
+}
+
+impl<T: HidppTransport> HidppDevice<T> {
+    pub fn new(transport: T, device_index: u8) -> Self {
+        HidppDevice {
+            transport,
+            device_index,
+            feature_indexes: HashMap::new(),
+        }
+    }
+
+    /// Sends one request and waits for its reply, skipping notifications and
+    /// replies to other software in between.
+    pub fn request(&mut self, feature_index: u8, function: u8, params: &[u8]) -> Result<Vec<u8>, HidppError> {
+        self.transport
+            .write(&build_request(self.device_index, feature_index, function, params))?;
+
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let Some(report) = self.transport.read(timeout)? else {
+                return Err(HidppError::Timeout);
+            };
+
+            match parse_response(self.device_index, &report) {
+                Response::Reply {
+                    feature_index: reply_feature_index,
+                    function: reply_function,
+                    params,
+                } if reply_feature_index == feature_index && reply_function == function => return Ok(params.to_vec()),
+                Response::Error {
+                    feature_index: error_feature_index,
+                    function: error_function,
+                    code,
+                } if error_feature_index == feature_index && error_function == function => {
+                    return Err(HidppError::Device(code))
+                }
+                _ if timeout.is_zero() => return Err(HidppError::Timeout),
+                _ => (),
+            }
+        }
+    }
+
+    /// Looks the feature up through IRoot.GetFeature, cached per device.
+    pub fn feature_index(&mut self, feature: u16) -> Result<Option<u8>, HidppError> {
+        if let Some(feature_index) = self.feature_indexes.get(&feature) {
+            return Ok(*feature_index);
+        };
+
+        let params = self.request(ROOT_FEATURE_INDEX, ROOT_GET_FEATURE, &feature.to_be_bytes())?;
+        // index 0 is IRoot itself, so it means "not supported" for any other feature
+        let feature_index = params.first().copied().filter(|index| *index != 0);
+        self.feature_indexes.insert(feature, feature_index);
+
+        Ok(feature_index)
+    }
+
+    /// Reads the battery through the best feature the device supports, `None` when it has none.
+    pub fn read_battery(&mut self) -> Result<Option<HidppBattery>, HidppError> {
+        if let Some(feature_index) = self.feature_index(UNIFIED_BATTERY)? {
+            let capabilities = self.request(feature_index, UNIFIED_BATTERY_GET_CAPABILITIES, &[])?;
+            // flags byte after the supported levels, bit 1 is "state of charge in percent"
+            let state_of_charge_supported = capabilities.get(1).is_some_and(|flags| flags & 0x02 != 0);
+            let status = self.request(feature_index, UNIFIED_BATTERY_GET_STATUS, &[])?;
+            return Ok(decode_unified_battery(&status, state_of_charge_supported));
+        };
+
+        if let Some(feature_index) = self.feature_index(BATTERY_STATUS)? {
+            let status = self.request(feature_index, BATTERY_STATUS_GET_LEVEL_STATUS, &[])?;
+            return Ok(decode_battery_status(&status));
+        };
+
+        if let Some(feature_index) = self.feature_index(BATTERY_VOLTAGE)? {
+            let voltage = self.request(feature_index, BATTERY_VOLTAGE_GET_VOLTAGE, &[])?;
+            return Ok(decode_battery_voltage(&voltage));
+        };
+
+        Ok(None)
+    }
+
+    /// Reads the marketing name through DEVICE_NAME, in chunks of up to 16 bytes.
+    pub fn read_name(&mut self) -> Result<Option<String>, HidppError> {
+        let Some(feature_index) = self.feature_index(DEVICE_NAME)? else {
+            return Ok(None);
+        };
+
+        let length = self
+            .request(feature_index, DEVICE_NAME_GET_COUNT, &[])?
+            .first()
+            .copied()
+            .unwrap_or(0) as usize;
+
+        let mut name = Vec::with_capacity(length);
+        while name.len() < length {
+            let chunk = self.request(feature_index, DEVICE_NAME_GET_NAME, &[name.len() as u8])?;
+            let chunk_length = chunk.len().min(length - name.len());
+            if chunk_length == 0 {
+                break;
+            };
+            name.extend_from_slice(&chunk[..chunk_length]);
+        }
+
+        let name = String::from_utf8_lossy(&name).trim_end_matches('\0').to_string();
+        Ok(Some(name).filter(|name| !name.is_empty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::thread;
+
+    /// Answers each expected request with its scripted reports, in order.
+    #[derive(Default)]
+    struct ScriptedTransport {
+        script: VecDeque<(Vec<u8>, Vec<Vec<u8>>)>,
+        pending: VecDeque<Vec<u8>>,
+    }
+
+    impl ScriptedTransport {
+        fn expect(mut self, feature_index: u8, function: u8, params: &[u8], replies: Vec<Vec<u8>>) -> Self {
+            let request = build_request(DIRECT_DEVICE_INDEX, feature_index, function, params);
+            self.script.push_back((request, replies));
+            self
+        }
+    }
+
+    impl HidppTransport for ScriptedTransport {
+        fn write(&mut self, report: &[u8]) -> Result<(), HidppError> {
+            let (request, replies) = self.script.pop_front().expect("unexpected request");
+            assert_eq!(report, request);
+            self.pending.extend(replies);
+            Ok(())
+        }
+
+        fn read(&mut self, _timeout: Duration) -> Result<Option<Vec<u8>>, HidppError> {
+            Ok(self.pending.pop_front())
+        }
+    }
+
+    impl Drop for ScriptedTransport {
+        fn drop(&mut self) {
+            if !thread::panicking() {
+                assert!(self.script.is_empty(), "requests left: {:02X?}", self.script);
+            };
+        }
+    }
+
+    fn reply(feature_index: u8, function: u8, params: &[u8]) -> Vec<u8> {
+        let mut report = vec![0; LONG_REPORT_LENGTH];
+        report[..4].copy_from_slice(&[
+            LONG_REPORT_ID,
+            DIRECT_DEVICE_INDEX,
+            feature_index,
+            (function << 4) | SOFTWARE_ID,
+        ]);
+        report[4..4 + params.len()].copy_from_slice(params);
+        report
+    }
+
+    fn error(feature_index: u8, function: u8, code: u8) -> Vec<u8> {
+        let mut report = vec![0; SHORT_REPORT_LENGTH];
+        report[..6].copy_from_slice(&[
+            SHORT_REPORT_ID,
+            DIRECT_DEVICE_INDEX,
+            ERROR_FEATURE_INDEX,
+            feature_index,
+            (function << 4) | SOFTWARE_ID,
+            code,
+        ]);
+        report
+    }
+
+    fn get_feature(transport: ScriptedTransport, feature: u16, feature_index: u8) -> ScriptedTransport {
+        transport.expect(
+            ROOT_FEATURE_INDEX,
+            ROOT_GET_FEATURE,
+            &feature.to_be_bytes(),
+            vec![reply(
+                ROOT_FEATURE_INDEX,
+                ROOT_GET_FEATURE,
+                &[feature_index, 0x00, 0x01],
+            )],
+        )
+    }
+
+    #[test]
+    fn feature_index_is_looked_up_once() {
+        let transport = get_feature(ScriptedTransport::default(), UNIFIED_BATTERY, 0x08);
+        let transport = get_feature(transport, BATTERY_STATUS, 0x00);
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        for _ in 0..2 {
+            assert_eq!(device.feature_index(UNIFIED_BATTERY).unwrap(), Some(0x08));
+            assert_eq!(device.feature_index(BATTERY_STATUS).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn request_skips_unrelated_reports() {
+        let transport = ScriptedTransport::default().expect(
+            ROOT_FEATURE_INDEX,
+            ROOT_GET_FEATURE,
+            &DEVICE_NAME.to_be_bytes(),
+            vec![
+                // a battery notification (software id 0), another device, another function
+                vec![LONG_REPORT_ID, DIRECT_DEVICE_INDEX, 0x08, 0x00, 0x37, 0x04, 0x00],
+                vec![SHORT_REPORT_ID, 0x01, ROOT_FEATURE_INDEX, SOFTWARE_ID, 0x03, 0x00, 0x00],
+                reply(ROOT_FEATURE_INDEX, 1, &[0x09]),
+                reply(ROOT_FEATURE_INDEX, ROOT_GET_FEATURE, &[0x03]),
+            ],
+        );
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        assert_eq!(device.feature_index(DEVICE_NAME).unwrap(), Some(0x03));
+    }
+
+    #[test]
+    fn request_errors() {
+        let transport = ScriptedTransport::default()
+            .expect(
+                ROOT_FEATURE_INDEX,
+                ROOT_GET_FEATURE,
+                &UNIFIED_BATTERY.to_be_bytes(),
+                vec![error(ROOT_FEATURE_INDEX, ROOT_GET_FEATURE, 0x05)],
+            )
+            .expect(
+                ROOT_FEATURE_INDEX,
+                ROOT_GET_FEATURE,
+                &UNIFIED_BATTERY.to_be_bytes(),
+                Vec::new(),
+            );
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        assert!(matches!(
+            device.feature_index(UNIFIED_BATTERY),
+            Err(HidppError::Device(0x05))
+        ));
+        // failed lookups aren't cached
+        assert!(matches!(
+            device.feature_index(UNIFIED_BATTERY),
+            Err(HidppError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn read_battery_prefers_unified_battery() {
+        let transport = get_feature(ScriptedTransport::default(), UNIFIED_BATTERY, 0x06)
+            .expect(
+                0x06,
+                UNIFIED_BATTERY_GET_CAPABILITIES,
+                &[],
+                vec![reply(0x06, UNIFIED_BATTERY_GET_CAPABILITIES, &[0x0F, 0x02])],
+            )
+            .expect(
+                0x06,
+                UNIFIED_BATTERY_GET_STATUS,
+                &[],
+                vec![reply(0x06, UNIFIED_BATTERY_GET_STATUS, &[0x37, 0x04, 0x01])],
+            );
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        assert_eq!(
+            device.read_battery().unwrap(),
+            Some(HidppBattery {
+                level: 55,
+                charging: true,
+                critical: false,
+            })
+        );
+    }
+
+    #[test]
+    fn read_battery_falls_back_to_older_features() {
+        let transport = get_feature(ScriptedTransport::default(), UNIFIED_BATTERY, 0x00);
+        let transport = get_feature(transport, BATTERY_STATUS, 0x00);
+        let transport = get_feature(transport, BATTERY_VOLTAGE, 0x05).expect(
+            0x05,
+            BATTERY_VOLTAGE_GET_VOLTAGE,
+            &[],
+            vec![reply(0x05, BATTERY_VOLTAGE_GET_VOLTAGE, &[0x0F, 0x50, 0x00])],
+        );
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        // 3920 mV
+        assert_eq!(
+            device.read_battery().unwrap(),
+            Some(HidppBattery {
+                level: 69,
+                charging: false,
+                critical: false,
+            })
+        );
+
+        let transport = get_feature(ScriptedTransport::default(), UNIFIED_BATTERY, 0x00);
+        let transport = get_feature(transport, BATTERY_STATUS, 0x04).expect(
+            0x04,
+            BATTERY_STATUS_GET_LEVEL_STATUS,
+            &[],
+            vec![reply(0x04, BATTERY_STATUS_GET_LEVEL_STATUS, &[0x50, 0x32, 0x01])],
+        );
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        assert_eq!(
+            device
+                .read_battery()
+                .unwrap()
+                .map(|battery| (battery.level, battery.charging)),
+            Some((80, true))
+        );
+    }
+
+    #[test]
+    fn read_battery_without_battery_features() {
+        let transport = get_feature(ScriptedTransport::default(), UNIFIED_BATTERY, 0x00);
+        let transport = get_feature(transport, BATTERY_STATUS, 0x00);
+        let transport = get_feature(transport, BATTERY_VOLTAGE, 0x00);
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        assert_eq!(device.read_battery().unwrap(), None);
+    }
+
+    #[test]
+    fn read_name_in_chunks() {
+        let name = b"MX Anywhere 3 for Mac";
+        let transport = get_feature(ScriptedTransport::default(), DEVICE_NAME, 0x03)
+            .expect(
+                0x03,
+                DEVICE_NAME_GET_COUNT,
+                &[],
+                vec![reply(0x03, DEVICE_NAME_GET_COUNT, &[name.len() as u8])],
+            )
+            .expect(
+                0x03,
+                DEVICE_NAME_GET_NAME,
+                &[0],
+                vec![reply(0x03, DEVICE_NAME_GET_NAME, &name[..16])],
+            )
+            .expect(
+                0x03,
+                DEVICE_NAME_GET_NAME,
+                &[16],
+                vec![reply(0x03, DEVICE_NAME_GET_NAME, &name[16..])],
+            );
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        assert_eq!(device.read_name().unwrap().as_deref(), Some("MX Anywhere 3 for Mac"));
+    }
+
+    #[test]
+    fn read_name_without_device_name() {
+        let transport = get_feature(ScriptedTransport::default(), DEVICE_NAME, 0x00);
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        assert_eq!(device.read_name().unwrap(), None);
+
+        let transport = get_feature(ScriptedTransport::default(), DEVICE_NAME, 0x03).expect(
+            0x03,
+            DEVICE_NAME_GET_COUNT,
+            &[],
+            vec![reply(0x03, DEVICE_NAME_GET_COUNT, &[0x00])],
+        );
+        let mut device = HidppDevice::new(transport, DIRECT_DEVICE_INDEX);
+
+        assert_eq!(device.read_name().unwrap(), None);
+    }
+}
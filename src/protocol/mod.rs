@@ -0,0 +1,60 @@
+//! Pure decoders for the battery formats BlueGauge understands, they only take
+//! bytes (or text) and never talk to a device, so they build on every platform.
+
+pub mod apple;
+// only the WinRT GATT client reads these, BlueZ's battery plugin claims the
+// Battery Service and keeps it off D-Bus
+#[cfg(any(target_os = "windows", test))]
+pub mod battery_service;
+pub mod beacon;
+pub mod controller;
+// the labels come from descriptors only the WinRT GATT client reads
+#[cfg(any(target_os = "windows", test))]
+pub mod descriptor;
+pub mod fast_pair;
+pub mod galaxy_buds;
+pub mod gatt_rule;
+pub mod hfp;
+pub mod hidpp;
+pub mod sony;
+
+/// Little-endian cursor over a characteristic value or advertisement payload.
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        ByteReader { data, position: 0 }
+    }
+
+    pub(crate) fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.position..self.position.checked_add(len)?)?;
+        self.position += len;
+        Some(bytes)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Option<u8> {
+        self.read_bytes(1).map(|bytes| bytes[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Option<u16> {
+        self.read_bytes(2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    #[cfg(any(target_os = "windows", test))]
+    pub(crate) fn read_u24(&mut self) -> Option<u32> {
+        self.read_bytes(3)
+            .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    }
+
+    /// Reads an optional field announced by a flag, `None` means the payload is truncated.
+    #[cfg(any(target_os = "windows", test))]
+    pub(crate) fn read_if<T>(&mut self, present: bool, read: fn(&mut Self) -> Option<T>) -> Option<Option<T>> {
+        match present {
+            true => read(self).map(Some),
+            false => Some(None),
+        }
+    }
+}
@@ -0,0 +1,572 @@
+//! Sony's headphones protocol on its RFCOMM channel, as spoken by the
+//! WH-1000XM3/XM4 and WF-1000XM3/XM4 generation.
+//!
+//! 3E 0C 00 00000002 10 00 1E 3C
+//! |  |  |  |        |     |  end of message
+//! |  |  |  |        |     checksum, sum of type, sequence number, length and payload
+//! |  |  |  |        payload, here the battery level inquiry for a single battery
+//! |  |  |  big-endian payload length
+//! |  |  sequence number, toggles between 0 and 1
+//! |  data type, 0x0C command, 0x01 acknowledgement
+//! start of message
+//!
+//! 0x3C, 0x3D and 0x3E between the markers are sent as 0x3D followed by the
+//! byte with bit 4 cleared.
+//!
+//! see: Gadgetbridge's sony/headphones protocol implementation
+
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::provider::{BatteryComponent, CASE_BATTERY, LEFT_BATTERY, MAIN_BATTERY, RIGHT_BATTERY};
+
+pub const SONY_SERVICE_UUID: u128 = 0x96CC203E_5068_46AD_B32D_E316F5E069BA;
+
+/// Headphones that speak the first version of the protocol; the XM5 generation
+/// moved to a second, incompatible one.
+const SUPPORTED_MODELS: [&str; 6] = ["WH-1000XM3", "WH-1000XM4", "WF-1000XM3", "WF-1000XM4", "WF-SP800N", "WH-XB900N"];
+
+const START_OF_MESSAGE: u8 = 0x3E;
+const END_OF_MESSAGE: u8 = 0x3C;
+const ESCAPE: u8 = 0x3D;
+const ESCAPE_MASK: u8 = 0x10;
+
+const DATA_TYPE_ACK: u8 = 0x01;
+const DATA_TYPE_COMMAND: u8 = 0x0C;
+
+const INIT_REQUEST: u8 = 0x00;
+const INIT_REPLY: u8 = 0x01;
+const BATTERY_LEVEL_REQUEST: u8 = 0x10;
+const BATTERY_LEVEL_REPLY: u8 = 0x11;
+const BATTERY_LEVEL_NOTIFY: u8 = 0x13;
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatteryKind {
+    Single = 0x00,
+    /// Left and right bud
+    Dual = 0x01,
+    Case = 0x02,
+}
+
+impl BatteryKind {
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0x00 => Some(BatteryKind::Single),
+            0x01 => Some(BatteryKind::Dual),
+            0x02 => Some(BatteryKind::Case),
+            _ => None,
+        }
+    }
+}
+
+/// Model names show up as e.g. "WH-1000XM4", or "LE_WH-1000XM4" for the LE half.
+pub fn is_supported_model(name: &str) -> bool {
+    SUPPORTED_MODELS.iter().any(|model| name.contains(model))
+}
+
+/// Truly wireless models report their buds and case, headphones a single battery.
+pub fn get_battery_kinds(name: &str) -> &'static [BatteryKind] {
+    match name.contains("WF-") {
+        true => &[BatteryKind::Dual, BatteryKind::Case],
+        false => &[BatteryKind::Single],
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SonyMessage {
+    pub data_type: u8,
+    pub sequence: u8,
+    pub payload: Vec<u8>,
+}
+
+pub fn encode_message(message: &SonyMessage) -> Vec<u8> {
+    let mut data = vec![message.data_type, message.sequence];
+    data.extend_from_slice(&(message.payload.len() as u32).to_be_bytes());
+    data.extend_from_slice(&message.payload);
+    data.push(checksum(&data));
+
+    let mut frame = vec![START_OF_MESSAGE];
+    for byte in data {
+        match byte {
+            START_OF_MESSAGE | END_OF_MESSAGE | ESCAPE => frame.extend_from_slice(&[ESCAPE, byte & !ESCAPE_MASK]),
+            byte => frame.push(byte),
+        }
+    }
+    frame.push(END_OF_MESSAGE);
+    frame
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+/// Cuts the byte stream of one connection into messages, frames with a wrong
+/// checksum or length are dropped.
+#[derive(Default)]
+pub struct FrameDecoder {
+    /// Unescaped bytes since the last start of message, `None` between frames
+    frame: Option<Vec<u8>>,
+    escaped: bool,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        FrameDecoder::default()
+    }
+
+    /// Appends received bytes and returns every message completed by them.
+    pub fn push(&mut self, data: &[u8]) -> Vec<SonyMessage> {
+        let mut messages = Vec::new();
+
+        for &byte in data {
+            match byte {
+                START_OF_MESSAGE => {
+                    self.frame = Some(Vec::new());
+                    self.escaped = false;
+                }
+                END_OF_MESSAGE => {
+                    if let Some(message) = self.frame.take().and_then(|frame| decode_frame(&frame)) {
+                        messages.push(message);
+                    };
+                }
+                ESCAPE => self.escaped = true,
+                byte => {
+                    if let Some(frame) = self.frame.as_mut() {
+                        frame.push(match self.escaped {
+                            true => byte | ESCAPE_MASK,
+                            false => byte,
+                        });
+                    };
+                    self.escaped = false;
+                }
+            }
+        }
+
+        messages
+    }
+}
+
+/// Checks the length and checksum of an unescaped frame.
+fn decode_frame(frame: &[u8]) -> Option<SonyMessage> {
+    let (&frame_checksum, data) = frame.split_last()?;
+    let [data_type, sequence, l0, l1, l2, l3, ref payload @ ..] = *data else {
+        return None;
+    };
+
+    match checksum(data) == frame_checksum && u32::from_be_bytes([l0, l1, l2, l3]) as usize == payload.len() {
+        true => Some(SonyMessage {
+            data_type,
+            sequence,
+            payload: payload.to_vec(),
+        }),
+        false => None,
+    }
+}
+
+pub fn battery_level_request(kind: BatteryKind) -> Vec<u8> {
+    vec![BATTERY_LEVEL_REQUEST, kind as u8]
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SonyBattery {
+    pub level: u8,
+    pub charging: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatteryReport {
+    Single(SonyBattery),
+    /// A bud that isn't connected reports level 0
+    Dual {
+        left: Option<SonyBattery>,
+        right: Option<SonyBattery>,
+    },
+    Case(SonyBattery),
+}
+
+impl BatteryReport {
+    pub fn batteries(&self) -> Vec<BatteryComponent> {
+        let component = |name: &str, battery: &SonyBattery| {
+            let mut component = BatteryComponent::new(name, battery.level);
+            component.charging = Some(battery.charging);
+            component
+        };
+
+        match self {
+            BatteryReport::Single(battery) => vec![component(MAIN_BATTERY, battery)],
+            BatteryReport::Dual { left, right } => [
+                left.as_ref().map(|battery| component(LEFT_BATTERY, battery)),
+                right.as_ref().map(|battery| component(RIGHT_BATTERY, battery)),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            BatteryReport::Case(battery) => vec![component(CASE_BATTERY, battery)],
+        }
+    }
+}
+
+/// Decodes the payload of a battery level reply (0x11) or notification (0x13):
+/// single and case: id, kind, level, charging
+/// dual:            id, kind, left level, left charging, right level, right charging
+pub fn decode_battery_level(payload: &[u8]) -> Option<BatteryReport> {
+    let [BATTERY_LEVEL_REPLY | BATTERY_LEVEL_NOTIFY, kind, ref levels @ ..] = *payload else {
+        return None;
+    };
+
+    let battery = |level: u8, charging: u8| SonyBattery {
+        level: level.min(100),
+        charging: charging == 1,
+    };
+
+    match (BatteryKind::from_u8(kind)?, levels) {
+        (BatteryKind::Single, &[level, charging, ..]) => Some(BatteryReport::Single(battery(level, charging))),
+        (BatteryKind::Case, &[level, charging, ..]) => Some(BatteryReport::Case(battery(level, charging))),
+        (BatteryKind::Dual, &[left_level, left_charging, right_level, right_charging, ..]) => Some(BatteryReport::Dual {
+            left: (left_level > 0).then(|| battery(left_level, left_charging)),
+            right: (right_level > 0).then(|| battery(right_level, right_charging)),
+        }),
+        _ => None,
+    }
+}
+
+/// One session on an opened channel; reads on the stream are expected to time
+/// out on their own so that a silent device can't block forever.
+pub struct SonyDevice<T> {
+    transport: T,
+    sequence: u8,
+    decoder: FrameDecoder,
+    received: VecDeque<SonyMessage>,
+}
+
+impl<T: Read + Write> SonyDevice<T> {
+    pub fn new(transport: T) -> Self {
+        SonyDevice {
+            transport,
+            sequence: 0,
+            decoder: FrameDecoder::new(),
+            received: VecDeque::new(),
+        }
+    }
+
+    /// Sends the init request the headphones expect before anything else.
+    pub fn init(&mut self) -> io::Result<()> {
+        self.request(&[INIT_REQUEST, 0x00], INIT_REPLY).map(|_| ())
+    }
+
+    pub fn read_battery(&mut self, kind: BatteryKind) -> io::Result<Option<BatteryReport>> {
+        let reply = self.request(&battery_level_request(kind), BATTERY_LEVEL_REPLY)?;
+        Ok(decode_battery_level(&reply))
+    }
+
+    /// Sends a command and waits for its acknowledgement and for the reply
+    /// starting with `reply_id`, acknowledging every command the device sends.
+    fn request(&mut self, payload: &[u8], reply_id: u8) -> io::Result<Vec<u8>> {
+        let request = SonyMessage {
+            data_type: DATA_TYPE_COMMAND,
+            sequence: self.sequence,
+            payload: payload.to_vec(),
+        };
+        self.transport.write_all(&encode_message(&request))?;
+
+        let deadline = Instant::now() + REPLY_TIMEOUT;
+        let mut acknowledged = false;
+        let mut reply = None;
+        while !acknowledged || reply.is_none() {
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(ErrorKind::TimedOut, "Sony device didn't reply"));
+            };
+            let Some(message) = self.receive()? else {
+                continue;
+            };
+
+            match message.data_type {
+                DATA_TYPE_ACK => {
+                    acknowledged = true;
+                    self.sequence = message.sequence;
+                }
+                DATA_TYPE_COMMAND => {
+                    let ack = SonyMessage {
+                        data_type: DATA_TYPE_ACK,
+                        sequence: 1 - message.sequence.min(1),
+                        payload: Vec::new(),
+                    };
+                    self.transport.write_all(&encode_message(&ack))?;
+
+                    if message.payload.first() == Some(&reply_id) {
+                        reply = Some(message.payload);
+                    };
+                }
+                _ => (),
+            }
+        }
+
+        Ok(reply.unwrap_or_default())
+    }
+
+    fn receive(&mut self) -> io::Result<Option<SonyMessage>> {
+        if let Some(message) = self.received.pop_front() {
+            return Ok(Some(message));
+        };
+
+        let mut buf = [0u8; 256];
+        let len = match self.transport.read(&mut buf) {
+            Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+            Ok(len) => len,
+            Err(err) if matches!(err.kind(), ErrorKind::TimedOut | ErrorKind::WouldBlock) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        self.received.extend(self.decoder.push(&buf[..len]));
+        Ok(self.received.pop_front())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(sequence: u8, payload: &[u8]) -> Vec<u8> {
+        encode_message(&SonyMessage {
+            data_type: DATA_TYPE_COMMAND,
+            sequence,
+            payload: payload.to_vec(),
+        })
+    }
+
+    fn ack(sequence: u8) -> Vec<u8> {
+        encode_message(&SonyMessage {
+            data_type: DATA_TYPE_ACK,
+            sequence,
+            payload: Vec::new(),
+        })
+    }
+
+    /// What the device sends back, `None` being a read that times out.
+    type Reads = Vec<Option<Vec<u8>>>;
+
+    /// Answers each expected write with its scripted reads.
+    #[derive(Default)]
+    struct ScriptedChannel {
+        script: VecDeque<(Vec<u8>, Reads)>,
+        pending: VecDeque<Option<Vec<u8>>>,
+    }
+
+    impl ScriptedChannel {
+        fn expect(mut self, write: Vec<u8>, reads: Reads) -> Self {
+            self.script.push_back((write, reads));
+            self
+        }
+    }
+
+    impl Write for ScriptedChannel {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let (write, reads) = self.script.pop_front().expect("unexpected write");
+            assert_eq!(buf, write);
+            self.pending.extend(reads);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for ScriptedChannel {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.pending.pop_front() {
+                Some(Some(data)) => {
+                    buf[..data.len()].copy_from_slice(&data);
+                    Ok(data.len())
+                }
+                Some(None) => Err(ErrorKind::TimedOut.into()),
+                // the device closed the channel
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl Drop for ScriptedChannel {
+        fn drop(&mut self) {
+            if !std::thread::panicking() {
+                assert!(self.script.is_empty(), "writes left: {:02X?}", self.script);
+            };
+        }
+    }
+
+    #[test]
+    fn documented_frame() {
+        assert_eq!(
+            command(0, &battery_level_request(BatteryKind::Single)),
+            [0x3E, 0x0C, 0x00, 0x00, 0x00, 0x00, 0x02, 0x10, 0x00, 0x1E, 0x3C]
+        );
+    }
+
+    #[test]
+    fn markers_are_escaped() {
+        let cases: [(&[u8], &[u8]); 3] = [
+            (&[0x3C], &[0x3D, 0x2C]),
+            (&[0x3D], &[0x3D, 0x2D]),
+            (&[0x3E], &[0x3D, 0x2E]),
+        ];
+
+        for (payload, escaped) in cases {
+            let frame = command(1, payload);
+            assert_eq!(&frame[7..9], escaped, "{payload:02X?}");
+            assert_eq!(frame.iter().filter(|byte| **byte == START_OF_MESSAGE).count(), 1);
+            assert_eq!(frame.iter().filter(|byte| **byte == END_OF_MESSAGE).count(), 1);
+
+            let mut decoder = FrameDecoder::new();
+            assert_eq!(
+                decoder.push(&frame),
+                [SonyMessage {
+                    data_type: DATA_TYPE_COMMAND,
+                    sequence: 1,
+                    payload: payload.to_vec(),
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn battery_payloads_round_trip() {
+        let payloads: [&[u8]; 4] = [
+            &[0x11, 0x00, 0x46, 0x01],
+            // 60% (0x3C) on the left bud, the right one not connected
+            &[0x11, 0x01, 0x3C, 0x00, 0x00, 0x00],
+            &[0x13, 0x01, 0x3E, 0x01, 0x3D, 0x01],
+            &[0x11, 0x02, 0x64, 0x00],
+        ];
+
+        let mut decoder = FrameDecoder::new();
+        let frames: Vec<u8> = payloads.iter().flat_map(|payload| command(0, payload)).collect();
+        let messages = decoder.push(&frames);
+
+        let payloads: Vec<&[u8]> = payloads.to_vec();
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| message.payload.as_slice())
+                .collect::<Vec<_>>(),
+            payloads
+        );
+    }
+
+    #[test]
+    fn battery_levels() {
+        let battery = |level, charging| SonyBattery { level, charging };
+        let cases: [(&[u8], Option<BatteryReport>); 7] = [
+            (
+                &[0x11, 0x00, 0x46, 0x01],
+                Some(BatteryReport::Single(battery(70, true))),
+            ),
+            (
+                &[0x11, 0x01, 0x3C, 0x00, 0x00, 0x00],
+                Some(BatteryReport::Dual {
+                    left: Some(battery(60, false)),
+                    right: None,
+                }),
+            ),
+            (
+                &[0x13, 0x01, 0x3E, 0x01, 0x3D, 0x01],
+                Some(BatteryReport::Dual {
+                    left: Some(battery(62, true)),
+                    right: Some(battery(61, true)),
+                }),
+            ),
+            (
+                &[0x11, 0x02, 0x64, 0x00],
+                Some(BatteryReport::Case(battery(100, false))),
+            ),
+            (&[0x11, 0x01, 0x3C, 0x00], None),
+            (&[0x11, 0x05, 0x3C, 0x00], None),
+            (&[0x20, 0x00, 0x46, 0x01], None),
+        ];
+
+        for (payload, expected) in cases {
+            assert_eq!(decode_battery_level(payload), expected, "{payload:02X?}");
+        }
+    }
+
+    #[test]
+    fn bad_frames_are_dropped() {
+        let mut bad_checksum = command(0, &[0x11, 0x00, 0x46, 0x01]);
+        let checksum = bad_checksum.len() - 2;
+        bad_checksum[checksum] ^= 0x01;
+        let mut bad_length = command(0, &[0x11, 0x00, 0x46, 0x01]);
+        // one byte more than the payload, with the checksum to match
+        bad_length[6] += 1;
+        bad_length[checksum] += 1;
+
+        let mut data = vec![0x00, 0x3C, 0x12];
+        data.extend(bad_checksum);
+        data.extend(bad_length);
+        // a frame cut off by the start of the next one
+        data.extend(&command(0, &[0x11, 0x02, 0x64, 0x00])[..5]);
+        data.extend(command(1, &[0x11, 0x02, 0x64, 0x00]));
+
+        let messages = FrameDecoder::new().push(&data);
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].sequence, 1);
+    }
+
+    #[test]
+    fn init_and_read_battery() {
+        let init_reply = command(0, &[INIT_REPLY, 0x01, 0x00]);
+        let channel = ScriptedChannel::default()
+            .expect(
+                command(0, &[INIT_REQUEST, 0x00]),
+                // a read that times out, then the reply split across reads
+                vec![
+                    None,
+                    Some(ack(1)),
+                    Some(init_reply[..6].to_vec()),
+                    Some(init_reply[6..].to_vec()),
+                ],
+            )
+            .expect(ack(1), Vec::new())
+            // the acknowledgement's sequence number is the one of the next request
+            .expect(
+                command(1, &battery_level_request(BatteryKind::Dual)),
+                vec![
+                    Some(ack(0)),
+                    // a notification before the reply is acknowledged, but isn't the reply
+                    Some(command(1, &[BATTERY_LEVEL_NOTIFY, 0x02, 0x50, 0x01])),
+                ],
+            )
+            .expect(
+                ack(0),
+                vec![Some(command(0, &[BATTERY_LEVEL_REPLY, 0x01, 0x3C, 0x00, 0x3E, 0x01]))],
+            )
+            .expect(ack(1), Vec::new());
+        let mut device = SonyDevice::new(channel);
+
+        device.init().unwrap();
+
+        assert_eq!(
+            device.read_battery(BatteryKind::Dual).unwrap(),
+            Some(BatteryReport::Dual {
+                left: Some(SonyBattery {
+                    level: 60,
+                    charging: false,
+                }),
+                right: Some(SonyBattery {
+                    level: 62,
+                    charging: true,
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn closed_channel_fails_the_request() {
+        let channel = ScriptedChannel::default().expect(command(0, &[INIT_REQUEST, 0x00]), vec![Some(ack(1))]);
+        let mut device = SonyDevice::new(channel);
+
+        assert_eq!(device.init().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+}
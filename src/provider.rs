@@ -0,0 +1,606 @@
+use crate::error::BlueGaugeError;
+use crate::identity::DeviceId;
+use std::error::Error;
+use std::fmt::Display;
+
+pub const MAIN_BATTERY: &str = "main";
+pub const LEFT_BATTERY: &str = "left";
+pub const RIGHT_BATTERY: &str = "right";
+pub const CASE_BATTERY: &str = "case";
+
+/// Why a source knows about a battery but has no level for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnknownReason {
+    /// The device doesn't expose its level, e.g. no Battery Service.
+    Unsupported,
+    /// The device has a level, but reading it failed.
+    ReadFailed,
+    /// The device isn't connected, so there's nothing current to read.
+    Disconnected,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BatteryLevel {
+    Known(u8),
+    Unknown(UnknownReason),
+}
+
+impl BatteryLevel {
+    pub fn value(&self) -> Option<u8> {
+        match self {
+            BatteryLevel::Known(level) => Some(*level),
+            BatteryLevel::Unknown(_) => None,
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        self.value().is_some()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatteryComponent {
+    /// e.g. "main", "left", "right", "case"
+    pub name: String,
+    pub level: BatteryLevel,
+    pub charging: Option<bool>,
+    pub critical: bool,
+    /// e.g. "Li-ion", only known for sources that report it
+    pub chemistry: Option<&'static str>,
+}
+
+impl BatteryComponent {
+    pub fn new(name: &str, level: u8) -> Self {
+        BatteryComponent {
+            name: name.to_string(),
+            level: BatteryLevel::Known(level),
+            charging: None,
+            critical: false,
+            chemistry: None,
+        }
+    }
+
+    /// Sources that only report one value for the whole device.
+    pub fn main(level: u8) -> Self {
+        BatteryComponent::new(MAIN_BATTERY, level)
+    }
+
+    pub fn unknown(name: &str, reason: UnknownReason) -> Self {
+        BatteryComponent {
+            level: BatteryLevel::Unknown(reason),
+            ..BatteryComponent::new(name, 0)
+        }
+    }
+
+    /// Only a known level can be low, a critical flag left over next to an
+    /// unknown one never counts.
+    pub fn is_critical(&self) -> bool {
+        self.critical && self.level.is_known()
+    }
+}
+
+#[derive(Clone)]
+pub struct BluetoothInfo {
+    pub id: DeviceId,
+    pub name: String,
+    pub batteries: Vec<BatteryComponent>,
+    pub status: bool,
+}
+
+pub type ProviderResult = Result<Vec<BluetoothInfo>, Box<dyn Error>>;
+
+/// The devices of every source that could be read, plus what went wrong with the others.
+#[derive(Default)]
+pub struct PollResult {
+    pub devices_info: Vec<BluetoothInfo>,
+    pub errors: Vec<BlueGaugeError>,
+}
+
+pub trait BatteryProvider {
+    /// Short, stable identifier used to turn the source on or off.
+    fn name(&self) -> &'static str;
+
+    fn poll(&mut self) -> ProviderResult;
+
+    /// Reads a single device after a change notification; sources that can't
+    /// address one device on its own fall back to a full poll.
+    fn poll_device(&mut self, id: &DeviceId) -> Result<Option<BluetoothInfo>, Box<dyn Error>> {
+        Ok(self.poll()?.into_iter().find(|info| info.id.matches(id)))
+    }
+
+    /// Devices the last poll failed on without failing the whole source,
+    /// e.g. one headset that didn't answer on its channel.
+    fn take_device_errors(&mut self) -> Vec<(DeviceId, Box<dyn Error>)> {
+        Vec::new()
+    }
+}
+
+/// Stands in for a source that couldn't be started, so it's still listed as
+/// unavailable on every poll and can be turned off like any other.
+struct UnavailableProvider {
+    name: &'static str,
+    error: String,
+}
+
+impl BatteryProvider for UnavailableProvider {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        Err(self.error.clone().into())
+    }
+
+    fn poll_device(&mut self, _id: &DeviceId) -> Result<Option<BluetoothInfo>, Box<dyn Error>> {
+        Ok(None)
+    }
+}
+
+struct RegisteredProvider {
+    provider: Box<dyn BatteryProvider + Send>,
+    enabled: bool,
+}
+
+#[derive(Default)]
+pub struct ProviderRegistry {
+    providers: Vec<RegisteredProvider>,
+    setup_errors: Vec<(&'static str, String)>,
+}
+
+impl ProviderRegistry {
+    pub fn new() -> Self {
+        ProviderRegistry::default()
+    }
+
+    pub fn register<P: BatteryProvider + Send + 'static>(&mut self, provider: P) {
+        self.providers.push(RegisteredProvider {
+            provider: Box::new(provider),
+            enabled: true,
+        });
+    }
+
+    pub fn register_unavailable(&mut self, name: &'static str, error: impl Display) {
+        self.register(UnavailableProvider {
+            name,
+            error: error.to_string(),
+        });
+    }
+
+    /// Reported with every full poll, next to the source errors.
+    pub fn report_setup_error(&mut self, what: &'static str, error: impl Display) {
+        self.setup_errors.push((what, error.to_string()));
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        self.providers
+            .iter_mut()
+            .filter(|p| p.provider.name() == name)
+            .for_each(|p| p.enabled = enabled);
+    }
+
+    pub fn poll(&mut self) -> PollResult {
+        let mut result = PollResult::default();
+        result
+            .errors
+            .extend(self.setup_errors.iter().map(|(what, error)| BlueGaugeError::Setup {
+                what,
+                error: error.clone().into(),
+            }));
+
+        for registered in self.providers.iter_mut().filter(|p| p.enabled) {
+            let provider = registered.provider.name();
+            match registered.provider.poll() {
+                Ok(provider_devices_info) => merge_devices_info(&mut result.devices_info, provider_devices_info),
+                Err(error) => result.errors.push(BlueGaugeError::Source { provider, error }),
+            }
+            result.errors.extend(
+                registered
+                    .provider
+                    .take_device_errors()
+                    .into_iter()
+                    .map(|(device, error)| BlueGaugeError::Device {
+                        provider,
+                        device,
+                        error,
+                    }),
+            );
+        }
+
+        result
+    }
+
+    /// Updates only the given devices in `devices_info`, devices no source
+    /// reports anymore are removed. A device any source failed on keeps its
+    /// previous reading, since the failed source may have been the one reporting it.
+    pub fn poll_devices(&mut self, ids: &[DeviceId], devices_info: &mut Vec<BluetoothInfo>) -> Vec<BlueGaugeError> {
+        let mut errors = Vec::new();
+
+        for id in ids {
+            let mut device_info = Vec::new();
+            let mut failed = false;
+
+            for registered in self.providers.iter_mut().filter(|p| p.enabled) {
+                let provider = registered.provider.name();
+                match registered.provider.poll_device(id) {
+                    Ok(Some(info)) => merge_devices_info(&mut device_info, vec![info]),
+                    Ok(None) => (),
+                    Err(error) => {
+                        failed = true;
+                        errors.push(BlueGaugeError::Device {
+                            provider,
+                            device: *id,
+                            error,
+                        });
+                    }
+                }
+                for (device, error) in registered.provider.take_device_errors() {
+                    failed |= device.matches(id);
+                    errors.push(BlueGaugeError::Device {
+                        provider,
+                        device,
+                        error,
+                    });
+                }
+            }
+
+            let position = devices_info.iter().position(|info| info.id.matches(id));
+            match (position, device_info.pop()) {
+                (Some(_), _) if failed => (),
+                (Some(position), Some(info)) => devices_info[position] = info,
+                (Some(position), None) => {
+                    devices_info.remove(position);
+                }
+                (None, Some(info)) => devices_info.push(info),
+                (None, None) => (),
+            }
+        }
+
+        errors
+    }
+}
+
+/// Providers are polled in registration order, so when two sources report the
+/// same device the earlier one keeps its reading for each battery component and
+/// only adds the components it didn't know about, unless it has no level for a
+/// component the later one does; a device counts as connected if any source says so.
+pub fn merge_devices_info(devices_info: &mut Vec<BluetoothInfo>, new_devices_info: Vec<BluetoothInfo>) {
+    for new_info in new_devices_info {
+        match devices_info.iter_mut().find(|info| is_same_device(info, &new_info)) {
+            Some(info) => {
+                info.id.merge(&new_info.id);
+                info.status |= new_info.status;
+                for battery in new_info.batteries {
+                    match info.batteries.iter_mut().find(|b| b.name == battery.name) {
+                        Some(b) if !b.level.is_known() && battery.level.is_known() => *b = battery,
+                        Some(_) => (),
+                        None => info.batteries.push(battery),
+                    }
+                }
+                // a device-wide unknown from one source is moot once another reads its parts
+                if info.batteries.iter().any(|b| b.level.is_known()) {
+                    info.batteries.retain(|b| b.name != MAIN_BATTERY || b.level.is_known());
+                };
+            }
+            None => devices_info.push(new_info),
+        }
+    }
+}
+
+fn is_same_device(a: &BluetoothInfo, b: &BluetoothInfo) -> bool {
+    match a.id.is_empty() && b.id.is_empty() {
+        // sources without any identity can only be matched by name
+        true => a.name == b.name,
+        false => a.id.matches(&b.id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeProvider {
+        name: &'static str,
+        // None makes every poll fail
+        devices_info: Option<Vec<BluetoothInfo>>,
+    }
+
+    impl BatteryProvider for FakeProvider {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn poll(&mut self) -> ProviderResult {
+            self.devices_info.clone().ok_or_else(|| "unavailable".into())
+        }
+    }
+
+    fn device(address: u64, batteries: Vec<BatteryComponent>, status: bool) -> BluetoothInfo {
+        BluetoothInfo {
+            id: DeviceId::from_address(address),
+            name: format!("Device {address}"),
+            batteries,
+            status,
+        }
+    }
+
+    fn registry(providers: Vec<FakeProvider>) -> ProviderRegistry {
+        let mut registry = ProviderRegistry::new();
+        providers.into_iter().for_each(|provider| registry.register(provider));
+        registry
+    }
+
+    #[test]
+    fn earlier_provider_keeps_its_reading() {
+        let mut registry = registry(vec![
+            FakeProvider {
+                name: "a",
+                devices_info: Some(vec![device(1, vec![BatteryComponent::main(80)], false)]),
+            },
+            FakeProvider {
+                name: "b",
+                devices_info: Some(vec![
+                    device(1, vec![BatteryComponent::main(20)], true),
+                    device(2, vec![BatteryComponent::main(50)], true),
+                ]),
+            },
+        ]);
+
+        let result = registry.poll();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.devices_info.len(), 2);
+        assert_eq!(result.devices_info[0].batteries, vec![BatteryComponent::main(80)]);
+        // connected as soon as one source says so
+        assert!(result.devices_info[0].status);
+        assert_eq!(result.devices_info[1].batteries, vec![BatteryComponent::main(50)]);
+    }
+
+    #[test]
+    fn failing_provider_is_reported_and_skipped() {
+        let mut registry = registry(vec![
+            FakeProvider {
+                name: "broken",
+                devices_info: None,
+            },
+            FakeProvider {
+                name: "ok",
+                devices_info: Some(vec![device(1, vec![BatteryComponent::main(80)], true)]),
+            },
+        ]);
+
+        let result = registry.poll();
+        assert_eq!(result.devices_info.len(), 1);
+        assert_eq!(result.errors.len(), 1);
+        assert!(matches!(
+            result.errors[0],
+            BlueGaugeError::Source { provider: "broken", .. }
+        ));
+        assert!(result.errors[0].device().is_none());
+    }
+
+    #[test]
+    fn disabled_provider_is_not_polled() {
+        let mut registry = registry(vec![
+            FakeProvider {
+                name: "a",
+                devices_info: Some(vec![device(1, vec![BatteryComponent::main(80)], true)]),
+            },
+            FakeProvider {
+                name: "b",
+                devices_info: None,
+            },
+        ]);
+        registry.set_enabled("a", false);
+        registry.set_enabled("b", false);
+
+        let result = registry.poll();
+        assert!(result.devices_info.is_empty());
+        assert!(result.errors.is_empty());
+    }
+
+    #[test]
+    fn merge_adds_components_and_fills_unknown_levels() {
+        let mut devices_info = vec![device(
+            1,
+            vec![
+                BatteryComponent::new(LEFT_BATTERY, 70),
+                BatteryComponent::unknown(CASE_BATTERY, UnknownReason::ReadFailed),
+            ],
+            true,
+        )];
+
+        merge_devices_info(
+            &mut devices_info,
+            vec![device(
+                1,
+                vec![
+                    BatteryComponent::new(LEFT_BATTERY, 10),
+                    BatteryComponent::new(RIGHT_BATTERY, 60),
+                    BatteryComponent::new(CASE_BATTERY, 90),
+                ],
+                false,
+            )],
+        );
+
+        assert_eq!(
+            devices_info[0].batteries,
+            vec![
+                BatteryComponent::new(LEFT_BATTERY, 70),
+                BatteryComponent::new(CASE_BATTERY, 90),
+                BatteryComponent::new(RIGHT_BATTERY, 60)
+            ]
+        );
+        assert!(devices_info[0].status);
+    }
+
+    #[test]
+    fn merge_drops_unknown_main_once_parts_are_known() {
+        let mut devices_info = vec![device(
+            1,
+            vec![BatteryComponent::unknown(MAIN_BATTERY, UnknownReason::Unsupported)],
+            true,
+        )];
+
+        merge_devices_info(
+            &mut devices_info,
+            vec![device(1, vec![BatteryComponent::new(LEFT_BATTERY, 40)], true)],
+        );
+
+        assert_eq!(devices_info[0].batteries, vec![BatteryComponent::new(LEFT_BATTERY, 40)]);
+    }
+
+    #[test]
+    fn merge_matches_by_any_shared_identity() {
+        let container_id = 0x1234_u128;
+        let mut devices_info = vec![BluetoothInfo {
+            id: DeviceId {
+                address: None,
+                container_id: Some(container_id),
+            },
+            ..device(0, vec![BatteryComponent::main(30)], false)
+        }];
+
+        merge_devices_info(
+            &mut devices_info,
+            vec![BluetoothInfo {
+                id: DeviceId {
+                    address: Some(1),
+                    container_id: Some(container_id),
+                },
+                ..device(1, vec![BatteryComponent::main(90)], true)
+            }],
+        );
+
+        assert_eq!(devices_info.len(), 1);
+        assert_eq!(
+            devices_info[0].id,
+            DeviceId {
+                address: Some(1),
+                container_id: Some(container_id)
+            }
+        );
+        assert_eq!(devices_info[0].batteries, vec![BatteryComponent::main(30)]);
+
+        // without any identity only the name can tell
+        let anonymous = |name: &str| BluetoothInfo {
+            id: DeviceId::default(),
+            name: name.to_string(),
+            batteries: vec![BatteryComponent::main(50)],
+            status: true,
+        };
+        merge_devices_info(
+            &mut devices_info,
+            vec![anonymous("Mouse"), anonymous("Mouse"), anonymous("Keyboard")],
+        );
+        assert_eq!(devices_info.len(), 3);
+    }
+
+    #[test]
+    fn poll_devices_updates_removes_and_keeps_failed() {
+        let mut devices_info = vec![
+            device(1, vec![BatteryComponent::main(10)], true),
+            device(2, vec![BatteryComponent::main(20)], true),
+        ];
+        let mut registry = registry(vec![FakeProvider {
+            name: "a",
+            devices_info: Some(vec![
+                device(1, vec![BatteryComponent::main(15)], true),
+                device(3, vec![BatteryComponent::main(30)], true),
+            ]),
+        }]);
+
+        let ids = [
+            DeviceId::from_address(1),
+            DeviceId::from_address(2),
+            DeviceId::from_address(3),
+        ];
+        assert!(registry.poll_devices(&ids, &mut devices_info).is_empty());
+        assert_eq!(
+            devices_info
+                .iter()
+                .map(|info| (info.id.address, info.batteries[0].level))
+                .collect::<Vec<_>>(),
+            vec![(Some(1), BatteryLevel::Known(15)), (Some(3), BatteryLevel::Known(30))]
+        );
+
+        registry.register(FakeProvider {
+            name: "broken",
+            devices_info: None,
+        });
+        devices_info[1].batteries[0].level = BatteryLevel::Known(99);
+        let errors = registry.poll_devices(&[DeviceId::from_address(3)], &mut devices_info);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].device(), Some(&DeviceId::from_address(3)));
+        assert_eq!(devices_info[1].batteries[0].level, BatteryLevel::Known(99));
+    }
+
+    /// Reads device 1 fine but fails on device 2 without failing the poll.
+    struct PartlyFailingProvider;
+
+    impl BatteryProvider for PartlyFailingProvider {
+        fn name(&self) -> &'static str {
+            "partly"
+        }
+
+        fn poll(&mut self) -> ProviderResult {
+            Ok(vec![device(1, vec![BatteryComponent::main(60)], true)])
+        }
+
+        fn take_device_errors(&mut self) -> Vec<(DeviceId, Box<dyn Error>)> {
+            vec![(DeviceId::from_address(2), "no answer".into())]
+        }
+    }
+
+    #[test]
+    fn device_errors_are_reported_next_to_readings() {
+        let mut registry = ProviderRegistry::new();
+        registry.register(PartlyFailingProvider);
+
+        let result = registry.poll();
+        assert_eq!(result.devices_info.len(), 1);
+        assert_eq!(result.errors.len(), 1);
+        assert!(matches!(
+            result.errors[0],
+            BlueGaugeError::Device { provider: "partly", .. }
+        ));
+        assert_eq!(result.errors[0].device(), Some(&DeviceId::from_address(2)));
+
+        // the failed device keeps its reading, the other one is updated
+        let mut devices_info = vec![
+            device(1, vec![BatteryComponent::main(10)], true),
+            device(2, vec![BatteryComponent::main(20)], true),
+        ];
+        let ids = [DeviceId::from_address(1), DeviceId::from_address(2)];
+        let errors = registry.poll_devices(&ids, &mut devices_info);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(devices_info[0].batteries, vec![BatteryComponent::main(60)]);
+        assert_eq!(devices_info[1].batteries, vec![BatteryComponent::main(20)]);
+    }
+
+    #[test]
+    fn unavailable_and_setup_errors_are_reported_on_every_poll() {
+        let mut registry = ProviderRegistry::new();
+        registry.register_unavailable("sony", "no bluetoothd");
+        registry.report_setup_error("config.toml", "expected a table");
+
+        for _ in 0..2 {
+            let result = registry.poll();
+            let errors: Vec<_> = result.errors.iter().map(|err| err.to_string()).collect();
+            assert_eq!(
+                errors,
+                vec![
+                    "config.toml unavailable: expected a table",
+                    "sony source unavailable: no bluetoothd",
+                ]
+            );
+        }
+
+        // a device refresh doesn't repeat them
+        let mut devices_info = Vec::new();
+        assert!(registry
+            .poll_devices(&[DeviceId::from_address(1)], &mut devices_info)
+            .is_empty());
+
+        registry.set_enabled("sony", false);
+        assert_eq!(registry.poll().errors.len(), 1);
+    }
+}
@@ -0,0 +1,105 @@
+use windows::core::GUID;
+use windows::Devices::Bluetooth::BluetoothConnectionStatus;
+use windows::Win32::Devices::Bluetooth::{AF_BTH, BTHPROTO_RFCOMM, SOCKADDR_BTH};
+use windows::Win32::Networking::WinSock::{
+    closesocket, connect, recv, send, setsockopt, socket, WSAGetLastError, WSAStartup, SEND_RECV_FLAGS, SOCKADDR,
+    SOCKET, SOCKET_ERROR, SOCK_STREAM, SOL_SOCKET, SO_RCVTIMEO, WSADATA,
+};
+
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::sync::Once;
+use std::time::Duration;
+
+use crate::bluetooth::find_bt_devices;
+use crate::spp::{PairedDevice, SppConnector, SppStream};
+
+const WINSOCK_VERSION: u16 = 0x0202;
+
+static WINSOCK_STARTUP: Once = Once::new();
+
+/// Winsock's Bluetooth sockets look the RFCOMM channel up through SDP by
+/// themselves when connecting with a service class id and port 0.
+pub struct WinsockSpp;
+
+impl SppConnector for WinsockSpp {
+    fn paired_devices(&mut self) -> Result<Vec<PairedDevice>, Box<dyn Error>> {
+        let mut paired_devices = Vec::new();
+        for bt_device in find_bt_devices()? {
+            paired_devices.push(PairedDevice {
+                address: bt_device.BluetoothAddress()?,
+                name: bt_device.Name()?.to_string(),
+                connected: bt_device.ConnectionStatus()? == BluetoothConnectionStatus::Connected,
+            });
+        }
+
+        Ok(paired_devices)
+    }
+
+    fn connect(&mut self, address: u64, service_uuid: u128, read_timeout: Duration) -> Result<Box<dyn SppStream>, Box<dyn Error>> {
+        WINSOCK_STARTUP.call_once(|| {
+            let mut data = WSADATA::default();
+            unsafe { WSAStartup(WINSOCK_VERSION, &mut data) };
+        });
+
+        let socket = RfcommSocket(unsafe { socket(AF_BTH as i32, SOCK_STREAM, BTHPROTO_RFCOMM as i32)? });
+
+        let timeout = (read_timeout.as_millis() as u32).to_ne_bytes();
+        if unsafe { setsockopt(socket.0, SOL_SOCKET, SO_RCVTIMEO, Some(&timeout)) } == SOCKET_ERROR {
+            return Err(last_socket_error().into());
+        };
+
+        let address = SOCKADDR_BTH {
+            addressFamily: AF_BTH,
+            btAddr: address,
+            serviceClassId: GUID::from_u128(service_uuid),
+            port: 0,
+        };
+        let result = unsafe {
+            connect(
+                socket.0,
+                &address as *const SOCKADDR_BTH as *const SOCKADDR,
+                std::mem::size_of::<SOCKADDR_BTH>() as i32,
+            )
+        };
+        match result == SOCKET_ERROR {
+            true => Err(last_socket_error().into()),
+            false => Ok(Box::new(socket)),
+        }
+    }
+}
+
+struct RfcommSocket(SOCKET);
+
+impl Read for RfcommSocket {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match unsafe { recv(self.0, buf, SEND_RECV_FLAGS(0)) } {
+            SOCKET_ERROR => Err(last_socket_error()),
+            len => Ok(len as usize),
+        }
+    }
+}
+
+impl Write for RfcommSocket {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match unsafe { send(self.0, buf, SEND_RECV_FLAGS(0)) } {
+            SOCKET_ERROR => Err(last_socket_error()),
+            len => Ok(len as usize),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for RfcommSocket {
+    fn drop(&mut self) {
+        unsafe { closesocket(self.0) };
+    }
+}
+
+/// WSAETIMEDOUT and friends map onto the matching `io::ErrorKind`.
+fn last_socket_error() -> io::Error {
+    io::Error::from_raw_os_error(unsafe { WSAGetLastError() }.0)
+}
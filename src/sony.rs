@@ -0,0 +1,148 @@
+use std::error::Error;
+use std::time::Duration;
+
+use crate::identity::DeviceId;
+use crate::protocol::sony::{get_battery_kinds, is_supported_model, SonyDevice, SONY_SERVICE_UUID};
+use crate::provider::{BatteryComponent, BatteryProvider, BluetoothInfo, ProviderResult};
+use crate::spp::{PairedDevice, SppConnector, SppStream};
+
+const READ_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Asks connected Sony headphones for their batteries over the control
+/// channel the Headphones Connect app uses.
+pub struct SonyBatteryProvider<C> {
+    connector: C,
+    // kept as text, the provider has to be Send
+    device_errors: Vec<(DeviceId, String)>,
+}
+
+impl<C: SppConnector> SonyBatteryProvider<C> {
+    pub fn new(connector: C) -> Self {
+        SonyBatteryProvider {
+            connector,
+            device_errors: Vec::new(),
+        }
+    }
+
+    /// `None` for anything but connected supported models, and for headphones
+    /// that failed, their error is kept for `take_device_errors`.
+    fn read_device(&mut self, device: PairedDevice) -> Option<BluetoothInfo> {
+        if !device.connected || !is_supported_model(&device.name) {
+            return None;
+        };
+
+        let batteries = self
+            .connector
+            .connect(device.address, SONY_SERVICE_UUID, READ_TIMEOUT)
+            .and_then(|stream| read_batteries(stream, &device.name));
+
+        let id = DeviceId::from_address(device.address);
+        match batteries {
+            Ok(batteries) if !batteries.is_empty() => Some(BluetoothInfo {
+                id,
+                name: device.name,
+                batteries,
+                status: true,
+            }),
+            Ok(_) => {
+                self.device_errors.push((id, "no battery reported".to_string()));
+                None
+            }
+            Err(err) => {
+                self.device_errors.push((id, err.to_string()));
+                None
+            }
+        }
+    }
+}
+
+impl<C: SppConnector> BatteryProvider for SonyBatteryProvider<C> {
+    fn name(&self) -> &'static str {
+        "sony"
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        let paired_devices = self.connector.paired_devices()?;
+        Ok(paired_devices
+            .into_iter()
+            .filter_map(|device| self.read_device(device))
+            .collect())
+    }
+
+    fn poll_device(&mut self, id: &DeviceId) -> Result<Option<BluetoothInfo>, Box<dyn Error>> {
+        // only paired classic devices have the channel
+        let Some(address) = id.address else {
+            return Ok(None);
+        };
+
+        let paired_device = self.connector.paired_device(address)?;
+        Ok(paired_device.and_then(|device| self.read_device(device)))
+    }
+
+    fn take_device_errors(&mut self) -> Vec<(DeviceId, Box<dyn Error>)> {
+        self.device_errors
+            .drain(..)
+            .map(|(id, error)| (id, error.into()))
+            .collect()
+    }
+}
+
+fn read_batteries(stream: Box<dyn SppStream>, name: &str) -> Result<Vec<BatteryComponent>, Box<dyn Error>> {
+    let mut device = SonyDevice::new(stream);
+    device.init()?;
+
+    let mut batteries = Vec::new();
+    for kind in get_battery_kinds(name) {
+        if let Some(report) = device.read_battery(*kind)? {
+            batteries.extend(report.batteries());
+        };
+    }
+
+    Ok(batteries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spp::FakeConnector;
+
+    #[test]
+    fn poll_device_connects_to_that_device_only() {
+        let mut provider = SonyBatteryProvider::new(FakeConnector {
+            devices: vec![
+                (1, "WH-1000XM4", true),
+                (2, "WF-1000XM3", true),
+                (3, "WF-1000XM4", false),
+                (4, "Galaxy Buds2 Pro", true),
+            ],
+            ..Default::default()
+        });
+
+        let cases = [
+            (DeviceId::from_address(2), vec![2]),
+            // not connected
+            (DeviceId::from_address(3), vec![]),
+            // not a Sony model
+            (DeviceId::from_address(4), vec![]),
+            // not paired
+            (DeviceId::from_address(5), vec![]),
+            // an LE device
+            (
+                DeviceId {
+                    address: None,
+                    container_id: Some(1),
+                },
+                vec![],
+            ),
+        ];
+
+        for (id, connects) in cases {
+            provider.connector.connects.clear();
+            assert!(provider.poll_device(&id).unwrap().is_none());
+            assert_eq!(provider.connector.connects, connects, "{id:?}");
+        }
+
+        let device_errors: Vec<_> = provider.take_device_errors().into_iter().map(|(id, _)| id).collect();
+        assert_eq!(device_errors, vec![DeviceId::from_address(2)]);
+    }
+}
@@ -0,0 +1,72 @@
+use std::error::Error;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A connected RFCOMM channel; reads fail with `TimedOut` or `WouldBlock`
+/// once the timeout given to `connect` passes without data.
+pub trait SppStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> SppStream for T {}
+
+pub struct PairedDevice {
+    pub address: u64,
+    pub name: String,
+    pub connected: bool,
+}
+
+/// Opens vendor RFCOMM services of paired classic devices, e.g. the Galaxy Buds
+/// and Sony headphones control channels.
+pub trait SppConnector {
+    fn paired_devices(&mut self) -> Result<Vec<PairedDevice>, Box<dyn Error>>;
+
+    /// `None` when no paired device has `address`.
+    fn paired_device(&mut self, address: u64) -> Result<Option<PairedDevice>, Box<dyn Error>> {
+        Ok(self.paired_devices()?.into_iter().find(|device| device.address == address))
+    }
+
+    fn connect(&mut self, address: u64, service_uuid: u128, read_timeout: Duration) -> Result<Box<dyn SppStream>, Box<dyn Error>>;
+}
+
+/// Lets several providers share one connector, e.g. one bluetoothd connection
+/// with its registered profiles.
+impl<C: SppConnector> SppConnector for Arc<Mutex<C>> {
+    fn paired_devices(&mut self) -> Result<Vec<PairedDevice>, Box<dyn Error>> {
+        self.lock().map_err(|err| err.to_string())?.paired_devices()
+    }
+
+    fn connect(&mut self, address: u64, service_uuid: u128, read_timeout: Duration) -> Result<Box<dyn SppStream>, Box<dyn Error>> {
+        self.lock()
+            .map_err(|err| err.to_string())?
+            .connect(address, service_uuid, read_timeout)
+    }
+}
+
+/// Lists `devices` (address, name, connected) as paired and records every
+/// connect, which fails since there's nothing behind it.
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeConnector {
+    pub devices: Vec<(u64, &'static str, bool)>,
+    pub connects: Vec<u64>,
+}
+
+#[cfg(test)]
+impl SppConnector for FakeConnector {
+    fn paired_devices(&mut self) -> Result<Vec<PairedDevice>, Box<dyn Error>> {
+        Ok(self
+            .devices
+            .iter()
+            .map(|&(address, name, connected)| PairedDevice {
+                address,
+                name: name.to_string(),
+                connected,
+            })
+            .collect())
+    }
+
+    fn connect(&mut self, address: u64, _service_uuid: u128, _read_timeout: Duration) -> Result<Box<dyn SppStream>, Box<dyn Error>> {
+        self.connects.push(address);
+        Err("connection refused".into())
+    }
+}
@@ -4,28 +4,66 @@ use tao::platform::run_return::EventLoopExtRunReturn;
 use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
 use tray_icon::{TrayIcon, TrayIconBuilder}; // TrayIconEvent
 
-use crate::bluetooth::{find_bluetooth_devices, get_bluetooth_info, BluetoothInfo};
+#[cfg(target_os = "windows")]
+use crate::bluetooth::register_providers;
+#[cfg(target_os = "windows")]
+use crate::bluetooth_watcher::start_change_sources;
+#[cfg(target_os = "linux")]
+use crate::bluez::register_providers;
+#[cfg(target_os = "linux")]
+use crate::bluez_watcher::start_change_sources;
+use crate::config::Config;
+use crate::error::BlueGaugeError;
+use crate::hfp::HfpBatteryProvider;
+use crate::provider::{
+    BatteryComponent, BatteryLevel, BluetoothInfo, ProviderRegistry, UnknownReason, CASE_BATTERY,
+    LEFT_BATTERY, MAIN_BATTERY, RIGHT_BATTERY,
+};
+use crate::watcher::{DeviceEvent, EventCoalescer, Refresh};
 
+use std::error::Error;
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex, MutexGuard};
-use std::thread::{self, sleep};
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
 const ICON_DATA: &[u8] = include_bytes!("../resources/logo.ico");
+// change notifications do the real work, the periodic poll is only a safety net
+// for sources without events (e.g. the PnP battery property)
+const POLL_INTERVAL: Duration = Duration::from_secs(120);
+const EVENT_DEBOUNCE: Duration = Duration::from_millis(500);
+const EVENT_MAX_DELAY: Duration = Duration::from_secs(3);
 
-pub fn show_systray() -> windows::core::Result<()> {
+pub fn show_systray() -> Result<(), Box<dyn Error>> {
     loop_systray()
 }
 
-fn loop_systray() -> windows::core::Result<()> {
+fn loop_systray() -> Result<(), Box<dyn Error>> {
     let mut event_loop = EventLoopBuilder::new().build();
     let event_loop_proxy = event_loop.create_proxy();
 
-    let bluetooth_devices = find_bluetooth_devices()?;
-    let bluetooth_devices_info = get_bluetooth_info(bluetooth_devices.0, bluetooth_devices.1)?;
+    let mut registry = ProviderRegistry::new();
+    let config = Config::load().unwrap_or_else(|err| {
+        registry.report_setup_error("config.toml", err);
+        Config::default()
+    });
+    let (event_sender, event_receiver) = mpsc::channel();
+    register_providers(&mut registry, &event_sender, &config);
+    if let Some(path) = &config.hfp_log {
+        match HfpBatteryProvider::from_log(path) {
+            Ok(provider) => registry.register(provider),
+            Err(err) => registry.register_unavailable("hfp", format!("{}: {err}", path.display())),
+        }
+    };
+    config
+        .disabled_providers
+        .iter()
+        .for_each(|name| registry.set_enabled(name, false));
 
-    let (tooltip, items) = convert_tray_info(bluetooth_devices_info);
-    let tray_tooltip = Arc::new(Mutex::new(tooltip));
-    let menu_items = Arc::new(Mutex::new(items));
+    // the first poll runs on the update thread, so a slow or failing source
+    // can't keep the tray from showing up
+    let tray_tooltip = Arc::new(Mutex::new(Vec::new()));
+    let menu_items = Arc::new(Mutex::new(Vec::new()));
 
     let mut tray_icon = TrayIconBuilder::new()
         .with_menu_on_left_click(true)
@@ -46,7 +84,14 @@ fn loop_systray() -> windows::core::Result<()> {
 
     let tray_tooltip_clone = Arc::clone(&tray_tooltip);
     let menu_items_clone = Arc::clone(&menu_items);
-    thread_update_info(tray_tooltip_clone, menu_items_clone, event_loop_proxy)?;
+    thread_update_info(
+        registry,
+        event_sender,
+        event_receiver,
+        tray_tooltip_clone,
+        menu_items_clone,
+        event_loop_proxy,
+    );
 
     let menu_channel = MenuEvent::receiver();
     // let tray_channel = TrayIconEvent::receiver();
@@ -95,51 +140,157 @@ fn load_icon() -> tray_icon::Icon {
     tray_icon::Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
 }
 
-fn convert_tray_info(bluetooth_devices_info: Vec<BluetoothInfo>) -> (Vec<String>, Vec<String>) {
+fn convert_tray_info(
+    bluetooth_devices_info: &[BluetoothInfo],
+    errors: &[BlueGaugeError],
+) -> (Vec<String>, Vec<String>) {
     let mut tray_tooltip_result = Vec::new();
     let mut menu_items_result = Vec::new();
     for blue_info in bluetooth_devices_info {
+        let battery = format_batteries(&blue_info.batteries);
         match blue_info.status {
             true => {
                 tray_tooltip_result
-                    .insert(0, format!("🟢 {} - {}%", blue_info.name, blue_info.battery));
+                    .insert(0, format!("🟢 {} - {}", blue_info.name, battery));
                 menu_items_result
-                    .insert(0, format!("🔗 {} - {}%", blue_info.name, blue_info.battery))
+                    .insert(0, format!("🔗 {} - {}", blue_info.name, battery))
             }
             false => {
-                tray_tooltip_result.push(format!("🔴 {} - {}%", blue_info.name, blue_info.battery));
-                menu_items_result.push(format!("     {} - {}%", blue_info.name, blue_info.battery))
+                tray_tooltip_result.push(format!("🔴 {} - {}", blue_info.name, battery));
+                menu_items_result.push(format!("     {} - {}", blue_info.name, battery))
             }
         }
     }
+    for error in errors {
+        let warning = match error {
+            BlueGaugeError::Source { provider, .. } => format!("⚠️ {} source unavailable", provider),
+            BlueGaugeError::Device { provider, device, .. } => {
+                match bluetooth_devices_info.iter().find(|info| info.id.matches(device)) {
+                    Some(info) => format!("⚠️ {} - {} read failed", info.name, provider),
+                    None => continue,
+                }
+            }
+            BlueGaugeError::Setup { what, .. } => format!("⚠️ {} unavailable", what),
+        };
+        tray_tooltip_result.push(warning.clone());
+        menu_items_result.push(warning);
+    }
     (tray_tooltip_result, menu_items_result)
 }
 
+/// e.g. "80%" for a single battery, "L 80% · R 5%❗ · Case 40%⚡" for earbuds;
+/// a level the source doesn't expose shows as "N/A", a failed read as "?" and
+/// a disconnected device as "--".
+fn format_batteries(batteries: &[BatteryComponent]) -> String {
+    batteries
+        .iter()
+        .map(|battery| {
+            let level = match battery.level {
+                BatteryLevel::Known(level) => format!("{}%", level),
+                BatteryLevel::Unknown(UnknownReason::Unsupported) => "N/A".to_string(),
+                BatteryLevel::Unknown(UnknownReason::ReadFailed) => "?".to_string(),
+                BatteryLevel::Unknown(UnknownReason::Disconnected) => "--".to_string(),
+            };
+            let marker = match (battery.charging, battery.is_critical()) {
+                (Some(true), _) => "⚡",
+                (_, true) => "❗",
+                _ => "",
+            };
+            match battery.name.as_str() {
+                MAIN_BATTERY => format!("{}{}", level, marker),
+                LEFT_BATTERY => format!("L {}{}", level, marker),
+                RIGHT_BATTERY => format!("R {}{}", level, marker),
+                CASE_BATTERY => format!("Case {}{}", level, marker),
+                name => format!("{} {}{}", name, level, marker),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" · ")
+}
+
 fn thread_update_info(
+    mut registry: ProviderRegistry,
+    event_sender: Sender<DeviceEvent>,
+    event_receiver: Receiver<DeviceEvent>,
     tray_tooltip_clone: Arc<Mutex<Vec<String>>>,
     menu_items_clone: Arc<Mutex<Vec<String>>>,
     event_loop_proxy: EventLoopProxy<()>,
-) -> windows::core::Result<()> {
-    thread::spawn(move || loop {
-        println!("thread: wait");
-        sleep(Duration::from_secs(30));
-        println!("thread: running");
-        let bluetooth_devices = find_bluetooth_devices().unwrap();
-        let bluetooth_devices_info = get_bluetooth_info(bluetooth_devices.0, bluetooth_devices.1).unwrap();
-        let (tooltip, items) = convert_tray_info(bluetooth_devices_info);
-
-        match (tray_tooltip_clone.lock(), menu_items_clone.lock()) {
-            (Ok(mut tray_tooltip), Ok(mut menu_items)) => {
-                *tray_tooltip = tooltip;
-                *menu_items = items;
-                println!("thread: update");
-                event_loop_proxy.send_event(()).ok();
-            }
-            _ => println!("thread: locked"),
-        };
+) {
+    thread::spawn(move || {
+        // event_sender stays alive in this thread, so the channel never disconnects
+        // even when no change source could be started and only polling is left
+        let mut change_sources = start_change_sources(event_sender.clone()).unwrap_or_else(|err| {
+            registry.report_setup_error("change notifications", err);
+            Vec::new()
+        });
+        let mut coalescer = EventCoalescer::new(EVENT_DEBOUNCE, EVENT_MAX_DELAY);
+        let mut bluetooth_devices_info: Vec<BluetoothInfo> = Vec::new();
+        let mut errors: Vec<BlueGaugeError> = Vec::new();
+        let mut next_poll = Instant::now();
+
+        loop {
+            println!("thread: wait");
+            let wake_at = coalescer
+                .deadline()
+                .map_or(next_poll, |deadline| deadline.min(next_poll));
+            if let Ok(event) = event_receiver.recv_timeout(wake_at.saturating_duration_since(Instant::now())) {
+                coalescer.push(event, Instant::now());
+            };
+
+            let now = Instant::now();
+            let refresh = match now >= next_poll {
+                true => {
+                    next_poll = now + POLL_INTERVAL;
+                    coalescer.clear();
+                    Some(Refresh::All)
+                }
+                false => coalescer.take_ready(now),
+            };
+            let Some(refresh) = refresh else {
+                continue;
+            };
+
+            println!("thread: running");
+            match refresh {
+                Refresh::All => {
+                    let result = registry.poll();
+                    bluetooth_devices_info = result.devices_info;
+                    errors = result.errors;
+                    change_sources.iter_mut().for_each(|source| {
+                        source.refresh().ok();
+                    });
+                }
+                Refresh::Devices(ids) => {
+                    // a device that reads fine again drops its earlier failure
+                    errors.retain(|err| !err.device().is_some_and(|device| ids.iter().any(|id| id.matches(device))));
+                    let device_errors = registry.poll_devices(&ids, &mut bluetooth_devices_info);
+                    errors.extend(device_errors);
+                }
+            };
+
+            update_info(&bluetooth_devices_info, &errors, &tray_tooltip_clone, &menu_items_clone, &event_loop_proxy);
+        }
     });
+}
 
-    Ok(())
+fn update_info(
+    bluetooth_devices_info: &[BluetoothInfo],
+    errors: &[BlueGaugeError],
+    tray_tooltip_clone: &Arc<Mutex<Vec<String>>>,
+    menu_items_clone: &Arc<Mutex<Vec<String>>>,
+    event_loop_proxy: &EventLoopProxy<()>,
+) {
+    let (tooltip, items) = convert_tray_info(bluetooth_devices_info, errors);
+
+    match (tray_tooltip_clone.lock(), menu_items_clone.lock()) {
+        (Ok(mut tray_tooltip), Ok(mut menu_items)) => {
+            *tray_tooltip = tooltip;
+            *menu_items = items;
+            println!("thread: update");
+            event_loop_proxy.send_event(()).ok();
+        }
+        _ => println!("thread: locked"),
+    };
 }
 
 fn update_tray_icon(
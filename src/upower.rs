@@ -0,0 +1,390 @@
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+use std::collections::HashMap;
+
+use crate::bluez::get_property;
+use crate::identity::{
+    parse_address, parse_bluez_object_path_address, parse_hid_power_supply_address, DeviceId,
+};
+use crate::provider::{BatteryComponent, BatteryProvider, BluetoothInfo, ProviderResult};
+
+const UPOWER_SERVICE: &str = "org.freedesktop.UPower";
+const UPOWER_PATH: &str = "/org/freedesktop/UPower";
+const UPOWER_INTERFACE: &str = "org.freedesktop.UPower";
+const DEVICE_INTERFACE: &str = "org.freedesktop.UPower.Device";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+// see: https://upower.freedesktop.org/docs/Device.html
+const TYPE_LINE_POWER: u32 = 1;
+const TYPE_BATTERY: u32 = 2;
+const TYPE_UPS: u32 = 3;
+
+const STATE_CHARGING: u32 = 1;
+const STATE_DISCHARGING: u32 = 2;
+const STATE_EMPTY: u32 = 3;
+const STATE_FULLY_CHARGED: u32 = 4;
+const STATE_PENDING_CHARGE: u32 = 5;
+const STATE_PENDING_DISCHARGE: u32 = 6;
+
+pub struct UPowerBatteryProvider {
+    connection: Option<Connection>,
+    service: String,
+}
+
+impl UPowerBatteryProvider {
+    /// Connects to upowerd on the system bus on the first poll.
+    pub fn new() -> Self {
+        UPowerBatteryProvider {
+            connection: None,
+            service: UPOWER_SERVICE.to_string(),
+        }
+    }
+
+    /// Talks to `service` over an existing connection instead, e.g. a session bus
+    /// stand-in that exports fake UPower objects.
+    #[cfg(test)]
+    pub fn with_connection(connection: Connection, service: &str) -> Self {
+        UPowerBatteryProvider {
+            connection: Some(connection),
+            service: service.to_string(),
+        }
+    }
+
+    fn connection(&mut self) -> zbus::Result<&Connection> {
+        if self.connection.is_none() {
+            self.connection = Some(Connection::system()?);
+        }
+        Ok(self.connection.as_ref().unwrap())
+    }
+}
+
+impl Default for UPowerBatteryProvider {
+    fn default() -> Self {
+        UPowerBatteryProvider::new()
+    }
+}
+
+impl BatteryProvider for UPowerBatteryProvider {
+    fn name(&self) -> &'static str {
+        "upower"
+    }
+
+    fn poll(&mut self) -> ProviderResult {
+        let service = self.service.clone();
+        let connection = self.connection()?;
+
+        let upower = Proxy::new(connection, service.as_str(), UPOWER_PATH, UPOWER_INTERFACE)?;
+        let device_paths: Vec<OwnedObjectPath> = upower.call("EnumerateDevices", &())?;
+
+        let mut devices_info = Vec::new();
+        for device_path in device_paths {
+            let properties = Proxy::new(connection, service.as_str(), device_path.as_str(), PROPERTIES_INTERFACE)?;
+            let device: HashMap<String, OwnedValue> = properties.call("GetAll", &(DEVICE_INTERFACE,))?;
+
+            if let Some(device_info) = get_upower_device_info(&device) {
+                devices_info.push(device_info);
+            };
+        }
+
+        Ok(devices_info)
+    }
+}
+
+fn get_upower_device_info(device: &HashMap<String, OwnedValue>) -> Option<BluetoothInfo> {
+    let device_type = get_property::<u32>(device, "Type").unwrap_or(0);
+    // the laptop's own batteries and AC adapters are not peripherals
+    if get_property::<bool>(device, "PowerSupply").unwrap_or(false)
+        || matches!(device_type, TYPE_LINE_POWER | TYPE_BATTERY | TYPE_UPS)
+    {
+        return None;
+    };
+
+    let native_path = get_property::<String>(device, "NativePath").unwrap_or_default();
+    let serial = get_property::<String>(device, "Serial").unwrap_or_default();
+    let name = get_property::<String>(device, "Model")
+        .filter(|model| !model.is_empty())
+        .unwrap_or_else(|| get_upower_type_name(device_type).to_string());
+
+    let percentage = get_property::<f64>(device, "Percentage")?;
+    let state = get_property::<u32>(device, "State").unwrap_or(0);
+
+    Some(BluetoothInfo {
+        id: DeviceId {
+            address: parse_upower_address(&native_path, &serial),
+            container_id: None,
+        },
+        name,
+        batteries: vec![BatteryComponent {
+            charging: get_upower_charging(state),
+            ..BatteryComponent::main(percentage.clamp(0.0, 100.0).round() as u8)
+        }],
+        status: get_property::<bool>(device, "IsPresent").unwrap_or(false),
+    })
+}
+
+/// UPower re-exports BlueZ's Battery1 devices and the kernel's HID batteries, so
+/// the address is recovered from whichever of these it came from to let the
+/// registry merge them with the BlueZ and sysfs readings.
+pub fn parse_upower_address(native_path: &str, serial: &str) -> Option<u64> {
+    parse_bluez_object_path_address(native_path)
+        .or_else(|| parse_hid_power_supply_address(native_path))
+        .or_else(|| parse_address(serial))
+}
+
+fn get_upower_charging(state: u32) -> Option<bool> {
+    match state {
+        STATE_CHARGING | STATE_PENDING_CHARGE => Some(true),
+        STATE_DISCHARGING | STATE_PENDING_DISCHARGE | STATE_EMPTY | STATE_FULLY_CHARGED => Some(false),
+        _ => None,
+    }
+}
+
+fn get_upower_type_name(device_type: u32) -> &'static str {
+    match device_type {
+        5 => "Mouse",
+        6 => "Keyboard",
+        8 => "Phone",
+        9 => "Media Player",
+        10 => "Tablet",
+        12 => "Gaming Input",
+        13 => "Pen",
+        14 => "Touchpad",
+        17 => "Headset",
+        18 => "Speakers",
+        19 => "Headphones",
+        22 => "Remote Control",
+        26 => "Wearable",
+        _ => "Unknown Device",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::{merge_devices_info, MAIN_BATTERY};
+    use crate::test_bus::TestBus;
+    use zbus::zvariant::Value;
+
+    const FAKE_SERVICE: &str = "org.freedesktop.UPower.Fake";
+
+    struct FakeUPower {
+        device_paths: Vec<&'static str>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.UPower")]
+    impl FakeUPower {
+        fn enumerate_devices(&self) -> Vec<OwnedObjectPath> {
+            self.device_paths
+                .iter()
+                .map(|path| OwnedObjectPath::try_from(*path).unwrap())
+                .collect()
+        }
+    }
+
+    struct FakeDevice {
+        native_path: &'static str,
+        model: &'static str,
+        device_type: u32,
+        power_supply: bool,
+        percentage: f64,
+        state: u32,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.UPower.Device")]
+    impl FakeDevice {
+        #[zbus(property)]
+        fn native_path(&self) -> String {
+            self.native_path.to_string()
+        }
+
+        #[zbus(property)]
+        fn serial(&self) -> String {
+            String::new()
+        }
+
+        #[zbus(property)]
+        fn model(&self) -> String {
+            self.model.to_string()
+        }
+
+        #[zbus(property, name = "Type")]
+        fn device_type(&self) -> u32 {
+            self.device_type
+        }
+
+        #[zbus(property)]
+        fn power_supply(&self) -> bool {
+            self.power_supply
+        }
+
+        #[zbus(property)]
+        fn percentage(&self) -> f64 {
+            self.percentage
+        }
+
+        #[zbus(property)]
+        fn state(&self) -> u32 {
+            self.state
+        }
+
+        #[zbus(property)]
+        fn is_present(&self) -> bool {
+            true
+        }
+    }
+
+    fn properties(values: Vec<(&str, Value)>) -> HashMap<String, OwnedValue> {
+        values
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_peripherals_from_fake_upower() {
+        let bus = TestBus::start();
+        let upower = bus.connect();
+        let object_server = upower.object_server();
+        let mouse = "/org/freedesktop/UPower/devices/mouse_dev_A4_C1_38_5D_2B_1E";
+        let laptop = "/org/freedesktop/UPower/devices/battery_BAT0";
+        object_server
+            .at(
+                UPOWER_PATH,
+                FakeUPower {
+                    device_paths: vec![mouse, laptop],
+                },
+            )
+            .unwrap();
+        object_server
+            .at(
+                mouse,
+                FakeDevice {
+                    native_path: "/org/bluez/hci0/dev_A4_C1_38_5D_2B_1E",
+                    model: "MX Master 3",
+                    device_type: 5,
+                    power_supply: false,
+                    percentage: 64.6,
+                    state: STATE_CHARGING,
+                },
+            )
+            .unwrap();
+        object_server
+            .at(
+                laptop,
+                FakeDevice {
+                    native_path: "BAT0",
+                    model: "",
+                    device_type: TYPE_BATTERY,
+                    power_supply: true,
+                    percentage: 90.0,
+                    state: STATE_DISCHARGING,
+                },
+            )
+            .unwrap();
+        drop(object_server);
+        upower.request_name(FAKE_SERVICE).unwrap();
+
+        let devices_info = UPowerBatteryProvider::with_connection(bus.connect(), FAKE_SERVICE)
+            .poll()
+            .unwrap();
+
+        assert_eq!(devices_info.len(), 1);
+        assert_eq!(devices_info[0].name, "MX Master 3");
+        assert_eq!(devices_info[0].id, DeviceId::from_address(0xA4C1_385D_2B1E));
+        assert!(devices_info[0].status);
+        assert_eq!(
+            devices_info[0].batteries,
+            vec![BatteryComponent {
+                charging: Some(true),
+                ..BatteryComponent::main(65)
+            }]
+        );
+    }
+
+    #[test]
+    fn maps_type_and_state() {
+        // (type, power supply, model, state) -> (name, charging), None when it's not a peripheral
+        let cases = [
+            (TYPE_LINE_POWER, false, "", 0, None),
+            (TYPE_BATTERY, false, "", STATE_DISCHARGING, None),
+            (TYPE_UPS, false, "", STATE_DISCHARGING, None),
+            (5, true, "", STATE_DISCHARGING, None),
+            (5, false, "", STATE_DISCHARGING, Some(("Mouse", Some(false)))),
+            (6, false, "K380", STATE_FULLY_CHARGED, Some(("K380", Some(false)))),
+            (12, false, "", STATE_PENDING_CHARGE, Some(("Gaming Input", Some(true)))),
+            (17, false, "", STATE_EMPTY, Some(("Headset", Some(false)))),
+            (
+                19,
+                false,
+                "",
+                STATE_PENDING_DISCHARGE,
+                Some(("Headphones", Some(false))),
+            ),
+            (99, false, "", 0, Some(("Unknown Device", None))),
+        ];
+
+        for (device_type, power_supply, model, state, expected) in cases {
+            let device = properties(vec![
+                ("Type", Value::from(device_type)),
+                ("PowerSupply", Value::from(power_supply)),
+                ("Model", Value::from(model)),
+                ("State", Value::from(state)),
+                ("Percentage", Value::from(50.0)),
+            ]);
+
+            let device_info = get_upower_device_info(&device);
+            assert_eq!(
+                device_info.map(|info| (info.name, info.batteries[0].charging)),
+                expected.map(|(name, charging)| (name.to_string(), charging)),
+                "type {device_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn device_without_percentage_is_skipped() {
+        let device = properties(vec![("Type", Value::from(5u32))]);
+        assert!(get_upower_device_info(&device).is_none());
+    }
+
+    #[test]
+    fn dedupes_against_bluez_and_sysfs() {
+        let cases = [
+            ("/org/bluez/hci0/dev_A4_C1_38_5D_2B_1E", "", Some(0xA4C1_385D_2B1E)),
+            ("hid-a4:c1:38:5d:2b:1e-battery", "", Some(0xA4C1_385D_2B1E)),
+            (
+                "/sys/devices/pci0000:00/usb1/1-1/power_supply/ps",
+                "a4:c1:38:5d:2b:1e",
+                Some(0xA4C1_385D_2B1E),
+            ),
+            ("BAT0", "", None),
+        ];
+        for (native_path, serial, address) in cases {
+            assert_eq!(parse_upower_address(native_path, serial), address, "{native_path}");
+        }
+
+        // BlueZ is registered first, so the mirrored UPower reading folds into its device
+        let mut devices_info = vec![BluetoothInfo {
+            id: DeviceId::from_address(0xA4C1_385D_2B1E),
+            name: "MX Master 3".to_string(),
+            batteries: vec![BatteryComponent::main(60)],
+            status: true,
+        }];
+        let upower_device = properties(vec![
+            ("Type", Value::from(5u32)),
+            ("NativePath", Value::from("/org/bluez/hci0/dev_A4_C1_38_5D_2B_1E")),
+            ("Model", Value::from("MX Master 3")),
+            ("Percentage", Value::from(64.0)),
+        ]);
+        merge_devices_info(
+            &mut devices_info,
+            get_upower_device_info(&upower_device).into_iter().collect(),
+        );
+
+        assert_eq!(devices_info.len(), 1);
+        assert_eq!(devices_info[0].batteries.len(), 1);
+        assert_eq!(devices_info[0].batteries[0].name, MAIN_BATTERY);
+        assert_eq!(devices_info[0].batteries[0].level.value(), Some(60));
+    }
+}
@@ -0,0 +1,192 @@
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::identity::DeviceId;
+
+pub enum DeviceEvent {
+    /// Connection status or battery level of one device changed.
+    Changed(DeviceId),
+    /// A device was paired or removed, everything has to be enumerated again.
+    Rescan,
+}
+
+pub enum Refresh {
+    All,
+    Devices(Vec<DeviceId>),
+}
+
+/// A platform backend feeding `DeviceEvent`s into the refresh thread.
+pub trait ChangeSource {
+    /// Called after every full rescan, so that newly paired devices get watched too.
+    fn refresh(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// Collects bursts of events (e.g. a headset reconnecting fires several
+/// ConnectionStatusChanged and PropertiesChanged in a row) into one refresh.
+///
+/// The refresh is due once no event arrived for `debounce`, or at the latest
+/// `max_delay` after the first event of the burst.
+pub struct EventCoalescer {
+    debounce: Duration,
+    max_delay: Duration,
+    first_event_at: Option<Instant>,
+    last_event_at: Option<Instant>,
+    rescan: bool,
+    devices: Vec<DeviceId>,
+}
+
+impl EventCoalescer {
+    pub fn new(debounce: Duration, max_delay: Duration) -> Self {
+        EventCoalescer {
+            debounce,
+            max_delay,
+            first_event_at: None,
+            last_event_at: None,
+            rescan: false,
+            devices: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, event: DeviceEvent, now: Instant) {
+        self.first_event_at.get_or_insert(now);
+        self.last_event_at = Some(now);
+
+        match event {
+            DeviceEvent::Rescan => self.rescan = true,
+            DeviceEvent::Changed(id) => {
+                match self.devices.iter_mut().find(|device| device.matches(&id)) {
+                    Some(device) => device.merge(&id),
+                    None => self.devices.push(id),
+                }
+            }
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        match (self.first_event_at, self.last_event_at) {
+            (Some(first), Some(last)) => Some((last + self.debounce).min(first + self.max_delay)),
+            _ => None,
+        }
+    }
+
+    pub fn take_ready(&mut self, now: Instant) -> Option<Refresh> {
+        if self.deadline()? > now {
+            return None;
+        };
+
+        let refresh = match self.rescan {
+            true => Refresh::All,
+            false => Refresh::Devices(std::mem::take(&mut self.devices)),
+        };
+        self.clear();
+
+        Some(refresh)
+    }
+
+    /// Drops pending events, e.g. because a periodic full poll just covered them.
+    pub fn clear(&mut self) {
+        self.first_event_at = None;
+        self.last_event_at = None;
+        self.rescan = false;
+        self.devices.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEBOUNCE: Duration = Duration::from_millis(500);
+    const MAX_DELAY: Duration = Duration::from_secs(3);
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn burst_is_due_after_the_debounce() {
+        let start = Instant::now();
+        let mut coalescer = EventCoalescer::new(DEBOUNCE, MAX_DELAY);
+        assert!(coalescer.deadline().is_none());
+        assert!(coalescer.take_ready(start).is_none());
+
+        coalescer.push(DeviceEvent::Changed(DeviceId::from_address(1)), start);
+        coalescer.push(DeviceEvent::Changed(DeviceId::from_address(2)), start + ms(300));
+        coalescer.push(DeviceEvent::Changed(DeviceId::from_address(1)), start + ms(600));
+        assert_eq!(coalescer.deadline(), Some(start + ms(1100)));
+        assert!(coalescer.take_ready(start + ms(1099)).is_none());
+
+        let refresh = coalescer.take_ready(start + ms(1100));
+        assert!(matches!(
+            refresh,
+            Some(Refresh::Devices(ids)) if ids == [DeviceId::from_address(1), DeviceId::from_address(2)]
+        ));
+        // taken along with the refresh
+        assert!(coalescer.deadline().is_none());
+    }
+
+    #[test]
+    fn steady_events_are_due_after_the_max_delay() {
+        let start = Instant::now();
+        let mut coalescer = EventCoalescer::new(DEBOUNCE, MAX_DELAY);
+
+        for i in 0..10 {
+            coalescer.push(DeviceEvent::Changed(DeviceId::from_address(1)), start + ms(400 * i));
+        }
+        assert_eq!(coalescer.deadline(), Some(start + MAX_DELAY));
+        assert!(coalescer.take_ready(start + MAX_DELAY - ms(1)).is_none());
+        assert!(coalescer.take_ready(start + MAX_DELAY).is_some());
+    }
+
+    #[test]
+    fn ids_of_one_device_are_merged() {
+        let start = Instant::now();
+        let mut coalescer = EventCoalescer::new(DEBOUNCE, MAX_DELAY);
+
+        coalescer.push(DeviceEvent::Changed(DeviceId::from_address(1)), start);
+        coalescer.push(
+            DeviceEvent::Changed(DeviceId {
+                address: Some(1),
+                container_id: Some(7),
+            }),
+            start,
+        );
+
+        let refresh = coalescer.take_ready(start + DEBOUNCE);
+        assert!(matches!(
+            refresh,
+            Some(Refresh::Devices(ids)) if ids == [DeviceId { address: Some(1), container_id: Some(7) }]
+        ));
+    }
+
+    #[test]
+    fn removed_device_turns_the_burst_into_a_rescan() {
+        let start = Instant::now();
+        let mut coalescer = EventCoalescer::new(DEBOUNCE, MAX_DELAY);
+
+        coalescer.push(DeviceEvent::Changed(DeviceId::from_address(1)), start);
+        coalescer.push(DeviceEvent::Rescan, start + ms(100));
+        coalescer.push(DeviceEvent::Changed(DeviceId::from_address(2)), start + ms(200));
+
+        assert!(matches!(coalescer.take_ready(start + ms(700)), Some(Refresh::All)));
+
+        // the rescan doesn't leak into the next burst
+        coalescer.push(DeviceEvent::Changed(DeviceId::from_address(3)), start + ms(800));
+        let refresh = coalescer.take_ready(start + ms(1300));
+        assert!(matches!(refresh, Some(Refresh::Devices(ids)) if ids == [DeviceId::from_address(3)]));
+    }
+
+    #[test]
+    fn clear_drops_pending_events() {
+        let start = Instant::now();
+        let mut coalescer = EventCoalescer::new(DEBOUNCE, MAX_DELAY);
+
+        coalescer.push(DeviceEvent::Rescan, start);
+        coalescer.clear();
+
+        assert!(coalescer.deadline().is_none());
+        assert!(coalescer.take_ready(start + MAX_DELAY).is_none());
+    }
+}